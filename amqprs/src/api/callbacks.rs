@@ -9,6 +9,9 @@
 //! After open a connection, immediately register the callbacks by [`Connection::register_callback`].
 //! After open a channel,  immediately register the callbacks by [`Channel::register_callback`].
 //!
+//! Only one callback can be registered per connection or channel, to observe the asynchronous
+//! messages from multiple places, subscribe to [`events`] instead.
+//!
 //! # Examples
 //! See [`DefaultConnectionCallback`] and [`DefaultChannelCallback`] for simple example.
 //!
@@ -19,8 +22,12 @@
 //! [`Connection::register_callback`]: ../connection/struct.Connection.html#method.register_callback
 //! [`Channel`]: ../channel/struct.Channel.html
 //! [`Channel::register_callback`]: ../channel/struct.Channel.html#method.register_callback
+//! [`events`]: ../events/index.html
 
-use super::{channel::Channel, connection::Connection};
+use super::{
+    channel::{Channel, UnsettledDelivery},
+    connection::Connection,
+};
 use crate::api::Result;
 use crate::frame::Cancel;
 use crate::{
@@ -47,6 +54,11 @@ pub trait ConnectionCallback {
     /// If returns [`Err`], no reply to server, which means server won't know
     /// whether the request has been received by client, and may consider
     /// the connection isn't shutdown.
+    ///
+    /// It is also invoked when client closes the connection because server
+    /// violates the protocol, e.g. sends an unexpected or malformed frame.
+    /// The `close` then contains the reply code (505 or 501) and text sent to server,
+    /// and the return value is ignored.
     async fn close(&mut self, connection: &Connection, close: Close) -> Result<()>;
 
     /// Callback to handle connection `blocked` indication from server
@@ -105,6 +117,11 @@ pub trait ChannelCallback {
     /// If returns [`Err`], no reply to server, which means server won't know
     /// whether the request has been received by client, and may consider
     /// the channel isn't closed.
+    ///
+    /// It is also invoked when client closes the channel because server
+    /// violates the protocol, e.g. sends an unexpected content frame.
+    /// The `close` then contains the reply code (505 or 501) and text sent to server,
+    /// and the return value is ignored.
     async fn close(&mut self, channel: &Channel, close: CloseChannel) -> Result<()>;
 
     /// Callback to handle server's request to `cancel` the consumer of current channel.
@@ -156,6 +173,37 @@ pub trait ChannelCallback {
         basic_properties: BasicProperties,
         content: Vec<u8>,
     );
+
+    /// Callback to warn that a delivery is about to pass its ack deadline.
+    ///
+    /// Only occurs for consumers with [`AckDeadline`], the warning time before the deadline is
+    /// configured by the deadline. The default implementation only logs a warning.
+    ///
+    /// The delivery can be settled from the callback, e.g. by [`Channel::basic_nack`].
+    ///
+    /// [`AckDeadline`]: ../channel/struct.AckDeadline.html
+    /// [`Channel::basic_nack`]: ../channel/struct.Channel.html#method.basic_nack
+    async fn ack_deadline_approaching(&mut self, channel: &Channel, delivery: UnsettledDelivery) {
+        #[cfg(feature = "traces")]
+        warn!(
+            "delivery {} of consumer {:?} on channel {} is approaching its ack deadline",
+            delivery.delivery_tag, delivery.consumer_tag, channel
+        );
+    }
+
+    /// Callback to handle a delivery which has passed its ack deadline.
+    ///
+    /// The delivery is already settled by client as configured by [`AckDeadline`] when it is
+    /// invoked. The default implementation only logs a warning.
+    ///
+    /// [`AckDeadline`]: ../channel/struct.AckDeadline.html
+    async fn ack_deadline_expired(&mut self, channel: &Channel, delivery: UnsettledDelivery) {
+        #[cfg(feature = "traces")]
+        warn!(
+            "delivery {} of consumer {:?} on channel {} has passed its ack deadline",
+            delivery.delivery_tag, delivery.consumer_tag, channel
+        );
+    }
 }
 
 /// Default type that implements `ChannelCallback`.
@@ -0,0 +1,1236 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use amqp_serde::types::{FieldValue, LongLongInt};
+
+use crate::api::{
+    error::Error,
+    validation::{check_exchange_name, check_flags, check_short_str},
+    FieldTable, Result,
+};
+
+use super::ExchangeType;
+
+const X_QUEUE_TYPE: &str = "x-queue-type";
+const X_MESSAGE_TTL: &str = "x-message-ttl";
+const X_EXPIRES: &str = "x-expires";
+const X_MAX_LENGTH: &str = "x-max-length";
+const X_MAX_LENGTH_BYTES: &str = "x-max-length-bytes";
+const X_OVERFLOW: &str = "x-overflow";
+const X_DEAD_LETTER_EXCHANGE: &str = "x-dead-letter-exchange";
+const X_DEAD_LETTER_ROUTING_KEY: &str = "x-dead-letter-routing-key";
+const X_DEAD_LETTER_STRATEGY: &str = "x-dead-letter-strategy";
+const X_SINGLE_ACTIVE_CONSUMER: &str = "x-single-active-consumer";
+const X_MAX_PRIORITY: &str = "x-max-priority";
+const X_DELIVERY_LIMIT: &str = "x-delivery-limit";
+const X_INITIAL_CLUSTER_SIZE: &str = "x-initial-cluster-size";
+const X_MAX_AGE: &str = "x-max-age";
+const X_STREAM_MAX_SEGMENT_SIZE_BYTES: &str = "x-stream-max-segment-size-bytes";
+
+const ALTERNATE_EXCHANGE: &str = "alternate-exchange";
+const X_DELAYED_TYPE: &str = "x-delayed-type";
+const HASH_HEADER: &str = "hash-header";
+const HASH_PROPERTY: &str = "hash-property";
+/// Properties supported by `hash-property` of consistent hash exchange.
+const HASH_PROPERTIES: [&str; 3] = ["message_id", "correlation_id", "timestamp"];
+
+const X_MATCH: &str = "x-match";
+
+/// Max TTL and expiry accepted by server, in milliseconds.
+const MAX_EXPIRY_MILLIS: u128 = u32::MAX as u128;
+
+////////////////////////////////////////////////////////////////////////////////
+/// Queue types of RabbitMQ, see [Queues](https://www.rabbitmq.com/queues.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum QueueType {
+    /// Classic queue.
+    Classic,
+    /// Quorum queue, it must be durable, non-exclusive and non-autodelete.
+    Quorum,
+    /// Stream queue, it must be durable, non-exclusive and non-autodelete.
+    Stream,
+}
+
+impl Display for QueueType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QueueType::Classic => write!(f, "classic"),
+            QueueType::Quorum => write!(f, "quorum"),
+            QueueType::Stream => write!(f, "stream"),
+        }
+    }
+}
+
+impl TryFrom<&str> for QueueType {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "classic" => Ok(QueueType::Classic),
+            "quorum" => Ok(QueueType::Quorum),
+            "stream" => Ok(QueueType::Stream),
+            other => Err(invalid_value(X_QUEUE_TYPE, other)),
+        }
+    }
+}
+
+/// Behaviour when a queue reaches its max length, see [Queue Length Limit](https://www.rabbitmq.com/maxlength.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Overflow {
+    /// Drop or dead-letter the oldest messages, it is the default of server.
+    DropHead,
+    /// Reject the newly published messages.
+    RejectPublish,
+    /// Reject and dead-letter the newly published messages, only for classic queues.
+    RejectPublishDlx,
+}
+
+impl Display for Overflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Overflow::DropHead => write!(f, "drop-head"),
+            Overflow::RejectPublish => write!(f, "reject-publish"),
+            Overflow::RejectPublishDlx => write!(f, "reject-publish-dlx"),
+        }
+    }
+}
+
+impl TryFrom<&str> for Overflow {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "drop-head" => Ok(Overflow::DropHead),
+            "reject-publish" => Ok(Overflow::RejectPublish),
+            "reject-publish-dlx" => Ok(Overflow::RejectPublishDlx),
+            other => Err(invalid_value(X_OVERFLOW, other)),
+        }
+    }
+}
+
+/// Dead lettering strategy of quorum queues, see [Dead Lettering](https://www.rabbitmq.com/dlx.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeadLetterStrategy {
+    /// Messages may be lost while being dead-lettered, it is the default of server.
+    AtMostOnce,
+    /// Messages are retained until the target queues confirm them,
+    /// requires [`Overflow::RejectPublish`].
+    AtLeastOnce,
+}
+
+impl Display for DeadLetterStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadLetterStrategy::AtMostOnce => write!(f, "at-most-once"),
+            DeadLetterStrategy::AtLeastOnce => write!(f, "at-least-once"),
+        }
+    }
+}
+
+impl TryFrom<&str> for DeadLetterStrategy {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "at-most-once" => Ok(DeadLetterStrategy::AtMostOnce),
+            "at-least-once" => Ok(DeadLetterStrategy::AtLeastOnce),
+            other => Err(invalid_value(X_DEAD_LETTER_STRATEGY, other)),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Typed arguments of [`queue_declare`], see [`QueueDeclareArguments::queue_arguments`].
+///
+/// Each field is sent as its `x-` argument only if it is set. Arguments which are not typed,
+/// e.g. those of plugins, can be put into `other`.
+///
+/// A declared [`FieldTable`] is parsed back by `QueueArguments::try_from`.
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::{Overflow, QueueArguments, QueueDeclareArguments, QueueType};
+/// # use std::time::Duration;
+/// let args = QueueDeclareArguments::durable_client_named("orders")
+///     .queue_arguments(
+///         QueueArguments::new()
+///             .queue_type(QueueType::Quorum)
+///             .message_ttl(Duration::from_secs(60))
+///             .max_length(10_000)
+///             .overflow(Overflow::RejectPublish)
+///             .dead_letter_exchange("orders.dlx".to_owned())
+///             .delivery_limit(5)
+///             .finish(),
+///     )
+///     .finish();
+/// assert!(args.validate().is_ok());
+/// ```
+///
+/// [`queue_declare`]: struct.Channel.html#method.queue_declare
+/// [`QueueDeclareArguments::queue_arguments`]: struct.QueueDeclareArguments.html#method.queue_arguments
+/// [`FieldTable`]: ../struct.FieldTable.html
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueueArguments {
+    /// `x-queue-type`, server's default type if not set.
+    pub queue_type: Option<QueueType>,
+    /// `x-message-ttl`, in milliseconds precision. Not for streams.
+    pub message_ttl: Option<Duration>,
+    /// `x-expires`, how long an unused queue lives, in milliseconds precision. Not for streams.
+    pub expires: Option<Duration>,
+    /// `x-max-length`, in number of messages. Not for streams.
+    pub max_length: Option<u64>,
+    /// `x-max-length-bytes`, in total bytes of message bodies.
+    pub max_length_bytes: Option<u64>,
+    /// `x-overflow`. Not for streams.
+    pub overflow: Option<Overflow>,
+    /// `x-dead-letter-exchange`. Not for streams.
+    pub dead_letter_exchange: Option<String>,
+    /// `x-dead-letter-routing-key`, requires `dead_letter_exchange`. Not for streams.
+    pub dead_letter_routing_key: Option<String>,
+    /// `x-dead-letter-strategy`, only for quorum queues.
+    pub dead_letter_strategy: Option<DeadLetterStrategy>,
+    /// `x-single-active-consumer`. Not for streams.
+    pub single_active_consumer: Option<bool>,
+    /// `x-max-priority`, only for classic queues.
+    pub max_priority: Option<u8>,
+    /// `x-delivery-limit`, only for quorum queues. A negative value means unlimited,
+    /// which is supported since RabbitMQ 4.0, where the default limit is 20.
+    pub delivery_limit: Option<i64>,
+    /// `x-initial-cluster-size`, only for quorum queues and streams.
+    pub initial_cluster_size: Option<u64>,
+    /// `x-max-age`, in seconds precision, only for streams.
+    pub max_age: Option<Duration>,
+    /// `x-stream-max-segment-size-bytes`, only for streams.
+    pub stream_max_segment_size_bytes: Option<u64>,
+    /// Other arguments which are not typed. Default: empty table.
+    pub other: FieldTable,
+}
+
+impl QueueArguments {
+    /// Create new arguments with nothing set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        queue_type, QueueType
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        message_ttl, Duration
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        expires, Duration
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        max_length, u64
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        max_length_bytes, u64
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        overflow, Overflow
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        dead_letter_exchange, String
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        dead_letter_routing_key, String
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        dead_letter_strategy, DeadLetterStrategy
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        single_active_consumer, bool
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        max_priority, u8
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        delivery_limit, i64
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        initial_cluster_size, u64
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        max_age, Duration
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        stream_max_segment_size_bytes, u64
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        other, FieldTable
+    }
+
+    /// Finish chained configuration and return new arguments.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the arguments, it is also done by [`QueueDeclareArguments::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if a value is out of the range accepted by server,
+    /// `dead_letter_routing_key` is set without `dead_letter_exchange`, or an argument is not
+    /// supported by `queue_type`. If `queue_type` is not set, the type is decided by server,
+    /// so arguments are not checked against it.
+    ///
+    /// [`QueueDeclareArguments::validate`]: struct.QueueDeclareArguments.html#method.validate
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn validate(&self) -> Result<()> {
+        if let Some(message_ttl) = self.message_ttl {
+            check_flags(
+                message_ttl.as_millis() <= MAX_EXPIRY_MILLIS,
+                "x-message-ttl: must not exceed 2^32 - 1 milliseconds",
+            )?;
+        }
+        if let Some(expires) = self.expires {
+            check_flags(
+                expires.as_millis() > 0 && expires.as_millis() <= MAX_EXPIRY_MILLIS,
+                "x-expires: must be between 1 and 2^32 - 1 milliseconds",
+            )?;
+        }
+        if let Some(ref exchange) = self.dead_letter_exchange {
+            check_exchange_name(X_DEAD_LETTER_EXCHANGE, exchange)?;
+        }
+        if let Some(ref routing_key) = self.dead_letter_routing_key {
+            check_short_str(X_DEAD_LETTER_ROUTING_KEY, routing_key)?;
+            check_flags(
+                self.dead_letter_exchange.is_some(),
+                "x-dead-letter-routing-key: requires x-dead-letter-exchange",
+            )?;
+        }
+        check_flags(
+            self.max_priority != Some(0),
+            "x-max-priority: must be between 1 and 255",
+        )?;
+        check_flags(
+            self.initial_cluster_size != Some(0),
+            "x-initial-cluster-size: must be positive",
+        )?;
+        if let Some(max_age) = self.max_age {
+            check_flags(
+                max_age.as_secs() > 0,
+                "x-max-age: must be at least one second",
+            )?;
+        }
+        match self.queue_type {
+            Some(queue_type) => self.check_queue_type(queue_type),
+            None => Ok(()),
+        }
+    }
+
+    /// Check that all set arguments are supported by the queue type.
+    fn check_queue_type(&self, queue_type: QueueType) -> Result<()> {
+        let unsupported = match queue_type {
+            QueueType::Classic => vec![
+                (X_DEAD_LETTER_STRATEGY, self.dead_letter_strategy.is_some()),
+                (X_DELIVERY_LIMIT, self.delivery_limit.is_some()),
+                (X_INITIAL_CLUSTER_SIZE, self.initial_cluster_size.is_some()),
+                (X_MAX_AGE, self.max_age.is_some()),
+                (
+                    X_STREAM_MAX_SEGMENT_SIZE_BYTES,
+                    self.stream_max_segment_size_bytes.is_some(),
+                ),
+            ],
+            QueueType::Quorum => vec![
+                (
+                    X_OVERFLOW,
+                    self.overflow == Some(Overflow::RejectPublishDlx),
+                ),
+                (X_MAX_PRIORITY, self.max_priority.is_some()),
+                (X_MAX_AGE, self.max_age.is_some()),
+                (
+                    X_STREAM_MAX_SEGMENT_SIZE_BYTES,
+                    self.stream_max_segment_size_bytes.is_some(),
+                ),
+            ],
+            QueueType::Stream => vec![
+                (X_MESSAGE_TTL, self.message_ttl.is_some()),
+                (X_EXPIRES, self.expires.is_some()),
+                (X_MAX_LENGTH, self.max_length.is_some()),
+                (X_OVERFLOW, self.overflow.is_some()),
+                (X_DEAD_LETTER_EXCHANGE, self.dead_letter_exchange.is_some()),
+                (
+                    X_DEAD_LETTER_ROUTING_KEY,
+                    self.dead_letter_routing_key.is_some(),
+                ),
+                (X_DEAD_LETTER_STRATEGY, self.dead_letter_strategy.is_some()),
+                (
+                    X_SINGLE_ACTIVE_CONSUMER,
+                    self.single_active_consumer.is_some(),
+                ),
+                (X_MAX_PRIORITY, self.max_priority.is_some()),
+                (X_DELIVERY_LIMIT, self.delivery_limit.is_some()),
+            ],
+        };
+        for (key, is_set) in unsupported {
+            if is_set {
+                return Err(Error::InvalidArgument(format!(
+                    "{}: not supported by {} queue",
+                    key, queue_type
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<QueueArguments> for FieldTable {
+    fn from(args: QueueArguments) -> Self {
+        let mut table = args.other;
+        if let Some(v) = args.queue_type {
+            insert(&mut table, X_QUEUE_TYPE, v.to_string().into());
+        }
+        if let Some(v) = args.message_ttl {
+            insert(&mut table, X_MESSAGE_TTL, millis_value(v));
+        }
+        if let Some(v) = args.expires {
+            insert(&mut table, X_EXPIRES, millis_value(v));
+        }
+        if let Some(v) = args.max_length {
+            insert(&mut table, X_MAX_LENGTH, int_value(v));
+        }
+        if let Some(v) = args.max_length_bytes {
+            insert(&mut table, X_MAX_LENGTH_BYTES, int_value(v));
+        }
+        if let Some(v) = args.overflow {
+            insert(&mut table, X_OVERFLOW, v.to_string().into());
+        }
+        if let Some(v) = args.dead_letter_exchange {
+            insert(&mut table, X_DEAD_LETTER_EXCHANGE, v.into());
+        }
+        if let Some(v) = args.dead_letter_routing_key {
+            insert(&mut table, X_DEAD_LETTER_ROUTING_KEY, v.into());
+        }
+        if let Some(v) = args.dead_letter_strategy {
+            insert(&mut table, X_DEAD_LETTER_STRATEGY, v.to_string().into());
+        }
+        if let Some(v) = args.single_active_consumer {
+            insert(&mut table, X_SINGLE_ACTIVE_CONSUMER, v.into());
+        }
+        if let Some(v) = args.max_priority {
+            insert(&mut table, X_MAX_PRIORITY, int_value(v.into()));
+        }
+        if let Some(v) = args.delivery_limit {
+            insert(&mut table, X_DELIVERY_LIMIT, FieldValue::l(v));
+        }
+        if let Some(v) = args.initial_cluster_size {
+            insert(&mut table, X_INITIAL_CLUSTER_SIZE, int_value(v));
+        }
+        if let Some(v) = args.max_age {
+            insert(&mut table, X_MAX_AGE, format!("{}s", v.as_secs()).into());
+        }
+        if let Some(v) = args.stream_max_segment_size_bytes {
+            insert(&mut table, X_STREAM_MAX_SEGMENT_SIZE_BYTES, int_value(v));
+        }
+        table
+    }
+}
+
+impl TryFrom<&FieldTable> for QueueArguments {
+    type Error = Error;
+
+    /// Parse the arguments of a declared queue, arguments which are not typed are kept in `other`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if a typed argument has a value of wrong type or range.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    fn try_from(table: &FieldTable) -> Result<Self> {
+        let mut args = QueueArguments::new();
+        for (key, value) in table.as_ref() {
+            let name: &String = key.as_ref();
+            match name.as_str() {
+                X_QUEUE_TYPE => {
+                    args.queue_type = Some(QueueType::try_from(str_value(name, value)?)?)
+                }
+                X_MESSAGE_TTL => {
+                    args.message_ttl = Some(Duration::from_millis(u64_value(name, value)?))
+                }
+                X_EXPIRES => args.expires = Some(Duration::from_millis(u64_value(name, value)?)),
+                X_MAX_LENGTH => args.max_length = Some(u64_value(name, value)?),
+                X_MAX_LENGTH_BYTES => args.max_length_bytes = Some(u64_value(name, value)?),
+                X_OVERFLOW => args.overflow = Some(Overflow::try_from(str_value(name, value)?)?),
+                X_DEAD_LETTER_EXCHANGE => {
+                    args.dead_letter_exchange = Some(str_value(name, value)?.to_owned())
+                }
+                X_DEAD_LETTER_ROUTING_KEY => {
+                    args.dead_letter_routing_key = Some(str_value(name, value)?.to_owned())
+                }
+                X_DEAD_LETTER_STRATEGY => {
+                    args.dead_letter_strategy =
+                        Some(DeadLetterStrategy::try_from(str_value(name, value)?)?)
+                }
+                X_SINGLE_ACTIVE_CONSUMER => match value {
+                    FieldValue::t(v) => args.single_active_consumer = Some(*v),
+                    other => return Err(invalid_type(name, "boolean", other)),
+                },
+                X_MAX_PRIORITY => {
+                    let v = u64_value(name, value)?;
+                    args.max_priority =
+                        Some(u8::try_from(v).map_err(|_| invalid_value(name, &v.to_string()))?);
+                }
+                X_DELIVERY_LIMIT => args.delivery_limit = Some(i64_value(name, value)?),
+                X_INITIAL_CLUSTER_SIZE => args.initial_cluster_size = Some(u64_value(name, value)?),
+                X_MAX_AGE => {
+                    let v = str_value(name, value)?;
+                    args.max_age = Some(parse_max_age(v).ok_or_else(|| invalid_value(name, v))?);
+                }
+                X_STREAM_MAX_SEGMENT_SIZE_BYTES => {
+                    args.stream_max_segment_size_bytes = Some(u64_value(name, value)?)
+                }
+                _ => {
+                    args.other.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Typed arguments of [`exchange_declare`], see [`ExchangeDeclareArguments::exchange_arguments`].
+///
+/// Each field is sent as its argument only if it is set. Arguments which are not typed
+/// can be put into `other`.
+///
+/// A declared [`FieldTable`] is parsed back by `ExchangeArguments::try_from`.
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::{ExchangeArguments, ExchangeDeclareArguments, ExchangeType};
+/// let args = ExchangeDeclareArguments::of_type("delayed", ExchangeType::DelayedMessage)
+///     .exchange_arguments(
+///         ExchangeArguments::new()
+///             .delayed_type(ExchangeType::Topic)
+///             .alternate_exchange("unroutable".to_owned())
+///             .finish(),
+///     )
+///     .finish();
+/// assert!(args.validate().is_ok());
+/// ```
+///
+/// [`exchange_declare`]: struct.Channel.html#method.exchange_declare
+/// [`ExchangeDeclareArguments::exchange_arguments`]: struct.ExchangeDeclareArguments.html#method.exchange_arguments
+/// [`FieldTable`]: ../struct.FieldTable.html
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExchangeArguments {
+    /// `alternate-exchange`, where messages unroutable by the exchange are published to.
+    pub alternate_exchange: Option<String>,
+    /// `x-delayed-type`, how `x-delayed-message` exchange routes the messages once delayed,
+    /// required by and only for it.
+    pub delayed_type: Option<ExchangeType>,
+    /// `hash-header`, header to hash instead of routing key, only for `x-consistent-hash` exchange.
+    pub hash_header: Option<String>,
+    /// `hash-property`, property to hash instead of routing key, one of `message_id`,
+    /// `correlation_id` or `timestamp`, only for `x-consistent-hash` exchange.
+    pub hash_property: Option<String>,
+    /// Other arguments which are not typed. Default: empty table.
+    pub other: FieldTable,
+}
+
+impl ExchangeArguments {
+    /// Create new arguments with nothing set.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        alternate_exchange, String
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        delayed_type, ExchangeType
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        hash_header, String
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        hash_property, String
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        other, FieldTable
+    }
+
+    /// Finish chained configuration and return new arguments.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the arguments, it is also done by [`ExchangeDeclareArguments::validate`],
+    /// which also checks them against the exchange type.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes, both `hash_header`
+    /// and `hash_property` are set, `hash_property` is not supported, or `delayed_type` is
+    /// `x-delayed-message` itself.
+    ///
+    /// [`ExchangeDeclareArguments::validate`]: struct.ExchangeDeclareArguments.html#method.validate
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn validate(&self) -> Result<()> {
+        if let Some(ref exchange) = self.alternate_exchange {
+            check_exchange_name(ALTERNATE_EXCHANGE, exchange)?;
+        }
+        if let Some(ref header) = self.hash_header {
+            check_short_str(HASH_HEADER, header)?;
+        }
+        check_flags(
+            !(self.hash_header.is_some() && self.hash_property.is_some()),
+            "hash-header: can not be set along with hash-property",
+        )?;
+        if let Some(ref property) = self.hash_property {
+            check_flags(
+                HASH_PROPERTIES.contains(&property.as_str()),
+                "hash-property: must be one of message_id, correlation_id or timestamp",
+            )?;
+        }
+        check_flags(
+            self.delayed_type != Some(ExchangeType::DelayedMessage),
+            "x-delayed-type: can not be x-delayed-message",
+        )
+    }
+
+    /// Check the arguments are supported by the exchange type.
+    pub(super) fn check_exchange_type(&self, exchange_type: &ExchangeType) -> Result<()> {
+        if *exchange_type == ExchangeType::DelayedMessage {
+            check_flags(
+                self.delayed_type.is_some(),
+                "x-delayed-type: required by x-delayed-message exchange",
+            )?;
+        } else {
+            check_flags(
+                self.delayed_type.is_none(),
+                "x-delayed-type: only for x-delayed-message exchange",
+            )?;
+        }
+        if *exchange_type != ExchangeType::ConsistentHashing {
+            check_flags(
+                self.hash_header.is_none() && self.hash_property.is_none(),
+                "hash-header: only for x-consistent-hash exchange",
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl From<ExchangeArguments> for FieldTable {
+    fn from(args: ExchangeArguments) -> Self {
+        let mut table = args.other;
+        if let Some(v) = args.alternate_exchange {
+            insert(&mut table, ALTERNATE_EXCHANGE, v.into());
+        }
+        if let Some(v) = args.delayed_type {
+            insert(&mut table, X_DELAYED_TYPE, String::from(v).into());
+        }
+        if let Some(v) = args.hash_header {
+            insert(&mut table, HASH_HEADER, v.into());
+        }
+        if let Some(v) = args.hash_property {
+            insert(&mut table, HASH_PROPERTY, v.into());
+        }
+        table
+    }
+}
+
+impl TryFrom<&FieldTable> for ExchangeArguments {
+    type Error = Error;
+
+    /// Parse the arguments of a declared exchange, arguments which are not typed are kept in `other`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if a typed argument is not a string.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    fn try_from(table: &FieldTable) -> Result<Self> {
+        let mut args = ExchangeArguments::new();
+        for (key, value) in table.as_ref() {
+            let name: &String = key.as_ref();
+            match name.as_str() {
+                ALTERNATE_EXCHANGE => {
+                    args.alternate_exchange = Some(str_value(name, value)?.to_owned())
+                }
+                X_DELAYED_TYPE => args.delayed_type = Some(str_value(name, value)?.into()),
+                HASH_HEADER => args.hash_header = Some(str_value(name, value)?.to_owned()),
+                HASH_PROPERTY => args.hash_property = Some(str_value(name, value)?.to_owned()),
+                _ => {
+                    args.other.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// How headers of a message are matched by a binding of headers exchange,
+/// see [Headers Exchange](https://www.rabbitmq.com/tutorials/amqp-concepts.html#exchange-headers).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeadersMatch {
+    /// All headers must match, it is the default of server.
+    All,
+    /// Any header must match.
+    Any,
+    /// Same as `All`, but headers starting with `x-` are also matched.
+    AllWithX,
+    /// Same as `Any`, but headers starting with `x-` are also matched.
+    AnyWithX,
+}
+
+impl HeadersMatch {
+    fn with_x(&self) -> bool {
+        matches!(self, HeadersMatch::AllWithX | HeadersMatch::AnyWithX)
+    }
+}
+
+impl Display for HeadersMatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HeadersMatch::All => write!(f, "all"),
+            HeadersMatch::Any => write!(f, "any"),
+            HeadersMatch::AllWithX => write!(f, "all-with-x"),
+            HeadersMatch::AnyWithX => write!(f, "any-with-x"),
+        }
+    }
+}
+
+impl TryFrom<&str> for HeadersMatch {
+    type Error = Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "all" => Ok(HeadersMatch::All),
+            "any" => Ok(HeadersMatch::Any),
+            "all-with-x" => Ok(HeadersMatch::AllWithX),
+            "any-with-x" => Ok(HeadersMatch::AnyWithX),
+            other => Err(invalid_value(X_MATCH, other)),
+        }
+    }
+}
+
+/// Value of a header to match by [`HeadersBindingArguments`].
+///
+/// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HeaderValue {
+    /// Matches if the header is present, regardless of its value.
+    Present,
+    /// Boolean value.
+    Bool(bool),
+    /// Integer value, sent as signed 64-bit.
+    Int(i64),
+    /// String value.
+    String(String),
+}
+
+impl From<bool> for HeaderValue {
+    fn from(v: bool) -> Self {
+        HeaderValue::Bool(v)
+    }
+}
+
+impl From<i32> for HeaderValue {
+    fn from(v: i32) -> Self {
+        HeaderValue::Int(v.into())
+    }
+}
+
+impl From<i64> for HeaderValue {
+    fn from(v: i64) -> Self {
+        HeaderValue::Int(v)
+    }
+}
+
+impl From<&str> for HeaderValue {
+    fn from(v: &str) -> Self {
+        HeaderValue::String(v.to_owned())
+    }
+}
+
+impl From<String> for HeaderValue {
+    fn from(v: String) -> Self {
+        HeaderValue::String(v)
+    }
+}
+
+impl From<HeaderValue> for FieldValue {
+    fn from(v: HeaderValue) -> Self {
+        match v {
+            HeaderValue::Present => FieldValue::V,
+            HeaderValue::Bool(v) => FieldValue::t(v),
+            HeaderValue::Int(v) => FieldValue::l(v),
+            HeaderValue::String(v) => v.into(),
+        }
+    }
+}
+
+/// Typed arguments of a binding to headers exchange, see `headers_arguments` of
+/// [`QueueBindArguments`] and [`ExchangeBindArguments`].
+///
+/// A binding's [`FieldTable`] is parsed back by `HeadersBindingArguments::try_from`.
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::{HeadersBindingArguments, HeadersMatch, QueueBindArguments};
+/// let args = QueueBindArguments::new("reports", "amq.headers", "")
+///     .headers_arguments(
+///         HeadersBindingArguments::new(HeadersMatch::Any)
+///             .header("format", "pdf")
+///             .header("version", 2)
+///             .finish(),
+///     )
+///     .finish();
+/// assert!(args.validate().is_ok());
+/// ```
+///
+/// [`QueueBindArguments`]: struct.QueueBindArguments.html
+/// [`ExchangeBindArguments`]: struct.ExchangeBindArguments.html
+/// [`FieldTable`]: ../struct.FieldTable.html
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HeadersBindingArguments {
+    /// `x-match`. Default: [`HeadersMatch::All`].
+    pub x_match: HeadersMatch,
+    /// Headers to match. Default: empty.
+    pub headers: BTreeMap<String, HeaderValue>,
+}
+
+impl Default for HeadersBindingArguments {
+    fn default() -> Self {
+        Self::new(HeadersMatch::All)
+    }
+}
+
+impl HeadersBindingArguments {
+    /// Create new arguments without headers.
+    pub fn new(x_match: HeadersMatch) -> Self {
+        Self {
+            x_match,
+            headers: BTreeMap::new(),
+        }
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        x_match, HeadersMatch
+    }
+
+    /// Chainable setter method to add a header to match.
+    pub fn header<V: Into<HeaderValue>>(&mut self, key: &str, value: V) -> &mut Self {
+        self.headers.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Finish chained configuration and return new arguments.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the arguments, it is also done by `validate` of the binding arguments
+    /// if `x-match` is present.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any header name is longer than 255 bytes, or starts
+    /// with `x-` but `x_match` ignores such headers.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn validate(&self) -> Result<()> {
+        for key in self.headers.keys() {
+            check_short_str("header", key)?;
+            if key.starts_with("x-") && !self.x_match.with_x() {
+                return Err(Error::InvalidArgument(format!(
+                    "header: '{}' is ignored by x-match {}",
+                    key, self.x_match
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<HeadersBindingArguments> for FieldTable {
+    fn from(args: HeadersBindingArguments) -> Self {
+        let mut table = FieldTable::new();
+        insert(&mut table, X_MATCH, args.x_match.to_string().into());
+        for (key, value) in args.headers {
+            // name is checked by `validate`
+            if let Ok(key) = key.try_into() {
+                table.insert(key, value.into());
+            }
+        }
+        table
+    }
+}
+
+impl TryFrom<&FieldTable> for HeadersBindingArguments {
+    type Error = Error;
+
+    /// Parse the arguments of a binding to headers exchange.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `x-match` is invalid, or a header value is not
+    /// a boolean, integer, string or void.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    fn try_from(table: &FieldTable) -> Result<Self> {
+        let mut args = HeadersBindingArguments::default();
+        for (key, value) in table.as_ref() {
+            let name: &String = key.as_ref();
+            if name == X_MATCH {
+                args.x_match = HeadersMatch::try_from(str_value(name, value)?)?;
+                continue;
+            }
+            let value =
+                match value {
+                    FieldValue::V => HeaderValue::Present,
+                    FieldValue::t(v) => HeaderValue::Bool(*v),
+                    FieldValue::S(_) => HeaderValue::String(str_value(name, value)?.to_owned()),
+                    other => HeaderValue::Int(i64_value(name, other).map_err(|_| {
+                        invalid_type(name, "boolean, integer, string or void", other)
+                    })?),
+                };
+            args.headers.insert(name.clone(), value);
+        }
+        Ok(args)
+    }
+}
+
+/// Validate arguments of a binding, which are typed as [`HeadersBindingArguments`] if `x-match`
+/// is present.
+pub(super) fn check_binding_arguments(arguments: &FieldTable) -> Result<()> {
+    if arguments.get(&X_MATCH.try_into().unwrap()).is_some() {
+        HeadersBindingArguments::try_from(arguments)?.validate()?;
+    }
+    Ok(())
+}
+
+/////////////////////////////////////////////////////////////////////////////
+// Conversion between typed values and field values.
+
+pub(super) fn insert(table: &mut FieldTable, key: &str, value: FieldValue) {
+    // keys are constants shorter than 255 bytes
+    table.insert(key.try_into().unwrap(), value);
+}
+
+/// Integers are sent as signed 64-bit, which server accepts for all integer arguments.
+fn int_value(v: u64) -> FieldValue {
+    FieldValue::l(LongLongInt::try_from(v).unwrap_or(LongLongInt::MAX))
+}
+
+fn millis_value(v: Duration) -> FieldValue {
+    FieldValue::l(LongLongInt::try_from(v.as_millis()).unwrap_or(LongLongInt::MAX))
+}
+
+pub(super) fn invalid_type(key: &str, expected: &str, value: &FieldValue) -> Error {
+    Error::InvalidArgument(format!("{}: expect {}, but got {}", key, expected, value))
+}
+
+pub(super) fn invalid_value(key: &str, value: &str) -> Error {
+    Error::InvalidArgument(format!("{}: invalid value '{}'", key, value))
+}
+
+/// Accept any integer type, as server does.
+pub(super) fn i64_value(key: &str, value: &FieldValue) -> Result<i64> {
+    match *value {
+        FieldValue::b(v) => Ok(v.into()),
+        FieldValue::B(v) => Ok(v.into()),
+        FieldValue::s(v) => Ok(v.into()),
+        FieldValue::u(v) => Ok(v.into()),
+        FieldValue::I(v) => Ok(v.into()),
+        FieldValue::i(v) => Ok(v.into()),
+        FieldValue::l(v) => Ok(v),
+        ref other => Err(invalid_type(key, "integer", other)),
+    }
+}
+
+pub(super) fn u64_value(key: &str, value: &FieldValue) -> Result<u64> {
+    let v = i64_value(key, value)?;
+    u64::try_from(v).map_err(|_| invalid_value(key, &v.to_string()))
+}
+
+pub(super) fn str_value<'a>(key: &str, value: &'a FieldValue) -> Result<&'a str> {
+    match value {
+        FieldValue::S(v) => {
+            let v: &String = v.as_ref();
+            Ok(v)
+        }
+        other => Err(invalid_type(key, "string", other)),
+    }
+}
+
+/// Parse `x-max-age` of the form `<number><unit>`, unit is one of `Y`, `M`, `D`, `h`, `m`, `s`.
+/// A year is 365 days, and a month is 30 days.
+fn parse_max_age(value: &str) -> Option<Duration> {
+    let unit = value.chars().last()?;
+    let number: u64 = value[..value.len() - unit.len_utf8()].parse().ok()?;
+    let secs = match unit {
+        'Y' => 365 * 24 * 3600,
+        'M' => 30 * 24 * 3600,
+        'D' => 24 * 3600,
+        'h' => 3600,
+        'm' => 60,
+        's' => 1,
+        _ => return None,
+    };
+    Some(Duration::from_secs(number.checked_mul(secs)?))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use amqp_serde::types::FieldValue;
+
+    use super::{
+        parse_max_age, DeadLetterStrategy, ExchangeArguments, HeaderValue, HeadersBindingArguments,
+        HeadersMatch, Overflow, QueueArguments, QueueType, X_MATCH, X_MAX_AGE, X_MESSAGE_TTL,
+        X_QUEUE_TYPE,
+    };
+    use crate::api::{channel::ExchangeType, FieldTable};
+
+    #[test]
+    fn test_queue_arguments_round_trip() {
+        let mut other = FieldTable::new();
+        other.insert(
+            "x-queue-leader-locator".try_into().unwrap(),
+            "balanced".into(),
+        );
+        let args = QueueArguments::new()
+            .queue_type(QueueType::Quorum)
+            .message_ttl(Duration::from_secs(60))
+            .expires(Duration::from_secs(3600))
+            .max_length(100)
+            .max_length_bytes(1 << 20)
+            .overflow(Overflow::RejectPublish)
+            .dead_letter_exchange("dlx".to_owned())
+            .dead_letter_routing_key("dead".to_owned())
+            .dead_letter_strategy(DeadLetterStrategy::AtLeastOnce)
+            .single_active_consumer(true)
+            .delivery_limit(5)
+            .initial_cluster_size(3)
+            .other(other)
+            .finish();
+        assert!(args.validate().is_ok());
+
+        let table = FieldTable::from(args.clone());
+        assert_eq!(
+            Some(&FieldValue::l(60_000)),
+            table.get(&X_MESSAGE_TTL.try_into().unwrap())
+        );
+        assert_eq!(
+            Some(&FieldValue::from("quorum")),
+            table.get(&X_QUEUE_TYPE.try_into().unwrap())
+        );
+        assert_eq!(args, QueueArguments::try_from(&table).unwrap());
+
+        let args = QueueArguments::new()
+            .queue_type(QueueType::Stream)
+            .max_age(Duration::from_secs(7 * 24 * 3600))
+            .stream_max_segment_size_bytes(1 << 20)
+            .finish();
+        assert!(args.validate().is_ok());
+        let table = FieldTable::from(args.clone());
+        assert_eq!(
+            Some(&FieldValue::from("604800s")),
+            table.get(&X_MAX_AGE.try_into().unwrap())
+        );
+        assert_eq!(args, QueueArguments::try_from(&table).unwrap());
+    }
+
+    #[test]
+    fn test_parse_queue_arguments() {
+        // any integer type is accepted
+        let mut table = FieldTable::new();
+        table.insert("x-message-ttl".try_into().unwrap(), FieldValue::i(1000));
+        table.insert("x-max-priority".try_into().unwrap(), FieldValue::B(10));
+        table.insert("x-max-age".try_into().unwrap(), "7D".into());
+        let args = QueueArguments::try_from(&table).unwrap();
+        assert_eq!(Some(Duration::from_secs(1)), args.message_ttl);
+        assert_eq!(Some(10), args.max_priority);
+        assert_eq!(Some(Duration::from_secs(7 * 24 * 3600)), args.max_age);
+
+        let mut table = FieldTable::new();
+        table.insert("x-message-ttl".try_into().unwrap(), "1000".into());
+        assert!(QueueArguments::try_from(&table).is_err());
+
+        let mut table = FieldTable::new();
+        table.insert("x-max-length".try_into().unwrap(), FieldValue::l(-1));
+        assert!(QueueArguments::try_from(&table).is_err());
+
+        // unlimited delivery of RabbitMQ 4.0
+        let mut table = FieldTable::new();
+        table.insert("x-delivery-limit".try_into().unwrap(), FieldValue::l(-1));
+        let args = QueueArguments::try_from(&table).unwrap();
+        assert_eq!(Some(-1), args.delivery_limit);
+        assert!(args.validate().is_ok());
+
+        let mut table = FieldTable::new();
+        table.insert("x-queue-type".try_into().unwrap(), "qourum".into());
+        assert!(QueueArguments::try_from(&table).is_err());
+
+        assert_eq!(Some(Duration::from_secs(3600)), parse_max_age("1h"));
+        assert_eq!(None, parse_max_age("1w"));
+        assert_eq!(None, parse_max_age("h"));
+        assert_eq!(None, parse_max_age(""));
+    }
+
+    #[test]
+    fn test_validate_queue_arguments() {
+        assert!(QueueArguments::new()
+            .dead_letter_routing_key("dead".to_owned())
+            .finish()
+            .validate()
+            .is_err());
+        assert!(QueueArguments::new()
+            .expires(Duration::ZERO)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(QueueArguments::new()
+            .message_ttl(Duration::from_millis(1 << 32))
+            .finish()
+            .validate()
+            .is_err());
+        assert!(QueueArguments::new()
+            .max_priority(0)
+            .finish()
+            .validate()
+            .is_err());
+
+        // incompatible with queue type
+        assert!(QueueArguments::new()
+            .queue_type(QueueType::Classic)
+            .delivery_limit(5)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(QueueArguments::new()
+            .queue_type(QueueType::Quorum)
+            .overflow(Overflow::RejectPublishDlx)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(QueueArguments::new()
+            .queue_type(QueueType::Quorum)
+            .max_priority(10)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(QueueArguments::new()
+            .queue_type(QueueType::Stream)
+            .message_ttl(Duration::from_secs(60))
+            .finish()
+            .validate()
+            .is_err());
+        // type decided by server
+        assert!(QueueArguments::new()
+            .delivery_limit(5)
+            .max_priority(10)
+            .finish()
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn test_exchange_arguments_round_trip() {
+        let args = ExchangeArguments::new()
+            .alternate_exchange("unroutable".to_owned())
+            .delayed_type(ExchangeType::Topic)
+            .finish();
+        assert!(args.validate().is_ok());
+        let table = FieldTable::from(args.clone());
+        assert_eq!(
+            Some(&FieldValue::from("topic")),
+            table.get(&"x-delayed-type".try_into().unwrap())
+        );
+        assert_eq!(args, ExchangeArguments::try_from(&table).unwrap());
+
+        assert!(ExchangeArguments::new()
+            .hash_header("user".to_owned())
+            .hash_property("message_id".to_owned())
+            .finish()
+            .validate()
+            .is_err());
+        assert!(ExchangeArguments::new()
+            .hash_property("priority".to_owned())
+            .finish()
+            .validate()
+            .is_err());
+        assert!(ExchangeArguments::new()
+            .delayed_type(ExchangeType::DelayedMessage)
+            .finish()
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_headers_binding_arguments_round_trip() {
+        let args = HeadersBindingArguments::new(HeadersMatch::AnyWithX)
+            .header("format", "pdf")
+            .header("version", 2)
+            .header("draft", false)
+            .header("x-tenant", HeaderValue::Present)
+            .finish();
+        assert!(args.validate().is_ok());
+        let table = FieldTable::from(args.clone());
+        assert_eq!(
+            Some(&FieldValue::from("any-with-x")),
+            table.get(&X_MATCH.try_into().unwrap())
+        );
+        assert_eq!(
+            Some(&FieldValue::l(2)),
+            table.get(&"version".try_into().unwrap())
+        );
+        assert_eq!(args, HeadersBindingArguments::try_from(&table).unwrap());
+
+        // any integer type is accepted, x-match defaults to all
+        let mut table = FieldTable::new();
+        table.insert("version".try_into().unwrap(), FieldValue::u(2));
+        let args = HeadersBindingArguments::try_from(&table).unwrap();
+        assert_eq!(HeadersMatch::All, args.x_match);
+        assert_eq!(Some(&HeaderValue::Int(2)), args.headers.get("version"));
+
+        let mut table = FieldTable::new();
+        table.insert(X_MATCH.try_into().unwrap(), "some".into());
+        assert!(HeadersBindingArguments::try_from(&table).is_err());
+        let mut table = FieldTable::new();
+        table.insert("ratio".try_into().unwrap(), FieldValue::d(0.5));
+        assert!(HeadersBindingArguments::try_from(&table).is_err());
+    }
+}
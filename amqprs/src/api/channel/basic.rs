@@ -1,27 +1,42 @@
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
 use amqp_serde::types::AmqpDeliveryTag;
-use tokio::sync::mpsc;
+use tokio::{
+    sync::{mpsc, oneshot},
+    time,
+};
 #[cfg(feature = "traces")]
-use tracing::{debug, trace};
+use tracing::{debug, error, trace};
 
 use crate::{
     api::{
-        channel::{ConsumerMessage, DispatcherManagementCommand, RegisterContentConsumer},
-        consumer::AsyncConsumer,
+        channel::{
+            AbortDrainContentConsumer, AckBatchingArguments, AckDeadline, ConsumerMessage,
+            DispatcherManagementCommand, Drain, DrainContentConsumer, DrainRequest, DrainSummary,
+            RegisterContentConsumer, RegisterFallbackConsumer, SetConsumerAckMode, Settlement,
+            UnsettledDelivery,
+        },
+        consumer::{AsyncConsumer, OutcomeConsumer},
         error::Error,
+        validation::{
+            check_exchange_name, check_flags, check_queue_name, check_short_str, to_short_str,
+        },
         FieldTable, Result,
     },
     consumer::BlockingConsumer,
     frame::{
-        Ack, BasicProperties, Cancel, CancelOk, Consume, ConsumeOk, ContentBody, ContentHeader,
-        ContentHeaderCommon, Frame, Get, GetOk, Nack, Publish, Qos, QosOk, Recover, RecoverOk,
-        Reject,
+        BasicProperties, Cancel, CancelOk, Consume, ConsumeOk, ContentBody, ContentHeader,
+        ContentHeaderCommon, Frame, GetOk, Publish, Qos, QosOk, Recover, RecoverOk,
     },
 };
 
-#[cfg(feature = "compliance_assert")]
-use crate::api::compliance_asserts::{assert_exchange_name, assert_queue_name};
-
-use super::{Channel, DeregisterContentConsumer, RegisterGetContentResponder};
+use super::{
+    get::recv_get_message, outcome::spawn_outcome_consumer, Channel, ConsumerOptions,
+    DeregisterContentConsumer, CONSUMER_EXPIRY_PERIOD,
+};
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`basic_qos`]
 ///
@@ -63,6 +78,20 @@ impl BasicQosArguments {
     pub fn finish(&mut self) -> Self {
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`basic_qos`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `prefetch_size` is not 0, which is not supported by RabbitMQ.
+    ///
+    /// [`basic_qos`]: struct.Channel.html#method.basic_qos
+    pub fn validate(&self) -> Result<()> {
+        check_flags(
+            self.prefetch_size == 0,
+            "prefetch_size: must be 0, prefetch by size is not supported",
+        )
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`basic_consume`]
@@ -96,14 +125,17 @@ pub struct BasicConsumeArguments {
     pub no_wait: bool,
     /// Default: empty table.
     pub arguments: FieldTable,
+    /// Client-side ack deadline of deliveries, only in manual ack mode. Default: `None`.
+    ///
+    /// See [`AckDeadline`] for details.
+    ///
+    /// [`AckDeadline`]: struct.AckDeadline.html
+    pub ack_deadline: Option<AckDeadline>,
 }
 
 impl BasicConsumeArguments {
     /// Create new arguments with defaults.
     pub fn new(queue: &str, consumer_tag: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(queue);
-
         Self {
             queue: queue.to_owned(),
             consumer_tag: consumer_tag.to_owned(),
@@ -112,6 +144,7 @@ impl BasicConsumeArguments {
             exclusive: false,
             no_wait: false,
             arguments: FieldTable::new(),
+            ack_deadline: None,
         }
     }
     impl_chainable_setter! {
@@ -155,11 +188,101 @@ impl BasicConsumeArguments {
         arguments, FieldTable
     }
 
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        ack_deadline, Option<AckDeadline>
+    }
+
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(&self.queue);
+        self.clone()
+    }
+
+    /// Validate the arguments, it is also done by [`basic_consume`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes,
+    /// or consumer tag is server-generated with `no_wait`, because the tag can not be returned,
+    /// or `ack_deadline` is invalid or set in auto ack mode.
+    ///
+    /// [`basic_consume`]: struct.Channel.html#method.basic_consume
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)?;
+        check_short_str("consumer_tag", &self.consumer_tag)?;
+        check_flags(
+            !(self.consumer_tag.is_empty() && self.no_wait),
+            "no_wait: can not consume with a server-generated consumer tag without waiting for it",
+        )?;
+        if let Some(ref ack_deadline) = self.ack_deadline {
+            check_flags(
+                !self.no_ack,
+                "ack_deadline: deliveries in auto ack mode are never settled by client",
+            )?;
+            ack_deadline.validate()?;
+        }
+        Ok(())
+    }
+}
+////////////////////////////////////////////////////////////////////////////////
+/// Arguments for [`set_orphaned_delivery_handling`]
+///
+/// A delivery is orphaned if no consumer is registered for its consumer tag, e.g. it arrives
+/// after the consumer is cancelled. Orphaned deliveries are buffered in case the consumer
+/// is registered later, once `expiry` elapses, they are
+/// 1. forwarded to the fallback consumer if one is set by [`set_fallback_consumer`], otherwise
+/// 2. negatively acknowledged with requeue if `requeue` is `true` and the consumer was in
+///    manual ack mode, otherwise
+/// 3. dropped.
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::OrphanedDeliveryArguments;
+/// # use std::time::Duration;
+/// let x = OrphanedDeliveryArguments::new()
+///     .expiry(Duration::from_secs(1))
+///     .requeue(true)
+///     .finish();
+/// ```
+///
+/// [`set_orphaned_delivery_handling`]: struct.Channel.html#method.set_orphaned_delivery_handling
+/// [`set_fallback_consumer`]: struct.Channel.html#method.set_fallback_consumer
+#[derive(Debug, Clone)]
+pub struct OrphanedDeliveryArguments {
+    /// How long orphaned deliveries are buffered before handled. Default: 5 seconds.
+    pub expiry: Duration,
+    /// Should orphaned deliveries be requeued? Default: `false`.
+    pub requeue: bool,
+}
+
+impl Default for OrphanedDeliveryArguments {
+    fn default() -> Self {
+        Self {
+            expiry: CONSUMER_EXPIRY_PERIOD,
+            requeue: false,
+        }
+    }
+}
+
+impl OrphanedDeliveryArguments {
+    /// Create new arguments with defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        expiry, Duration
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        requeue, bool
+    }
 
+    /// Finish chained configuration and return new arguments.
+    pub fn finish(&mut self) -> Self {
         self.clone()
     }
 }
@@ -197,6 +320,21 @@ impl BasicCancelArguments {
     pub fn finish(&mut self) -> Self {
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`basic_cancel`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if consumer tag is empty or longer than 255 bytes.
+    ///
+    /// [`basic_cancel`]: struct.Channel.html#method.basic_cancel
+    pub fn validate(&self) -> Result<()> {
+        check_short_str("consumer_tag", &self.consumer_tag)?;
+        check_flags(
+            !self.consumer_tag.is_empty(),
+            "consumer_tag: must not be empty",
+        )
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`basic_get`]
@@ -215,9 +353,6 @@ pub struct BasicGetArguments {
 impl BasicGetArguments {
     /// Create new arguments with defaults.
     pub fn new(queue: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(queue);
-
         Self {
             queue: queue.to_owned(),
             no_ack: false,
@@ -233,11 +368,19 @@ impl BasicGetArguments {
     }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(&self.queue);
-
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`basic_get`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if queue name is longer than 255 bytes.
+    ///
+    /// [`basic_get`]: struct.Channel.html#method.basic_get
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)
+    }
 }
 
 /// Tuple returned by [`Channel::basic_get`] method.
@@ -269,6 +412,17 @@ impl BasicAckArguments {
             multiple,
         }
     }
+
+    /// Validate the arguments, it is also done by [`basic_ack`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `delivery_tag` is 0 without `multiple`.
+    ///
+    /// [`basic_ack`]: struct.Channel.html#method.basic_ack
+    pub fn validate(&self) -> Result<()> {
+        check_delivery_tag(self.delivery_tag, self.multiple)
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`basic_nack`]
@@ -304,6 +458,17 @@ impl BasicNackArguments {
             requeue,
         }
     }
+
+    /// Validate the arguments, it is also done by [`basic_nack`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `delivery_tag` is 0 without `multiple`.
+    ///
+    /// [`basic_nack`]: struct.Channel.html#method.basic_nack
+    pub fn validate(&self) -> Result<()> {
+        check_delivery_tag(self.delivery_tag, self.multiple)
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`basic_reject`]
@@ -335,6 +500,25 @@ impl BasicRejectArguments {
             requeue,
         }
     }
+
+    /// Validate the arguments, it is also done by [`basic_reject`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `delivery_tag` is 0.
+    ///
+    /// [`basic_reject`]: struct.Channel.html#method.basic_reject
+    pub fn validate(&self) -> Result<()> {
+        check_delivery_tag(self.delivery_tag, false)
+    }
+}
+
+/// Delivery tag 0 is only valid to settle all outstanding messages with `multiple`.
+fn check_delivery_tag(delivery_tag: AmqpDeliveryTag, multiple: bool) -> Result<()> {
+    check_flags(
+        delivery_tag != 0 || multiple,
+        "delivery_tag: must not be 0 unless `multiple` is set",
+    )
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`basic_publish`]
@@ -357,9 +541,6 @@ pub struct BasicPublishArguments {
 impl BasicPublishArguments {
     /// Create new arguments with defaults.
     pub fn new(exchange: &str, routing_key: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_exchange_name(exchange);
-
         Self {
             exchange: exchange.to_owned(),
             routing_key: routing_key.to_owned(),
@@ -385,11 +566,25 @@ impl BasicPublishArguments {
     }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_exchange_name(&self.exchange);
-
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`basic_publish`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes,
+    /// or `immediate` is set, which is not supported by RabbitMQ.
+    ///
+    /// [`basic_publish`]: struct.Channel.html#method.basic_publish
+    pub fn validate(&self) -> Result<()> {
+        check_exchange_name("exchange", &self.exchange)?;
+        check_short_str("routing_key", &self.routing_key)?;
+        check_flags(
+            !self.immediate,
+            "immediate: is not supported, use `mandatory` with per-message TTL instead",
+        )
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -399,18 +594,13 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns error if any failure in comunication with server.
     pub async fn basic_qos(&self, args: BasicQosArguments) -> Result<()> {
+        args.validate()?;
         let qos = Qos::new(args.prefetch_size, args.prefetch_count, args.global);
-        let responder_rx = self.register_responder(QosOk::header()).await?;
-
-        let _method = synchronous_request!(
-            self.shared.outgoing_tx,
-            (self.shared.channel_id, qos.into_frame()),
-            responder_rx,
-            Frame::QosOk,
-            Error::ChannelUseError
-        )?;
+        let responder_rx = self.send_request(QosOk::header(), qos.into_frame())?;
+        let _method = synchronous_request!(responder_rx, Frame::QosOk, Error::ChannelUseError)?;
         Ok(())
     }
 
@@ -420,14 +610,18 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns an error if a failure occurs while comunicating with the server.
     pub async fn basic_consume<F>(&self, consumer: F, args: BasicConsumeArguments) -> Result<String>
     where
         F: AsyncConsumer + Send + 'static,
     {
+        let no_ack = args.no_ack;
+        let ack_deadline = args.ack_deadline;
         let consumer_tag = self.request_basic_consume(args).await?;
 
-        self.spawn_consumer(consumer_tag.clone(), consumer).await?;
+        self.spawn_consumer(consumer_tag.clone(), no_ack, ack_deadline, consumer)
+            .await?;
 
         Ok(consumer_tag)
     }
@@ -438,6 +632,7 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns an error if a failure occurs while comunicating with the server.
     ///
     /// [`basic_consume`]: struct.Channel.html#method.basic_consume
@@ -449,14 +644,115 @@ impl Channel {
     where
         F: BlockingConsumer + Send + 'static,
     {
+        let no_ack = args.no_ack;
+        let ack_deadline = args.ack_deadline;
         let consumer_tag = self.request_basic_consume(args).await?;
 
-        self.spawn_blocking_consumer(consumer_tag.clone(), consumer)
+        self.spawn_blocking_consumer(consumer_tag.clone(), no_ack, ack_deadline, consumer)
             .await?;
 
         Ok(consumer_tag)
     }
 
+    /// Similar as [`basic_consume`] but the consumer returns the [`Outcome`] of each delivery,
+    /// and acknowledgement is sent according to the outcome.
+    ///
+    /// If the consumer returns error or panics, the default outcome of `options` is used, so that
+    /// no delivery is left unacknowledged. Acks can be coalesced by `options`, see [`ConsumerOptions`].
+    ///
+    /// Returns the consumer tag on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if arguments or options are invalid, or automatic ack
+    /// mode is requested, because the outcome is acknowledged by client.
+    /// Returns an error if a failure occurs while comunicating with the server.
+    ///
+    /// [`basic_consume`]: struct.Channel.html#method.basic_consume
+    /// [`Outcome`]: ../consumer/enum.Outcome.html
+    /// [`ConsumerOptions`]: struct.ConsumerOptions.html
+    pub async fn basic_consume_with_outcome<F>(
+        &self,
+        consumer: F,
+        args: BasicConsumeArguments,
+        options: ConsumerOptions,
+    ) -> Result<String>
+    where
+        F: OutcomeConsumer + Send + 'static,
+    {
+        check_flags(
+            options.concurrency <= 1,
+            "concurrency: use basic_consume_concurrent to handle deliveries concurrently",
+        )?;
+        self.consume_with_outcome(vec![consumer], args, options)
+            .await
+    }
+
+    /// Similar as [`basic_consume_with_outcome`] but handles up to `concurrency` of `options`
+    /// deliveries concurrently, each by a clone of the consumer.
+    ///
+    /// Deliveries of same ordering key of `options` are handled one by one in order of delivery.
+    /// Acknowledgement of a delivery is sent once it is handled, acks coalesced with `multiple = true`
+    /// never cover a delivery which is still being handled.
+    ///
+    /// Returns the consumer tag on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if arguments or options are invalid, or automatic ack
+    /// mode is requested, because the outcome is acknowledged by client.
+    /// Returns an error if a failure occurs while comunicating with the server.
+    ///
+    /// [`basic_consume_with_outcome`]: struct.Channel.html#method.basic_consume_with_outcome
+    pub async fn basic_consume_concurrent<F>(
+        &self,
+        consumer: F,
+        args: BasicConsumeArguments,
+        options: ConsumerOptions,
+    ) -> Result<String>
+    where
+        F: OutcomeConsumer + Clone + Send + 'static,
+    {
+        let consumers = vec![consumer; options.concurrency];
+        self.consume_with_outcome(consumers, args, options).await
+    }
+
+    /// Start consume, each consumer handles deliveries in its own task.
+    async fn consume_with_outcome<F>(
+        &self,
+        consumers: Vec<F>,
+        args: BasicConsumeArguments,
+        options: ConsumerOptions,
+    ) -> Result<String>
+    where
+        F: OutcomeConsumer + Send + 'static,
+    {
+        options.validate()?;
+        check_flags(
+            !args.no_ack,
+            "no_ack: outcome consumer requires manual ack mode",
+        )?;
+        let ack_deadline = args.ack_deadline;
+        let consumer_tag = self.request_basic_consume(args).await?;
+
+        let (consumer_tx, drain_tx) = spawn_outcome_consumer(
+            self.clone_as_secondary(),
+            consumer_tag.clone(),
+            consumers,
+            options,
+        );
+        self.register_consumer(
+            consumer_tag.clone(),
+            false,
+            consumer_tx,
+            Some(drain_tx),
+            ack_deadline,
+        )
+        .await?;
+
+        Ok(consumer_tag)
+    }
+
     /// Similar to [`basic_consume`] but returns the raw unbounded [`UnboundedReceiver`]
     ///
     /// Returns the consumer tag and the [`UnboundedReceiver`] on success.
@@ -545,6 +841,7 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns an error if a failure occurs while comunicating with the server.
     ///
     /// [`basic_consume`]: struct.Channel.html#method.basic_consume
@@ -555,6 +852,8 @@ impl Channel {
         &self,
         args: BasicConsumeArguments,
     ) -> Result<(String, mpsc::UnboundedReceiver<ConsumerMessage>)> {
+        let no_ack = args.no_ack;
+        let ack_deadline = args.ack_deadline;
         let consumer_tag = self.request_basic_consume(args).await?;
 
         let (consumer_tx, consumer_rx): (
@@ -562,14 +861,21 @@ impl Channel {
             mpsc::UnboundedReceiver<ConsumerMessage>,
         ) = mpsc::unbounded_channel();
 
-        self.register_consumer(consumer_tag.clone(), consumer_tx)
-            .await?;
+        self.register_consumer(
+            consumer_tag.clone(),
+            no_ack,
+            consumer_tx,
+            None,
+            ack_deadline,
+        )
+        .await?;
 
         Ok((consumer_tag, consumer_rx))
     }
 
     /// Send basic consume request to server
     async fn request_basic_consume(&self, args: BasicConsumeArguments) -> Result<String> {
+        args.validate()?;
         let BasicConsumeArguments {
             queue,
             consumer_tag,
@@ -578,11 +884,12 @@ impl Channel {
             exclusive,
             no_wait,
             arguments,
+            ..
         } = args;
         let mut consume = Consume::new(
             0,
-            queue.try_into().unwrap(),
-            consumer_tag.clone().try_into().unwrap(),
+            to_short_str("queue", queue)?,
+            to_short_str("consumer_tag", consumer_tag.clone())?,
             arguments,
         );
         consume.set_no_local(no_local);
@@ -590,28 +897,62 @@ impl Channel {
         consume.set_exclusive(exclusive);
         consume.set_nowait(no_wait);
         let consumer_tag = if args.no_wait {
+            // no `consume-ok` tells dispatcher the ack mode, so record it before any delivery
+            self.shared.dispatcher_mgmt_tx.send(
+                DispatcherManagementCommand::SetConsumerAckMode(SetConsumerAckMode {
+                    consumer_tag: consumer_tag.clone(),
+                    no_ack,
+                }),
+            )?;
             self.shared
                 .outgoing_tx
                 .send((self.shared.channel_id, consume.into_frame()))
                 .await?;
             consumer_tag
         } else {
-            let responder_rx = self.register_responder(ConsumeOk::header()).await?;
-
-            let method = synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.shared.channel_id, consume.into_frame()),
-                responder_rx,
-                Frame::ConsumeOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(ConsumeOk::header(), consume.into_frame())?;
+            let method =
+                synchronous_request!(responder_rx, Frame::ConsumeOk, Error::ChannelUseError)?;
             method.consumer_tag.into()
         };
         Ok(consumer_tag)
     }
 
     /// Spawn async consumer task
-    async fn spawn_consumer<F>(&self, consumer_tag: String, mut consumer: F) -> Result<()>
+    async fn spawn_consumer<F>(
+        &self,
+        consumer_tag: String,
+        no_ack: bool,
+        ack_deadline: Option<AckDeadline>,
+        consumer: F,
+    ) -> Result<()>
+    where
+        F: AsyncConsumer + Send + 'static,
+    {
+        let (consumer_tx, drain_tx) =
+            self.spawn_consumer_task(consumer_tag.clone(), no_ack, consumer);
+        self.register_consumer(
+            consumer_tag,
+            no_ack,
+            consumer_tx,
+            Some(drain_tx),
+            ack_deadline,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Spawn task to run async consumer, returns the tx halves to forward deliveries to the task,
+    /// and to request draining of the task.
+    fn spawn_consumer_task<F>(
+        &self,
+        consumer_tag: String,
+        no_ack: bool,
+        mut consumer: F,
+    ) -> (
+        mpsc::UnboundedSender<ConsumerMessage>,
+        oneshot::Sender<DrainRequest>,
+    )
     where
         F: AsyncConsumer + Send + 'static,
     {
@@ -620,7 +961,8 @@ impl Channel {
             mpsc::UnboundedReceiver<ConsumerMessage>,
         ) = mpsc::unbounded_channel();
 
-        let ctag = consumer_tag.clone();
+        let (drain_tx, mut drain) = Drain::new(no_ack);
+        let ctag = consumer_tag;
         let channel = self.clone_as_secondary();
 
         // spawn consumer task
@@ -635,14 +977,30 @@ impl Channel {
             loop {
                 match consumer_rx.recv().await {
                     Some(mut msg) => {
+                        let deliver = msg.deliver.take().unwrap();
+                        if drain.should_requeue() {
+                            let delivery_tag = deliver.delivery_tag();
+                            if let Err(_err) = channel
+                                .basic_nack(BasicNackArguments::new(delivery_tag, false, true))
+                                .await
+                            {
+                                #[cfg(feature = "traces")]
+                                error!(
+                                    "failed to requeue delivery {} of consumer {}, cause: {}",
+                                    delivery_tag, ctag, _err
+                                );
+                            }
+                            continue;
+                        }
                         consumer
                             .consume(
                                 &channel,
-                                msg.deliver.take().unwrap(),
+                                deliver,
                                 msg.basic_properties.take().unwrap(),
                                 msg.content.take().unwrap(),
                             )
                             .await;
+                        drain.processed();
                     }
                     None => {
                         #[cfg(feature = "traces")]
@@ -651,14 +1009,20 @@ impl Channel {
                     }
                 }
             }
+            drain.finish();
         });
 
-        self.register_consumer(consumer_tag, consumer_tx).await?;
-        Ok(())
+        (consumer_tx, drain_tx)
     }
 
     /// Spawn blocking consumer task
-    async fn spawn_blocking_consumer<F>(&self, consumer_tag: String, mut consumer: F) -> Result<()>
+    async fn spawn_blocking_consumer<F>(
+        &self,
+        consumer_tag: String,
+        no_ack: bool,
+        ack_deadline: Option<AckDeadline>,
+        mut consumer: F,
+    ) -> Result<()>
     where
         F: BlockingConsumer + Send + 'static,
     {
@@ -667,6 +1031,7 @@ impl Channel {
             mpsc::UnboundedReceiver<ConsumerMessage>,
         ) = mpsc::unbounded_channel();
 
+        let (drain_tx, mut drain) = Drain::new(no_ack);
         let ctag = consumer_tag.clone();
         let channel = self.clone_as_secondary();
 
@@ -682,12 +1047,29 @@ impl Channel {
             loop {
                 match consumer_rx.blocking_recv() {
                     Some(mut msg) => {
+                        let deliver = msg.deliver.take().unwrap();
+                        if drain.should_requeue() {
+                            let delivery_tag = deliver.delivery_tag();
+                            if let Err(_err) = channel.basic_nack_blocking(BasicNackArguments::new(
+                                delivery_tag,
+                                false,
+                                true,
+                            )) {
+                                #[cfg(feature = "traces")]
+                                error!(
+                                    "failed to requeue delivery {} of consumer {}, cause: {}",
+                                    delivery_tag, ctag, _err
+                                );
+                            }
+                            continue;
+                        }
                         consumer.consume(
                             &channel,
-                            msg.deliver.take().unwrap(),
+                            deliver,
                             msg.basic_properties.take().unwrap(),
                             msg.content.take().unwrap(),
                         );
+                        drain.processed();
                     }
                     None => {
                         #[cfg(feature = "traces")]
@@ -696,9 +1078,17 @@ impl Channel {
                     }
                 }
             }
+            drain.finish();
         });
 
-        self.register_consumer(consumer_tag, consumer_tx).await?;
+        self.register_consumer(
+            consumer_tag,
+            no_ack,
+            consumer_tx,
+            Some(drain_tx),
+            ack_deadline,
+        )
+        .await?;
         Ok(())
     }
 
@@ -706,11 +1096,64 @@ impl Channel {
     async fn register_consumer(
         &self,
         consumer_tag: String,
+        no_ack: bool,
         consumer_tx: mpsc::UnboundedSender<ConsumerMessage>,
+        drain_tx: Option<oneshot::Sender<DrainRequest>>,
+        ack_deadline: Option<AckDeadline>,
     ) -> Result<()> {
         self.shared.dispatcher_mgmt_tx.send(
             DispatcherManagementCommand::RegisterContentConsumer(RegisterContentConsumer {
                 consumer_tag,
+                no_ack,
+                consumer_tx,
+                drain_tx,
+                ack_deadline,
+            }),
+        )?;
+        Ok(())
+    }
+
+    /// Set how to handle orphaned deliveries, which have no registered consumer.
+    ///
+    /// See [`OrphanedDeliveryArguments`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the channel is closed.
+    ///
+    /// [`OrphanedDeliveryArguments`]: struct.OrphanedDeliveryArguments.html
+    pub async fn set_orphaned_delivery_handling(
+        &self,
+        args: OrphanedDeliveryArguments,
+    ) -> Result<()> {
+        self.shared.dispatcher_mgmt_tx.send(
+            DispatcherManagementCommand::SetOrphanedDeliveryHandling(args),
+        )?;
+        Ok(())
+    }
+
+    /// Set a fallback consumer to receive orphaned deliveries once they expire, similar to
+    /// the default consumer of other clients. It replaces the previous fallback consumer if any.
+    ///
+    /// The fallback consumer is responsible for acknowledging the deliveries if they were
+    /// consumed in manual ack mode. Use [`Deliver::consumer_tag`] to find out the original consumer.
+    ///
+    /// See [`OrphanedDeliveryArguments`] for details.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the channel is closed.
+    ///
+    /// [`OrphanedDeliveryArguments`]: struct.OrphanedDeliveryArguments.html
+    /// [`Deliver::consumer_tag`]: ../struct.Deliver.html#method.consumer_tag
+    pub async fn set_fallback_consumer<F>(&self, consumer: F) -> Result<()>
+    where
+        F: AsyncConsumer + Send + 'static,
+    {
+        // fallback consumer is never cancelled, so it is never drained
+        let (consumer_tx, _) = self.spawn_consumer_task("fallback".to_owned(), false, consumer);
+        self.shared.dispatcher_mgmt_tx.send(
+            DispatcherManagementCommand::RegisterFallbackConsumer(RegisterFallbackConsumer {
                 consumer_tx,
             }),
         )?;
@@ -721,14 +1164,16 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::DeliveryTagError`] if the delivery tag is unknown or already settled.
     /// Returns error if any failure in comunication with server.
     pub async fn basic_ack(&self, args: BasicAckArguments) -> Result<()> {
-        let ack = Ack::new(args.delivery_tag, args.multiple);
-        self.shared
-            .outgoing_tx
-            .send((self.shared.channel_id, ack.into_frame()))
-            .await?;
-        Ok(())
+        args.validate()?;
+        self.settle(Settlement::Ack {
+            delivery_tag: args.delivery_tag,
+            multiple: args.multiple,
+        })
+        .await
     }
 
     /// Blocking version of [`basic_ack`], should be invoked in blocking context.
@@ -739,31 +1184,34 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::DeliveryTagError`] if the delivery tag is unknown or already settled.
     /// Returns error if any failure in comunication with server.
     ///
     /// [`basic_ack`]: struct.Channel.html#method.basic_ack
     pub fn basic_ack_blocking(&self, args: BasicAckArguments) -> Result<()> {
-        let ack = Ack::new(args.delivery_tag, args.multiple);
-        self.shared
-            .outgoing_tx
-            .blocking_send((self.shared.channel_id, ack.into_frame()))?;
-        Ok(())
+        args.validate()?;
+        self.settle_blocking(Settlement::Ack {
+            delivery_tag: args.delivery_tag,
+            multiple: args.multiple,
+        })
     }
 
     /// See [AMQP_0-9-1 Reference](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.nack)
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::DeliveryTagError`] if the delivery tag is unknown or already settled.
     /// Returns error if any failure in comunication with server.
     pub async fn basic_nack(&self, args: BasicNackArguments) -> Result<()> {
-        let mut nack = Nack::new(args.delivery_tag);
-        nack.set_multiple(args.multiple);
-        nack.set_requeue(args.requeue);
-        self.shared
-            .outgoing_tx
-            .send((self.shared.channel_id, nack.into_frame()))
-            .await?;
-        Ok(())
+        args.validate()?;
+        self.settle(Settlement::Nack {
+            delivery_tag: args.delivery_tag,
+            multiple: args.multiple,
+            requeue: args.requeue,
+        })
+        .await
     }
 
     /// Blocking version of [`basic_nack`], should be invoked in blocking context.
@@ -774,77 +1222,172 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::DeliveryTagError`] if the delivery tag is unknown or already settled.
     /// Returns error if any failure in comunication with server.
     ///
     /// [`basic_nack`]: struct.Channel.html#method.basic_nack
     pub fn basic_nack_blocking(&self, args: BasicNackArguments) -> Result<()> {
-        let mut nack = Nack::new(args.delivery_tag);
-        nack.set_multiple(args.multiple);
-        nack.set_requeue(args.requeue);
-        self.shared
-            .outgoing_tx
-            .blocking_send((self.shared.channel_id, nack.into_frame()))?;
-        Ok(())
+        args.validate()?;
+        self.settle_blocking(Settlement::Nack {
+            delivery_tag: args.delivery_tag,
+            multiple: args.multiple,
+            requeue: args.requeue,
+        })
     }
 
     /// See [AMQP_0-9-1 Reference](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.reject)
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::DeliveryTagError`] if the delivery tag is unknown or already settled.
     /// Returns error if any failure in comunication with server.
     pub async fn basic_reject(&self, args: BasicRejectArguments) -> Result<()> {
-        let reject = Reject::new(args.delivery_tag, args.requeue);
-        self.shared
-            .outgoing_tx
-            .send((self.shared.channel_id, reject.into_frame()))
-            .await?;
-        Ok(())
+        args.validate()?;
+        self.settle(Settlement::Reject {
+            delivery_tag: args.delivery_tag,
+            requeue: args.requeue,
+        })
+        .await
     }
 
     /// Blocking version of `basic_reject`
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::DeliveryTagError`] if the delivery tag is unknown or already settled.
     /// Returns error if any failure in comunication with server.
     pub fn basic_reject_blocking(&self, args: BasicRejectArguments) -> Result<()> {
-        let reject = Reject::new(args.delivery_tag, args.requeue);
+        args.validate()?;
+        self.settle_blocking(Settlement::Reject {
+            delivery_tag: args.delivery_tag,
+            requeue: args.requeue,
+        })
+    }
+
+    /// Check the settlement against the unsettled deliveries and send it to server.
+    ///
+    /// It does not wait for dispatcher, so it can be called from callbacks.
+    async fn settle(&self, settlement: Settlement) -> Result<()> {
+        self.shared.settle(settlement).await
+    }
+
+    fn settle_blocking(&self, settlement: Settlement) -> Result<()> {
         self.shared
-            .outgoing_tx
-            .blocking_send((self.shared.channel_id, reject.into_frame()))?;
-        Ok(())
+            .update_settlements_blocking(|settlements| Ok((settlements.settle(settlement)?, ())))
     }
 
-    /// See [AMQP_0-9-1 Reference](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.cancel)
+    /// Returns the deliveries not yet settled by client, in the order of delivery tags.
     ///
-    /// Returns consumer tag if succeed.
+    /// Deliveries to consumers in auto ack mode, and deliveries of `get` with `no_ack = true` are
+    /// settled once received. Deliveries whose acks are accumulated by ack batching are settled,
+    /// see [`set_ack_batching`].
     ///
     /// # Errors
     ///
-    /// Returns error if any failure in comunication with server.
-    pub async fn basic_cancel(&self, args: BasicCancelArguments) -> Result<String> {
-        let BasicCancelArguments {
-            consumer_tag,
-            no_wait,
-        } = args;
-
-        let cancel = Cancel::new(consumer_tag.clone().try_into().unwrap(), no_wait);
+    /// Returns error if the channel is closed.
+    ///
+    /// [`set_ack_batching`]: struct.Channel.html#method.set_ack_batching
+    pub async fn unsettled_deliveries(&self) -> Result<Vec<UnsettledDelivery>> {
+        if !self.is_open() {
+            return Err(Error::ChannelUseError(format!(
+                "channel {} is closed",
+                self.channel_id()
+            )));
+        }
+        Ok(self.shared.settlements.lock().await.unsettled())
+    }
 
-        let consumer_tag = if args.no_wait {
-            self.shared
-                .outgoing_tx
-                .send((self.shared.channel_id, cancel.into_frame()))
-                .await?;
-            consumer_tag
-        } else {
-            let responder_rx = self.register_responder(CancelOk::header()).await?;
-
-            let cancel_ok = synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.shared.channel_id, cancel.into_frame()),
-                responder_rx,
-                Frame::CancelOk,
-                Error::ChannelUseError
-            )?;
+    /// Nack all unsettled deliveries by one nack with `multiple = true`, typically before shutdown.
+    ///
+    /// Accumulated acks of ack batching are sent before the nack. Returns the number of nacked deliveries.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if any failure in comunication with server.
+    pub async fn nack_all_unsettled(&self, requeue: bool) -> Result<usize> {
+        let settlement = Settlement::Nack {
+            delivery_tag: 0,
+            multiple: true,
+            requeue,
+        };
+        self.shared
+            .update_settlements(|settlements| {
+                let count = settlements.unsettled_count();
+                Ok((settlements.settle(settlement)?, count))
+            })
+            .await
+    }
+
+    /// Enable ack batching of the channel, see [`AckBatchingArguments`] for details.
+    ///
+    /// It can not be disabled once enabled.
+    /// Acks of messages delivered in manual ack mode are then accumulated, and sent as one ack with
+    /// `multiple = true` once all lower delivery tags are settled. Nacks and rejects are not delayed,
+    /// and accumulated acks of lower delivery tags are always sent before a nack with `multiple = true`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns error if the channel is closed.
+    ///
+    /// [`AckBatchingArguments`]: struct.AckBatchingArguments.html
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub async fn set_ack_batching(&self, args: AckBatchingArguments) -> Result<()> {
+        args.validate()?;
+        if !self.is_open() {
+            return Err(Error::ChannelUseError(format!(
+                "channel {} is closed",
+                self.channel_id()
+            )));
+        }
+        self.shared.settlements.lock().await.enable_batching(args);
+        self.shared.ack_batching.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Send all accumulated acks to server, it returns once they are sent.
+    ///
+    /// It does nothing if ack batching is not enabled.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the channel is closed.
+    pub async fn flush_acks(&self) -> Result<()> {
+        self.shared.flush_acks().await
+    }
+
+    /// See [AMQP_0-9-1 Reference](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.cancel)
+    ///
+    /// Returns consumer tag if succeed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns error if any failure in comunication with server.
+    pub async fn basic_cancel(&self, args: BasicCancelArguments) -> Result<String> {
+        args.validate()?;
+        let BasicCancelArguments {
+            consumer_tag,
+            no_wait,
+        } = args;
+
+        let cancel = Cancel::new(to_short_str("consumer_tag", consumer_tag.clone())?, no_wait);
+
+        // accumulated acks are sent before `cancel`
+        self.flush_acks().await?;
+        let consumer_tag = if args.no_wait {
+            self.shared
+                .outgoing_tx
+                .send((self.shared.channel_id, cancel.into_frame()))
+                .await?;
+            consumer_tag
+        } else {
+            let responder_rx = self.send_request(CancelOk::header(), cancel.into_frame())?;
+            let cancel_ok =
+                synchronous_request!(responder_rx, Frame::CancelOk, Error::ChannelUseError)?;
             cancel_ok.consumer_tag.into()
         };
 
@@ -856,48 +1399,96 @@ impl Channel {
         Ok(consumer_tag2)
     }
 
-    /// See [AMQP_0-9-1 Reference](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.get)
+    /// Cancel the consumer gracefully, without losing any delivery.
     ///
-    /// Either returns a tuple [`GetMessage`] or [`None`] if no message available.
+    /// Unlike [`basic_cancel`], the consumer keeps processing the deliveries received before server
+    /// confirms the cancellation, until `timeout` elapsed. Deliveries whose processing does not start
+    /// before then are nack-ed with `requeue = true`. It returns once the consumer finishes,
+    /// and waits at most another `timeout` for the delivery in process when `timeout` elapsed.
+    ///
+    /// Deliveries of a consumer in auto ack mode can not be requeued, so all of them are processed
+    /// regardless of `timeout`. Deliveries of [`basic_consume_rx`] are handled by the holder of the
+    /// receiver, so nothing is processed or requeued by this method.
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if `consumer_tag` is invalid.
+    /// Returns [`Error::InternalChannelError`] if the consumer does not finish in time,
+    /// the consumer is cancelled anyway and keeps draining in background.
     /// Returns error if any failure in comunication with server.
-    pub async fn basic_get(&self, args: BasicGetArguments) -> Result<Option<GetMessage>> {
-        let get = Get::new(0, args.queue.try_into().unwrap(), args.no_ack);
-
-        let (tx, mut rx) = mpsc::unbounded_channel();
-        let command = RegisterGetContentResponder { tx };
-        self.shared.dispatcher_mgmt_tx.send(
-            DispatcherManagementCommand::RegisterGetContentResponder(command),
-        )?;
+    ///
+    /// [`basic_cancel`]: struct.Channel.html#method.basic_cancel
+    /// [`basic_consume_rx`]: struct.Channel.html#method.basic_consume_rx
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    /// [`Error::InternalChannelError`]: ../error/enum.Error.html#variant.InternalChannelError
+    pub async fn basic_cancel_graceful(
+        &self,
+        consumer_tag: &str,
+        timeout: Duration,
+    ) -> Result<DrainSummary> {
+        BasicCancelArguments::new(consumer_tag).validate()?;
+        let cancel = Cancel::new(
+            to_short_str("consumer_tag", consumer_tag.to_owned())?,
+            false,
+        );
 
-        self.shared
-            .outgoing_tx
-            .send((self.shared.channel_id, get.into_frame()))
-            .await?;
-        let get_ok = match rx.recv().await.ok_or_else(|| {
-            Error::InternalChannelError("failed to receive response to Get".to_string())
-        })? {
-            Frame::GetEmpty(_, _) => return Ok(None),
-            Frame::GetOk(_, get_ok) => get_ok,
-            _ => unreachable!("expect GetOk or GetEmpty"),
+        // draining starts once server confirms `cancel-ok`, after which no delivery arrives
+        let (responder, responder_rx) = oneshot::channel();
+        let deadline = Instant::now() + timeout;
+        let cmd = DrainContentConsumer {
+            consumer_tag: consumer_tag.to_owned(),
+            request: DrainRequest {
+                deadline,
+                responder,
+            },
         };
+        self.shared
+            .dispatcher_mgmt_tx
+            .send(DispatcherManagementCommand::DrainContentConsumer(cmd))?;
+        let cancelled = async {
+            self.flush_acks().await?;
+            let responder_rx2 = self.send_request(CancelOk::header(), cancel.into_frame())?;
+            synchronous_request!(responder_rx2, Frame::CancelOk, Error::ChannelUseError)
+        }
+        .await;
+        if let Err(err) = cancelled {
+            // the consumer is not cancelled, do not drain it at a later cancellation
+            drop(responder_rx);
+            let cmd = AbortDrainContentConsumer {
+                consumer_tag: consumer_tag.to_owned(),
+            };
+            self.shared
+                .dispatcher_mgmt_tx
+                .send(DispatcherManagementCommand::AbortDrainContentConsumer(cmd))
+                .ok();
+            return Err(err);
+        }
 
-        let basic_properties = match rx.recv().await.ok_or_else(|| {
-            Error::InternalChannelError("failed to receive Get ContentHeader".to_string())
-        })? {
-            Frame::ContentHeader(header) => header.basic_properties,
-            _ => unreachable!("expect ContentHeader"),
-        };
+        let summary = time::timeout_at((deadline + timeout).into(), responder_rx)
+            .await
+            .map_err(|_| {
+                Error::InternalChannelError(format!("timeout draining consumer {}", consumer_tag))
+            })?
+            .map_err(|_| {
+                Error::InternalChannelError(format!("failed to drain consumer {}", consumer_tag))
+            })?;
+        // acks of the drained deliveries may be accumulated
+        self.flush_acks().await?;
+        Ok(summary)
+    }
 
-        let content = match rx.recv().await.ok_or_else(|| {
-            Error::InternalChannelError("failed to receive Get ContentBody".to_string())
-        })? {
-            Frame::ContentBody(content) => content.inner,
-            _ => unreachable!("expect ContentBody"),
-        };
-        Ok(Some((get_ok, basic_properties, content)))
+    /// See [AMQP_0-9-1 Reference](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.get)
+    ///
+    /// Either returns a tuple [`GetMessage`] or [`None`] if no message available.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns error if any failure in comunication with server.
+    pub async fn basic_get(&self, args: BasicGetArguments) -> Result<Option<GetMessage>> {
+        args.validate()?;
+        let mut rx = self.register_get(args, 1)?;
+        recv_get_message(&mut rx).await
     }
 
     /// See [AMQP_0-9-1 Reference](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.recover)
@@ -908,15 +1499,8 @@ impl Channel {
     pub async fn basic_recover(&self, requeue: bool) -> Result<()> {
         let recover = Recover::new(requeue);
 
-        let responder_rx = self.register_responder(RecoverOk::header()).await?;
-
-        let _method = synchronous_request!(
-            self.shared.outgoing_tx,
-            (self.shared.channel_id, recover.into_frame()),
-            responder_rx,
-            Frame::RecoverOk,
-            Error::ChannelUseError
-        )?;
+        let responder_rx = self.send_request(RecoverOk::header(), recover.into_frame())?;
+        let _method = synchronous_request!(responder_rx, Frame::RecoverOk, Error::ChannelUseError)?;
         Ok(())
     }
 
@@ -924,18 +1508,27 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::Blocked`] if the connection is blocked or the channel flow is paused by server,
+    /// and [`PublishGating::FailFast`] is set for the connection. If [`PublishGating::Wait`] is set,
+    /// it waits until publishing is resumed instead.
     /// Returns error in case of a network I/O failure. For data safety, use
     /// [publisher confirms](https://rabbitmq.com/publishers.html#data-safety).
+    ///
+    /// [`PublishGating::FailFast`]: ../connection/enum.PublishGating.html#variant.FailFast
+    /// [`PublishGating::Wait`]: ../connection/enum.PublishGating.html#variant.Wait
     pub async fn basic_publish(
         &self,
         basic_properties: BasicProperties,
         content: Vec<u8>,
         args: BasicPublishArguments,
     ) -> Result<()> {
+        args.validate()?;
+        self.check_publish_gate().await?;
         let mut publish = Publish::new(
             0,
-            args.exchange.try_into().unwrap(),
-            args.routing_key.try_into().unwrap(),
+            to_short_str("exchange", args.exchange)?,
+            to_short_str("routing_key", args.routing_key)?,
         );
         publish.set_mandatory(args.mandatory);
         publish.set_immediate(args.immediate);
@@ -961,19 +1554,29 @@ impl Channel {
 
 #[cfg(test)]
 mod tests {
-    use crate::callbacks::{DefaultChannelCallback, DefaultConnectionCallback};
+    use crate::callbacks::{ChannelCallback, DefaultChannelCallback, DefaultConnectionCallback};
     use crate::test_utils::setup_logging;
     use crate::{
         api::{
+            channel::{AckDeadline, Channel, ConsumerOptions, OrderingKey, UnsettledDelivery},
             channel::{QueueBindArguments, QueueDeclareArguments},
-            connection::{Connection, OpenConnectionArguments},
-            consumer::DefaultConsumer,
+            connection::{Connection, OpenConnectionArguments, PublishGating},
+            consumer::{AsyncConsumer, DefaultConsumer, HandlerError, Outcome, OutcomeConsumer},
+            events::ChannelEvent,
+            Result,
         },
-        frame::BasicProperties,
+        frame::{Ack, BasicProperties, Cancel, CloseChannel, Deliver, Nack, Return},
+        FieldTable,
     };
-    use tokio::time;
+    use async_trait::async_trait;
+    use tokio::{sync::mpsc, time};
 
-    use super::{BasicConsumeArguments, BasicPublishArguments, BasicQosArguments};
+    use super::{
+        AckBatchingArguments, BasicAckArguments, BasicCancelArguments, BasicConsumeArguments,
+        BasicGetArguments, BasicNackArguments, BasicPublishArguments, BasicQosArguments,
+        BasicRejectArguments, OrphanedDeliveryArguments,
+    };
+    use crate::api::error::Error;
 
     #[tokio::test(flavor = "multi_thread", worker_threads = 5)]
     async fn test_basic_consume_auto_ack() {
@@ -1051,6 +1654,690 @@ mod tests {
         time::sleep(time::Duration::from_secs(1)).await;
     }
 
+    #[tokio::test]
+    async fn test_ack_batching() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        channel
+            .set_ack_batching(AckBatchingArguments::new(10, time::Duration::from_secs(60)))
+            .await
+            .unwrap();
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        for _ in 0..3 {
+            channel
+                .basic_publish(
+                    BasicProperties::default(),
+                    b"hello".to_vec(),
+                    BasicPublishArguments::new("", &queue_name),
+                )
+                .await
+                .unwrap();
+        }
+        let mut delivery_tags = vec![];
+        while delivery_tags.len() < 3 {
+            if let Some((get_ok, ..)) = channel
+                .basic_get(BasicGetArguments::new(&queue_name))
+                .await
+                .unwrap()
+            {
+                delivery_tags.push(get_ok.delivery_tag());
+            }
+        }
+        // out of order completion
+        for delivery_tag in [delivery_tags[2], delivery_tags[0], delivery_tags[1]] {
+            channel
+                .basic_ack(BasicAckArguments::new(delivery_tag, false))
+                .await
+                .unwrap();
+        }
+        // already settled
+        let result = channel
+            .basic_ack(BasicAckArguments::new(delivery_tags[0], false))
+            .await;
+        assert!(matches!(result, Err(Error::DeliveryTagError(_))));
+        // accumulated acks are flushed before close
+        channel.close().await.unwrap();
+
+        let channel = connection.open_channel(None).await.unwrap();
+        let (_, message_count, _) = channel
+            .queue_declare(
+                QueueDeclareArguments::new(&queue_name)
+                    .passive(true)
+                    .finish(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(0, message_count);
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_unsettled_deliveries() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        for _ in 0..3 {
+            channel
+                .basic_publish(
+                    BasicProperties::default(),
+                    b"hello".to_vec(),
+                    BasicPublishArguments::new("", &queue_name),
+                )
+                .await
+                .unwrap();
+        }
+        let mut delivery_tags = vec![];
+        while delivery_tags.len() < 3 {
+            if let Some((get_ok, ..)) = channel
+                .basic_get(BasicGetArguments::new(&queue_name))
+                .await
+                .unwrap()
+            {
+                delivery_tags.push(get_ok.delivery_tag());
+            }
+        }
+        let unsettled = channel.unsettled_deliveries().await.unwrap();
+        assert_eq!(
+            delivery_tags,
+            unsettled
+                .iter()
+                .map(|delivery| delivery.delivery_tag)
+                .collect::<Vec<_>>()
+        );
+        assert!(unsettled
+            .iter()
+            .all(|delivery| delivery.consumer_tag.is_none()));
+
+        channel
+            .basic_ack(BasicAckArguments::new(delivery_tags[1], false))
+            .await
+            .unwrap();
+        // rejected client side, the channel is still open
+        let result = channel
+            .basic_reject(BasicRejectArguments::new(delivery_tags[1], true))
+            .await;
+        assert!(matches!(result, Err(Error::DeliveryTagError(_))));
+        let result = channel
+            .basic_ack(BasicAckArguments::new(delivery_tags[2] + 1, false))
+            .await;
+        assert!(matches!(result, Err(Error::DeliveryTagError(_))));
+
+        assert_eq!(2, channel.nack_all_unsettled(true).await.unwrap());
+        assert!(channel.unsettled_deliveries().await.unwrap().is_empty());
+
+        // wait for the requeue to take effect
+        time::sleep(time::Duration::from_millis(100)).await;
+        let (_, message_count, _) = channel
+            .queue_declare(
+                QueueDeclareArguments::new(&queue_name)
+                    .passive(true)
+                    .finish(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(2, message_count);
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_ack_deadline() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let mut events = channel.events();
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        let ack_deadline = AckDeadline::new(time::Duration::from_millis(500))
+            .warning(time::Duration::from_millis(200))
+            .finish();
+        let (_, mut messages_rx) = channel
+            .basic_consume_rx(
+                BasicConsumeArguments::new(&queue_name, "test_ack_deadline")
+                    .ack_deadline(Some(ack_deadline))
+                    .finish(),
+            )
+            .await
+            .unwrap();
+        channel
+            .basic_publish(
+                BasicProperties::default(),
+                b"hello".to_vec(),
+                BasicPublishArguments::new("", &queue_name),
+            )
+            .await
+            .unwrap();
+
+        // the delivery is never acked in time
+        let deliver = messages_rx.recv().await.unwrap().deliver.unwrap();
+        let mut expired = None;
+        while expired.is_none() {
+            match time::timeout(time::Duration::from_secs(2), events.recv())
+                .await
+                .unwrap()
+                .unwrap()
+            {
+                ChannelEvent::AckDeadlineApproaching(delivery) => {
+                    assert_eq!(deliver.delivery_tag(), delivery.delivery_tag);
+                }
+                ChannelEvent::AckDeadlineExpired(delivery) => expired = Some(delivery),
+                _ => {}
+            }
+        }
+        let expired = expired.unwrap();
+        assert_eq!(deliver.delivery_tag(), expired.delivery_tag);
+        assert_eq!(Some("test_ack_deadline".to_owned()), expired.consumer_tag);
+        let result = channel
+            .basic_ack(BasicAckArguments::new(deliver.delivery_tag(), false))
+            .await;
+        assert!(matches!(result, Err(Error::DeliveryTagError(_))));
+
+        // requeued and redelivered
+        let redeliver = messages_rx.recv().await.unwrap().deliver.unwrap();
+        assert!(redeliver.redelivered());
+        channel
+            .basic_ack(BasicAckArguments::new(redeliver.delivery_tag(), false))
+            .await
+            .unwrap();
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_settle_in_ack_deadline_callback() {
+        setup_logging();
+
+        /// Nack the delivery approaching its deadline.
+        struct NackingCallback(mpsc::UnboundedSender<u64>);
+        #[async_trait]
+        impl ChannelCallback for NackingCallback {
+            async fn close(&mut self, channel: &Channel, close: CloseChannel) -> Result<()> {
+                DefaultChannelCallback.close(channel, close).await
+            }
+            async fn cancel(&mut self, channel: &Channel, cancel: Cancel) -> Result<()> {
+                DefaultChannelCallback.cancel(channel, cancel).await
+            }
+            async fn flow(&mut self, channel: &Channel, active: bool) -> Result<bool> {
+                DefaultChannelCallback.flow(channel, active).await
+            }
+            async fn publish_ack(&mut self, channel: &Channel, ack: Ack) {
+                DefaultChannelCallback.publish_ack(channel, ack).await
+            }
+            async fn publish_nack(&mut self, channel: &Channel, nack: Nack) {
+                DefaultChannelCallback.publish_nack(channel, nack).await
+            }
+            async fn publish_return(
+                &mut self,
+                channel: &Channel,
+                ret: Return,
+                basic_properties: BasicProperties,
+                content: Vec<u8>,
+            ) {
+                DefaultChannelCallback
+                    .publish_return(channel, ret, basic_properties, content)
+                    .await
+            }
+            async fn ack_deadline_approaching(
+                &mut self,
+                channel: &Channel,
+                delivery: UnsettledDelivery,
+            ) {
+                channel
+                    .basic_nack(BasicNackArguments::new(delivery.delivery_tag, false, false))
+                    .await
+                    .unwrap();
+                self.0.send(delivery.delivery_tag).unwrap();
+            }
+        }
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        channel
+            .register_callback(NackingCallback(tx))
+            .await
+            .unwrap();
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        let ack_deadline = AckDeadline::new(time::Duration::from_millis(500))
+            .warning(time::Duration::from_millis(300))
+            .finish();
+        let (_, mut messages_rx) = channel
+            .basic_consume_rx(
+                BasicConsumeArguments::new(&queue_name, "test_settle_in_ack_deadline_callback")
+                    .ack_deadline(Some(ack_deadline))
+                    .finish(),
+            )
+            .await
+            .unwrap();
+        channel
+            .basic_publish(
+                BasicProperties::default(),
+                b"hello".to_vec(),
+                BasicPublishArguments::new("", &queue_name),
+            )
+            .await
+            .unwrap();
+
+        let deliver = messages_rx.recv().await.unwrap().deliver.unwrap();
+        let nacked = time::timeout(time::Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(deliver.delivery_tag(), nacked);
+        // settled by the callback, the dispatcher is still responsive
+        assert!(channel.unsettled_deliveries().await.unwrap().is_empty());
+        let result = channel
+            .basic_ack(BasicAckArguments::new(nacked, false))
+            .await;
+        assert!(matches!(result, Err(Error::DeliveryTagError(_))));
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_basic_cancel_graceful() {
+        setup_logging();
+
+        struct SlowConsumer;
+        #[async_trait]
+        impl AsyncConsumer for SlowConsumer {
+            async fn consume(
+                &mut self,
+                channel: &Channel,
+                deliver: Deliver,
+                _basic_properties: BasicProperties,
+                _content: Vec<u8>,
+            ) {
+                time::sleep(time::Duration::from_millis(200)).await;
+                channel
+                    .basic_ack(BasicAckArguments::new(deliver.delivery_tag(), false))
+                    .await
+                    .unwrap();
+            }
+        }
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        for _ in 0..5 {
+            channel
+                .basic_publish(
+                    BasicProperties::default(),
+                    b"hello".to_vec(),
+                    BasicPublishArguments::new("", &queue_name),
+                )
+                .await
+                .unwrap();
+        }
+        let consumer_tag = channel
+            .basic_consume(SlowConsumer, BasicConsumeArguments::new(&queue_name, ""))
+            .await
+            .unwrap();
+        // wait for all messages to be delivered
+        time::sleep(time::Duration::from_millis(100)).await;
+
+        assert!(matches!(
+            channel
+                .basic_cancel_graceful("", time::Duration::from_millis(300))
+                .await,
+            Err(Error::InvalidArgument(_))
+        ));
+        let summary = channel
+            .basic_cancel_graceful(&consumer_tag, time::Duration::from_millis(300))
+            .await
+            .unwrap();
+        assert_eq!(5, summary.processed + summary.requeued);
+        assert!(summary.processed > 0);
+        assert!(summary.requeued > 0);
+
+        // wait for the requeue to take effect
+        time::sleep(time::Duration::from_millis(100)).await;
+        let (_, message_count, _) = channel
+            .queue_declare(
+                QueueDeclareArguments::new(&queue_name)
+                    .passive(true)
+                    .finish(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.requeued, message_count as usize);
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_basic_cancel_graceful_timeout() {
+        setup_logging();
+
+        struct StuckConsumer;
+        #[async_trait]
+        impl AsyncConsumer for StuckConsumer {
+            async fn consume(
+                &mut self,
+                _channel: &Channel,
+                _deliver: Deliver,
+                _basic_properties: BasicProperties,
+                _content: Vec<u8>,
+            ) {
+                time::sleep(time::Duration::from_secs(1)).await;
+            }
+        }
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        channel
+            .basic_publish(
+                BasicProperties::default(),
+                b"hello".to_vec(),
+                BasicPublishArguments::new("", &queue_name),
+            )
+            .await
+            .unwrap();
+        let consumer_tag = channel
+            .basic_consume(StuckConsumer, BasicConsumeArguments::new(&queue_name, ""))
+            .await
+            .unwrap();
+        time::sleep(time::Duration::from_millis(100)).await;
+
+        let result = channel
+            .basic_cancel_graceful(&consumer_tag, time::Duration::from_millis(100))
+            .await;
+        assert!(matches!(result, Err(Error::InternalChannelError(_))));
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_basic_consume_with_outcome() {
+        setup_logging();
+
+        struct TestConsumer(mpsc::UnboundedSender<Vec<u8>>);
+        #[async_trait]
+        impl OutcomeConsumer for TestConsumer {
+            async fn handle(
+                &mut self,
+                _channel: &Channel,
+                _deliver: Deliver,
+                _basic_properties: BasicProperties,
+                content: Vec<u8>,
+            ) -> std::result::Result<Outcome, HandlerError> {
+                self.0.send(content.clone()).unwrap();
+                match content.as_slice() {
+                    b"ack" => Ok(Outcome::Ack),
+                    b"error" => Err("failed to handle".into()),
+                    _ => panic!("handler panicked"),
+                }
+            }
+        }
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        for content in ["ack", "ack", "error", "panic", "ack"] {
+            channel
+                .basic_publish(
+                    BasicProperties::default(),
+                    content.as_bytes().to_vec(),
+                    BasicPublishArguments::new("", &queue_name),
+                )
+                .await
+                .unwrap();
+        }
+
+        // auto ack is not allowed
+        let result = channel
+            .basic_consume_with_outcome(
+                TestConsumer(mpsc::unbounded_channel().0),
+                BasicConsumeArguments::new(&queue_name, "")
+                    .auto_ack(true)
+                    .finish(),
+                ConsumerOptions::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let consumer_tag = channel
+            .basic_consume_with_outcome(
+                TestConsumer(tx),
+                BasicConsumeArguments::new(&queue_name, ""),
+                ConsumerOptions::new()
+                    .default_outcome(Outcome::Reject { requeue: false })
+                    .max_ack_batch(2)
+                    .finish(),
+            )
+            .await
+            .unwrap();
+        for _ in 0..5 {
+            time::timeout(time::Duration::from_secs(2), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        }
+        channel
+            .basic_cancel(BasicCancelArguments::new(&consumer_tag))
+            .await
+            .unwrap();
+        // wait for the last ack to be flushed
+        time::sleep(time::Duration::from_millis(100)).await;
+        channel.close().await.unwrap();
+
+        // all deliveries are settled, nothing requeued after channel closed
+        let channel = connection.open_channel(None).await.unwrap();
+        let (_, message_count, _) = channel
+            .queue_declare(
+                QueueDeclareArguments::new(&queue_name)
+                    .passive(true)
+                    .finish(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(0, message_count);
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn test_basic_consume_concurrent() {
+        setup_logging();
+
+        #[derive(Clone)]
+        struct TestConsumer(mpsc::UnboundedSender<(String, u64)>);
+        #[async_trait]
+        impl OutcomeConsumer for TestConsumer {
+            async fn handle(
+                &mut self,
+                _channel: &Channel,
+                _deliver: Deliver,
+                basic_properties: BasicProperties,
+                content: Vec<u8>,
+            ) -> std::result::Result<Outcome, HandlerError> {
+                let key = basic_properties
+                    .headers()
+                    .unwrap()
+                    .get(&"key".try_into().unwrap());
+                let seq: u64 = String::from_utf8(content).unwrap().parse().unwrap();
+                // later deliveries complete earlier
+                time::sleep(time::Duration::from_millis(50 - seq * 2)).await;
+                self.0.send((key.unwrap().to_string(), seq)).unwrap();
+                Ok(Outcome::Ack)
+            }
+        }
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        for seq in 0..20u64 {
+            let mut headers = FieldTable::new();
+            let key = if seq % 2 == 0 { "even" } else { "odd" };
+            headers.insert("key".try_into().unwrap(), key.into());
+            channel
+                .basic_publish(
+                    BasicProperties::default().with_headers(headers).finish(),
+                    seq.to_string().into_bytes(),
+                    BasicPublishArguments::new("", &queue_name),
+                )
+                .await
+                .unwrap();
+        }
+        channel
+            .basic_qos(BasicQosArguments::new(0, 20, false))
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let consumer_tag = channel
+            .basic_consume_concurrent(
+                TestConsumer(tx),
+                BasicConsumeArguments::new(&queue_name, ""),
+                ConsumerOptions::new()
+                    .concurrency(4)
+                    .max_ack_batch(5)
+                    .ordering_key(Some(OrderingKey::Header("key".to_owned())))
+                    .finish(),
+            )
+            .await
+            .unwrap();
+        let mut last_seq = std::collections::HashMap::new();
+        for _ in 0..20 {
+            let (key, seq) = time::timeout(time::Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            // deliveries of same key are handled in order
+            if let Some(last) = last_seq.insert(key, seq) {
+                assert!(last < seq);
+            }
+        }
+        channel
+            .basic_cancel(BasicCancelArguments::new(&consumer_tag))
+            .await
+            .unwrap();
+        time::sleep(time::Duration::from_millis(100)).await;
+        channel.close().await.unwrap();
+
+        // all deliveries are acked
+        let channel = connection.open_channel(None).await.unwrap();
+        let (_, message_count, _) = channel
+            .queue_declare(
+                QueueDeclareArguments::new(&queue_name)
+                    .passive(true)
+                    .finish(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(0, message_count);
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_orphaned_delivery_of_unregistered_consumer() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        channel
+            .set_orphaned_delivery_handling(
+                OrphanedDeliveryArguments::new()
+                    .expiry(time::Duration::from_millis(100))
+                    .requeue(true)
+                    .finish(),
+            )
+            .await
+            .unwrap();
+        let (queue_name, _, _) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        channel
+            .basic_publish(
+                BasicProperties::default(),
+                b"orphaned".to_vec(),
+                BasicPublishArguments::new("", &queue_name),
+            )
+            .await
+            .unwrap();
+
+        // consumer is confirmed by server but never registered,
+        // as if `basic_consume` is cancelled after `consume-ok`
+        let consumer_tag = channel
+            .request_basic_consume(BasicConsumeArguments::new(&queue_name, "unregistered"))
+            .await
+            .unwrap();
+        time::sleep(time::Duration::from_millis(500)).await;
+        channel
+            .basic_cancel(BasicCancelArguments::new(&consumer_tag))
+            .await
+            .unwrap();
+        time::sleep(time::Duration::from_millis(500)).await;
+
+        // the orphaned delivery is requeued instead of left unacked
+        let (get_ok, _, content) = channel
+            .basic_get(BasicGetArguments::new(&queue_name))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(get_ok.redelivered());
+        assert_eq!(b"orphaned".to_vec(), content);
+
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
     #[tokio::test]
     async fn test_basic_publish() {
         setup_logging();
@@ -1089,6 +2376,62 @@ mod tests {
         time::sleep(time::Duration::from_secs(1)).await;
     }
 
+    #[tokio::test]
+    async fn test_basic_publish_gating() {
+        setup_logging();
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami")
+            .connection_name("test_basic_publish_gating")
+            .publish_gating(PublishGating::FailFast)
+            .finish();
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let args = BasicPublishArguments::new("amq.topic", "amqprs.gating");
+
+        // simulate notifications from server
+        connection.set_blocked(Some("low on memory".to_owned()));
+        match channel
+            .basic_publish(BasicProperties::default(), vec![], args.clone())
+            .await
+        {
+            Err(Error::Blocked(_)) => {}
+            other => panic!("expect blocked error, but got {:?}", other),
+        }
+        connection.set_blocked(None);
+        channel.set_flow_active(false);
+        assert!(matches!(
+            channel
+                .basic_publish(BasicProperties::default(), vec![], args.clone())
+                .await,
+            Err(Error::Blocked(_))
+        ));
+        channel.set_flow_active(true);
+        channel
+            .basic_publish(BasicProperties::default(), vec![], args)
+            .await
+            .unwrap();
+
+        // wait until unblocked
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami")
+            .publish_gating(PublishGating::Wait)
+            .finish();
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        connection.set_blocked(Some("low on memory".to_owned()));
+        let publisher = {
+            let channel = channel.clone();
+            tokio::spawn(async move {
+                let args = BasicPublishArguments::new("amq.topic", "amqprs.gating");
+                channel
+                    .basic_publish(BasicProperties::default(), vec![], args)
+                    .await
+            })
+        };
+        time::sleep(time::Duration::from_millis(100)).await;
+        assert!(!publisher.is_finished());
+        connection.set_blocked(None);
+        publisher.await.unwrap().unwrap();
+    }
+
     #[tokio::test]
     async fn test_basic_qos() {
         setup_logging();
@@ -1138,4 +2481,55 @@ mod tests {
         channel.close().await.unwrap();
         connection.close().await.unwrap();
     }
+
+    #[test]
+    fn test_validate_arguments() {
+        assert!(BasicQosArguments::new(0, 100, false).validate().is_ok());
+        assert!(BasicQosArguments::new(1024, 100, false).validate().is_err());
+
+        assert!(BasicConsumeArguments::new("q", "").validate().is_ok());
+        assert!(BasicConsumeArguments::new("q", "")
+            .no_wait(true)
+            .finish()
+            .validate()
+            .is_err());
+        let ack_deadline = AckDeadline::new(time::Duration::from_secs(60));
+        assert!(BasicConsumeArguments::new("q", "")
+            .ack_deadline(Some(ack_deadline))
+            .finish()
+            .validate()
+            .is_ok());
+        assert!(BasicConsumeArguments::new("q", "")
+            .auto_ack(true)
+            .ack_deadline(Some(ack_deadline))
+            .finish()
+            .validate()
+            .is_err());
+        assert!(BasicConsumeArguments::new("q", "")
+            .ack_deadline(Some(
+                AckDeadline::new(time::Duration::from_secs(60))
+                    .warning(time::Duration::from_secs(60))
+                    .finish()
+            ))
+            .finish()
+            .validate()
+            .is_err());
+        assert!(BasicCancelArguments::new("").validate().is_err());
+
+        assert!(BasicAckArguments::new(0, true).validate().is_ok());
+        assert!(BasicAckArguments::new(0, false).validate().is_err());
+        assert!(BasicNackArguments::new(0, false, true).validate().is_err());
+        assert!(BasicRejectArguments::new(0, true).validate().is_err());
+
+        let routing_key = "k".repeat(256);
+        assert!(matches!(
+            BasicPublishArguments::new("amq.topic", &routing_key).validate(),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(BasicPublishArguments::new("amq.topic", "k")
+            .immediate(true)
+            .finish()
+            .validate()
+            .is_err());
+    }
 }
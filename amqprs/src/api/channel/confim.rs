@@ -39,15 +39,9 @@ impl Channel {
                 .await?;
             Ok(())
         } else {
-            let responder_rx = self.register_responder(SelectOk::header()).await?;
-
-            let _method = synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.shared.channel_id, select.into_frame()),
-                responder_rx,
-                Frame::SelectOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(SelectOk::header(), select.into_frame())?;
+            let _method =
+                synchronous_request!(responder_rx, Frame::SelectOk, Error::ChannelUseError)?;
             Ok(())
         }
     }
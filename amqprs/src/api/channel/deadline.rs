@@ -0,0 +1,277 @@
+use std::collections::{BTreeMap, HashMap};
+
+use tokio::time;
+
+use crate::api::{consumer::Outcome, validation::check_flags, Result};
+
+use super::Settlement;
+
+////////////////////////////////////////////////////////////////////////////////
+/// Ack deadline of a consumer, see [`BasicConsumeArguments::ack_deadline`].
+///
+/// Server closes the channel if a delivery stays unacknowledged longer than its `consumer_timeout`,
+/// which affects every consumer of the channel. With an ack deadline shorter than that, client warns
+/// by [`ChannelCallback::ack_deadline_approaching`] `warning` before the deadline, and settles the
+/// delivery as `on_expiry` once the deadline passes. Later settlement of the delivery by the consumer
+/// fails with [`Error::DeliveryTagError`] instead.
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::AckDeadline;
+/// # use amqprs::consumer::Outcome;
+/// # use std::time::Duration;
+/// let x = AckDeadline::new(Duration::from_secs(600))
+///     .warning(Duration::from_secs(60))
+///     .on_expiry(Outcome::Reject { requeue: false })
+///     .finish();
+/// ```
+///
+/// [`BasicConsumeArguments::ack_deadline`]: struct.BasicConsumeArguments.html#structfield.ack_deadline
+/// [`ChannelCallback::ack_deadline_approaching`]: ../callbacks/trait.ChannelCallback.html#method.ack_deadline_approaching
+/// [`Error::DeliveryTagError`]: ../error/enum.Error.html#variant.DeliveryTagError
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AckDeadline {
+    /// Max time a delivery stays unsettled since it is received by client.
+    pub timeout: time::Duration,
+    /// How long before the deadline to warn, zero to not warn. Default: one tenth of `timeout`.
+    pub warning: time::Duration,
+    /// How to settle the expired delivery. Default: `Outcome::Nack { requeue: true }`.
+    pub on_expiry: Outcome,
+}
+
+impl AckDeadline {
+    /// Create new deadline with defaults.
+    pub fn new(timeout: time::Duration) -> Self {
+        Self {
+            timeout,
+            warning: timeout / 10,
+            on_expiry: Outcome::Nack { requeue: true },
+        }
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        timeout, time::Duration
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        warning, time::Duration
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        on_expiry, Outcome
+    }
+
+    /// Finish chained configuration and return new deadline.
+    pub fn finish(&mut self) -> Self {
+        *self
+    }
+
+    /// Validate the deadline, it is also done by [`BasicConsumeArguments::validate`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `timeout` is zero, `warning` is not shorter than
+    /// `timeout`, or `on_expiry` is [`Outcome::Ack`].
+    ///
+    /// [`BasicConsumeArguments::validate`]: struct.BasicConsumeArguments.html#method.validate
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    /// [`Outcome::Ack`]: ../consumer/enum.Outcome.html#variant.Ack
+    pub fn validate(&self) -> Result<()> {
+        check_flags(
+            !self.timeout.is_zero(),
+            "ack_deadline: timeout must not be zero",
+        )?;
+        check_flags(
+            self.warning < self.timeout,
+            "ack_deadline: warning must be shorter than timeout",
+        )?;
+        check_flags(
+            self.on_expiry != Outcome::Ack,
+            "ack_deadline: expired delivery can not be acked",
+        )
+    }
+
+    /// Settlement of the delivery once the deadline passes.
+    pub(crate) fn expiry_settlement(&self, delivery_tag: u64) -> Settlement {
+        match self.on_expiry {
+            Outcome::Reject { requeue } => Settlement::Reject {
+                delivery_tag,
+                requeue,
+            },
+            Outcome::Nack { requeue } => Settlement::Nack {
+                delivery_tag,
+                multiple: false,
+                requeue,
+            },
+            // rejected by `validate`, never ack a delivery which is not processed
+            Outcome::Ack => Settlement::Nack {
+                delivery_tag,
+                multiple: false,
+                requeue: true,
+            },
+        }
+    }
+}
+
+/// Scheduled warnings and expiries of ack deadlines, in the order of time.
+///
+/// Entries of a delivery are removed once it is settled, see [`Settlements`].
+///
+/// [`Settlements`]: struct.Settlements.html
+#[derive(Default)]
+pub(crate) struct AckDeadlines {
+    /// keyed by (due time, delivery tag, `true` if expiry)
+    events: BTreeMap<(time::Instant, u64, bool), AckDeadline>,
+    /// expiry time of scheduled deliveries, to find their events
+    expiries: HashMap<u64, time::Instant>,
+}
+
+impl AckDeadlines {
+    /// Schedule the warning and expiry of a delivery received at `received_at`,
+    /// replacing the events already scheduled for the delivery.
+    pub fn schedule(
+        &mut self,
+        delivery_tag: u64,
+        received_at: time::Instant,
+        deadline: AckDeadline,
+    ) {
+        self.remove(delivery_tag);
+        let expiry = received_at + deadline.timeout;
+        if !deadline.warning.is_zero() {
+            self.events
+                .insert((expiry - deadline.warning, delivery_tag, false), deadline);
+        }
+        self.events.insert((expiry, delivery_tag, true), deadline);
+        self.expiries.insert(delivery_tag, expiry);
+    }
+
+    /// Remove the events of a delivery, e.g. it is settled.
+    pub fn remove(&mut self, delivery_tag: u64) {
+        let expiry = match self.expiries.remove(&delivery_tag) {
+            Some(expiry) => expiry,
+            None => return,
+        };
+        if let Some(deadline) = self.events.remove(&(expiry, delivery_tag, true)) {
+            if !deadline.warning.is_zero() {
+                self.events
+                    .remove(&(expiry - deadline.warning, delivery_tag, false));
+            }
+        }
+    }
+
+    /// Remove all events.
+    pub fn clear(&mut self) {
+        self.events.clear();
+        self.expiries.clear();
+    }
+
+    /// Returns the time of the earliest event.
+    pub fn next_due(&self) -> Option<time::Instant> {
+        self.events.keys().next().map(|&(due, ..)| due)
+    }
+
+    /// Take the earliest event due by `now`, returns the delivery tag, `true` if expired,
+    /// and the deadline.
+    pub fn pop_due(&mut self, now: time::Instant) -> Option<(u64, bool, AckDeadline)> {
+        let key = *self.events.keys().next()?;
+        if key.0 > now {
+            return None;
+        }
+        let deadline = self.events.remove(&key)?;
+        if key.2 {
+            self.expiries.remove(&key.1);
+        }
+        Some((key.1, key.2, deadline))
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use tokio::time;
+
+    use super::{AckDeadline, AckDeadlines};
+    use crate::api::consumer::Outcome;
+
+    #[test]
+    fn test_ack_deadlines() {
+        let mut deadlines = AckDeadlines::default();
+        assert!(deadlines.next_due().is_none());
+
+        let now = time::Instant::now();
+        let deadline = AckDeadline::new(time::Duration::from_secs(10));
+        deadlines.schedule(2, now + time::Duration::from_secs(1), deadline);
+        deadlines.schedule(1, now, deadline);
+        deadlines.schedule(
+            3,
+            now,
+            AckDeadline::new(time::Duration::from_secs(10))
+                .warning(time::Duration::ZERO)
+                .finish(),
+        );
+        assert_eq!(
+            Some(now + time::Duration::from_secs(9)),
+            deadlines.next_due()
+        );
+        assert!(deadlines.pop_due(now).is_none());
+
+        // warnings and expiries in the order of time
+        let later = now + time::Duration::from_secs(11);
+        let mut events = vec![];
+        while let Some((delivery_tag, expired, _)) = deadlines.pop_due(later) {
+            events.push((delivery_tag, expired));
+        }
+        assert_eq!(
+            vec![(1, false), (1, true), (2, false), (3, true), (2, true)],
+            events
+        );
+        assert!(deadlines.next_due().is_none());
+        assert_eq!(0, deadlines.expiries.len());
+    }
+
+    #[test]
+    fn test_remove_ack_deadlines() {
+        let mut deadlines = AckDeadlines::default();
+        let now = time::Instant::now();
+        let deadline = AckDeadline::new(time::Duration::from_secs(10));
+        deadlines.schedule(1, now, deadline);
+        deadlines.schedule(2, now, deadline);
+        // rescheduling replaces the events
+        deadlines.schedule(2, now + time::Duration::from_secs(1), deadline);
+        assert_eq!(2, deadlines.expiries.len());
+
+        // the warning is taken, the expiry is still removed
+        assert_eq!(
+            Some((1, false)),
+            deadlines
+                .pop_due(now + time::Duration::from_secs(9))
+                .map(|(tag, expired, _)| (tag, expired))
+        );
+        deadlines.remove(1);
+        deadlines.remove(2);
+        deadlines.remove(3);
+        assert_eq!(0, deadlines.expiries.len());
+        assert!(deadlines.next_due().is_none());
+    }
+
+    #[test]
+    fn test_validate_deadline() {
+        let timeout = time::Duration::from_secs(10);
+        assert!(AckDeadline::new(timeout).validate().is_ok());
+        assert!(AckDeadline::new(time::Duration::ZERO).validate().is_err());
+        assert!(AckDeadline::new(timeout)
+            .warning(timeout)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(AckDeadline::new(timeout)
+            .on_expiry(Outcome::Ack)
+            .finish()
+            .validate()
+            .is_err());
+    }
+}
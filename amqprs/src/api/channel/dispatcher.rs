@@ -1,5 +1,6 @@
 use std::collections::{HashMap, VecDeque};
 
+use amqp_serde::types::ShortUint;
 use tokio::{
     sync::{mpsc, oneshot},
     task::yield_now,
@@ -7,16 +8,25 @@ use tokio::{
 };
 
 use crate::{
-    api::{callbacks::ChannelCallback, channel::ReturnMessage},
+    api::{
+        callbacks::ChannelCallback, channel::ReturnMessage, connection::CloseReason, error::Error,
+        events::ChannelEvent,
+    },
     channel::GetOkMessage,
-    frame::{CancelOk, CloseChannelOk, ContentBody, FlowOk, Frame, MethodHeader},
-    net::IncomingMessage,
+    frame::{
+        CancelOk, CloseChannel, CloseChannelOk, ContentBody, FlowOk, Frame, MethodHeader,
+        FRAME_ERROR, UNEXPECTED_FRAME,
+    },
+    net::{ConnManagementCommand, IncomingMessage, IncomingResponse},
     BasicProperties, Return,
 };
 #[cfg(feature = "traces")]
-use tracing::{debug, error, info, trace};
+use tracing::{debug, error, info, trace, warn};
 
-use super::{Channel, ConsumerMessage, DispatcherManagementCommand};
+use super::{
+    AckDeadline, Channel, ConsumerMessage, DispatcherManagementCommand, DrainRequest, DrainSummary,
+    OrphanedDeliveryArguments, RegisterGetContentResponder, RegisterOneshotResponder, Settlement,
+};
 
 /// Assumption:
 /// Depends on total number of consumers per channel, a reasonable value
@@ -28,7 +38,21 @@ const CONSUMER_PURGE_INTERVAL: time::Duration = time::Duration::from_secs(10);
 /// Consumer is expected to be registered right after `consume/consume-ok` handshake is done
 /// which can't take longer than `5` seconds.
 /// After consumer is canceled, all on-the-fly messages should be received within `5` seconds
-const CONSUMER_EXPIRY_PERIOD: time::Duration = time::Duration::from_secs(5);
+///
+/// It is the default expiry of orphaned deliveries, see [`OrphanedDeliveryArguments`].
+pub(super) const CONSUMER_EXPIRY_PERIOD: time::Duration = time::Duration::from_secs(5);
+
+/// Lower bound of purge interval, when a short expiry of orphaned deliveries is configured.
+const MIN_CONSUMER_PURGE_INTERVAL: time::Duration = time::Duration::from_millis(100);
+
+/// Max number of outstanding `get` requests of a batch.
+const MAX_PIPELINED_GETS: usize = 10;
+
+/// Synchronous request waiting for its turn to be sent to server.
+enum PendingRequest {
+    Oneshot(RegisterOneshotResponder),
+    GetContent(RegisterGetContentResponder),
+}
 
 /// Resource for handling consumer messages.
 struct ConsumerResource {
@@ -39,26 +63,49 @@ struct ConsumerResource {
     tx: Option<mpsc::UnboundedSender<ConsumerMessage>>,
     /// expiry time of fifo buffer
     expiration: Option<time::Instant>,
+    /// ack mode of the consumer, unknown until server confirms the consumer.
+    no_ack: Option<bool>,
+    /// tx half to request draining of the consumer task.
+    drain_tx: Option<oneshot::Sender<DrainRequest>>,
+    /// ack deadline of deliveries to the consumer.
+    ack_deadline: Option<AckDeadline>,
 }
 
 impl ConsumerResource {
-    fn new() -> Self {
+    fn new(expiry: time::Duration, no_ack: Option<bool>) -> Self {
         Self {
             fifo: VecDeque::new(),
             tx: None,
-            expiration: Some(time::Instant::now() + CONSUMER_EXPIRY_PERIOD),
+            expiration: Some(time::Instant::now() + expiry),
+            no_ack,
+            drain_tx: None,
+            ack_deadline: None,
         }
     }
 
     fn register_tx(
         &mut self,
         tx: mpsc::UnboundedSender<ConsumerMessage>,
+        no_ack: bool,
+        drain_tx: Option<oneshot::Sender<DrainRequest>>,
+        ack_deadline: Option<AckDeadline>,
     ) -> Option<mpsc::UnboundedSender<ConsumerMessage>> {
         // once consumer's tx half is registered, clear the expiry timer
         self.expiration.take();
+        self.no_ack = Some(no_ack);
+        self.drain_tx = drain_tx;
+        self.ack_deadline = ack_deadline;
         self.tx.replace(tx)
     }
 
+    /// Drop the tx half so the consumer task exits, and start the expiry timer for
+    /// deliveries which may still arrive afterwards.
+    fn retire(&mut self, expiry: time::Duration) {
+        self.tx.take();
+        self.drain_tx.take();
+        self.expiration = Some(time::Instant::now() + expiry);
+    }
+
     fn get_tx(&self) -> Option<&mpsc::UnboundedSender<ConsumerMessage>> {
         self.tx.as_ref()
     }
@@ -76,14 +123,36 @@ impl ConsumerResource {
     }
 }
 
+/// State of receiving a method followed by content frames.
+///
+/// It returns to `Initial` once the content is complete.
 enum State {
     Initial,
     Deliver,
     GetOk,
-    GetEmpty,
     Return,
 }
 
+/// Append a content body frame to the content buffer.
+///
+/// Returns `true` if the content is complete, or the reply code and text if server violates the protocol.
+fn append_content_body(
+    content: &mut Option<Vec<u8>>,
+    remaining: &mut usize,
+    body: &[u8],
+) -> std::result::Result<bool, (ShortUint, &'static str)> {
+    let buffer = content.as_mut().ok_or((
+        UNEXPECTED_FRAME,
+        "content body received before content header",
+    ))?;
+    *remaining = remaining.checked_sub(body.len()).ok_or((
+        FRAME_ERROR,
+        "content body exceeds the body size of content header",
+    ))?;
+    buffer.extend_from_slice(body);
+    Ok(*remaining == 0)
+}
+
 /// Dispatcher for a channel.
 ///
 /// Each channel will spawn a dispatcher.
@@ -94,10 +163,31 @@ pub(crate) struct ChannelDispatcher {
     dispatcher_rx: mpsc::UnboundedReceiver<IncomingMessage>,
     dispatcher_mgmt_rx: mpsc::UnboundedReceiver<DispatcherManagementCommand>,
     consumer_resources: HashMap<String, ConsumerResource>,
-    get_content_responder: Option<mpsc::UnboundedSender<IncomingMessage>>,
-    responders: HashMap<&'static MethodHeader, oneshot::Sender<IncomingMessage>>,
+    /// responders of `get` requests in the order of requests sent to server
+    get_content_responders: VecDeque<RegisterGetContentResponder>,
+    /// responders of synchronous requests in the order of requests sent to server
+    responders: HashMap<&'static MethodHeader, VecDeque<oneshot::Sender<IncomingResponse>>>,
+    /// synchronous requests waiting for the in-flight request to complete,
+    /// only one synchronous request is in flight per channel as the protocol requires.
+    pending_requests: VecDeque<PendingRequest>,
+    /// `true` if a synchronous request is sent and its response is not yet received.
+    is_request_in_flight: bool,
     callback: Option<Box<dyn ChannelCallback + Send + 'static>>,
     state: State,
+    /// how to handle deliveries without registered consumer
+    orphan_handling: OrphanedDeliveryArguments,
+    /// tx half of fallback consumer task to receive orphaned deliveries
+    fallback_tx: Option<mpsc::UnboundedSender<ConsumerMessage>>,
+    /// drain requests of consumers waiting for `cancel-ok`
+    pending_drains: HashMap<String, DrainRequest>,
+    /// ack mode of consumers confirmed by server and not yet cancelled,
+    /// so deliveries can be settled even if the consumer is never registered.
+    consumer_ack_modes: HashMap<String, bool>,
+    /// ack mode of the in-flight `consume` request.
+    consume_no_ack: Option<bool>,
+    /// `true` if client is closing the channel due to protocol error,
+    /// then frames are discarded until server responds `close-ok`.
+    is_closing: bool,
 }
 /////////////////////////////////////////////////////////////////////////////
 impl ChannelDispatcher {
@@ -111,32 +201,47 @@ impl ChannelDispatcher {
             dispatcher_rx,
             dispatcher_mgmt_rx,
             consumer_resources: HashMap::new(),
-            get_content_responder: None,
+            get_content_responders: VecDeque::new(),
             responders: HashMap::new(),
+            pending_requests: VecDeque::new(),
+            is_request_in_flight: false,
             callback: None,
+            orphan_handling: OrphanedDeliveryArguments::default(),
+            fallback_tx: None,
+            pending_drains: HashMap::new(),
+            consumer_ack_modes: HashMap::new(),
+            consume_no_ack: None,
             state: State::Initial,
+            is_closing: false,
         }
     }
 
     /// Return the consumer resource if it always exists, otherwise create new one.
-    fn get_or_new_consumer_resource(&mut self, consumer_tag: &String) -> &mut ConsumerResource {
-        if !self.consumer_resources.contains_key(consumer_tag) {
-            let resource = ConsumerResource::new();
-            self.consumer_resources
-                .insert(consumer_tag.clone(), resource);
+    fn get_or_new_consumer_resource(&mut self, consumer_tag: &str) -> &mut ConsumerResource {
+        let no_ack = self.consumer_ack_modes.get(consumer_tag).copied();
+        self.consumer_resources
+            .entry(consumer_tag.to_owned())
+            .or_insert_with(|| ConsumerResource::new(self.orphan_handling.expiry, no_ack))
+    }
+
+    /// Record ack mode of a consumer confirmed by server.
+    fn set_consumer_ack_mode(&mut self, consumer_tag: String, no_ack: bool) {
+        if let Some(consumer) = self.consumer_resources.get_mut(&consumer_tag) {
+            consumer.no_ack.get_or_insert(no_ack);
         }
-        self.consumer_resources.get_mut(consumer_tag).unwrap()
+        self.consumer_ack_modes.insert(consumer_tag, no_ack);
     }
 
-    /// purge expired consumer resource
-    fn purge_consumer_resource(&mut self) {
+    /// purge expired consumer resource, and handle its orphaned deliveries.
+    async fn purge_consumer_resource(&mut self) {
         // find all resources that are expired
+        let now = time::Instant::now();
         let purge_keys: Vec<String> = self
             .consumer_resources
             .iter()
             .filter_map(|(k, v)| {
                 if let Some(expiration) = v.get_expiration() {
-                    if expiration < &time::Instant::now() {
+                    if expiration < &now {
                         return Some(k.clone());
                     }
                 }
@@ -146,37 +251,192 @@ impl ChannelDispatcher {
 
         // purge expired resources
         for key in purge_keys {
-            self.consumer_resources.remove(&key);
+            if let Some(consumer) = self.consumer_resources.remove(&key) {
+                #[cfg(feature = "traces")]
+                debug!(
+                    "purge stale consumer resource {} on channel {}, total orphaned deliveries: {}",
+                    key,
+                    self.channel,
+                    consumer.fifo.len()
+                );
+                for message in consumer.fifo {
+                    self.handle_orphaned_delivery(message, consumer.no_ack)
+                        .await;
+                }
+            }
+        }
+    }
+
+    /// Forward orphaned delivery to fallback consumer, or requeue it, otherwise drop it.
+    ///
+    /// Delivery of unknown ack mode is kept tracked, because it may still need to be settled.
+    async fn handle_orphaned_delivery(&mut self, message: ConsumerMessage, no_ack: Option<bool>) {
+        let message = match self.fallback_tx {
+            Some(ref fallback_tx) => match fallback_tx.send(message) {
+                Ok(_) => return,
+                // fallback consumer task has exited
+                Err(err) => {
+                    self.fallback_tx.take();
+                    err.0
+                }
+            },
+            None => message,
+        };
+        let delivery_tag = match message.deliver {
+            Some(ref deliver) => deliver.delivery_tag(),
+            None => return,
+        };
+        // only deliveries of consumer in manual ack mode can be requeued,
+        // otherwise server closes the channel due to unknown delivery tag
+        if self.orphan_handling.requeue && no_ack == Some(false) {
+            let settlement = Settlement::Nack {
+                delivery_tag,
+                multiple: false,
+                requeue: true,
+            };
+            self.channel.shared.settle(settlement).await.ok();
+        } else {
             #[cfg(feature = "traces")]
-            debug!(
-                "purge stale consumer resource {} on channel {}",
-                key, self.channel
+            warn!(
+                "drop orphaned delivery {} on channel {}",
+                delivery_tag, self.channel
             );
+            // never settled by client, do not hold back accumulated acks
+            if no_ack.is_some() {
+                self.channel
+                    .shared
+                    .settlements
+                    .lock()
+                    .await
+                    .forget(delivery_tag);
+            }
         }
     }
-    /// Remove the consumer resource.
+
+    /// Retire the consumer resource.
     ///
     /// Becuase the tx channel will drop, the consumer task will also exit.
-    fn remove_consumer_resource(&mut self, consumer_tag: &String) -> Option<ConsumerResource> {
-        self.consumer_resources.remove(consumer_tag)
+    /// The resource is kept until expiry to handle deliveries which may still arrive.
+    fn retire_consumer_resource(&mut self, consumer_tag: &String) -> Option<&ConsumerResource> {
+        let expiry = self.orphan_handling.expiry;
+        let consumer = self.consumer_resources.get_mut(consumer_tag)?;
+        consumer.retire(expiry);
+        Some(consumer)
+    }
+
+    /// Start draining the consumer if requested, once server confirms its cancellation.
+    ///
+    /// If the consumer does not support draining or its task has exited, respond an empty summary.
+    fn start_drain(&mut self, consumer_tag: &str) {
+        let request = match self.pending_drains.remove(consumer_tag) {
+            // the request may have been cancelled
+            Some(request) if !request.responder.is_closed() => request,
+            _ => return,
+        };
+        let request = match self
+            .consumer_resources
+            .get_mut(consumer_tag)
+            .and_then(|consumer| consumer.drain_tx.take())
+        {
+            Some(drain_tx) => match drain_tx.send(request) {
+                Ok(_) => return,
+                Err(request) => request,
+            },
+            None => request,
+        };
+        request.responder.send(DrainSummary::default()).ok();
+    }
+
+    /// Warn deliveries approaching their ack deadlines, and settle the expired ones.
+    ///
+    /// The settlements are not locked while callbacks run, so callbacks can settle deliveries.
+    async fn handle_ack_deadlines(&mut self) {
+        let now = time::Instant::now();
+        loop {
+            let (delivery, expired, ack_deadline) = {
+                let mut settlements = self.channel.shared.settlements.lock().await;
+                let (delivery_tag, expired, ack_deadline) = match settlements.pop_due_deadline(now)
+                {
+                    Some(due) => due,
+                    None => break,
+                };
+                match settlements.get(delivery_tag) {
+                    Some(delivery) => (delivery.clone(), expired, ack_deadline),
+                    None => continue,
+                }
+            };
+            let delivery_tag = delivery.delivery_tag;
+            if !expired {
+                #[cfg(feature = "traces")]
+                warn!(
+                    "delivery {} on channel {} is approaching its ack deadline",
+                    delivery_tag, self.channel
+                );
+                self.channel
+                    .publish_event(ChannelEvent::AckDeadlineApproaching(delivery.clone()));
+                if let Some(ref mut cb) = self.callback {
+                    cb.ack_deadline_approaching(&self.channel, delivery).await;
+                }
+                continue;
+            }
+            let result = self
+                .channel
+                .shared
+                .settle(ack_deadline.expiry_settlement(delivery_tag))
+                .await;
+            if let Err(_err) = result {
+                #[cfg(feature = "traces")]
+                error!(
+                    "failed to settle expired delivery {} on channel {}, cause: {}",
+                    delivery_tag, self.channel, _err
+                );
+                continue;
+            }
+            #[cfg(feature = "traces")]
+            warn!(
+                "delivery {} on channel {} has passed its ack deadline, settled as {:?}",
+                delivery_tag, self.channel, ack_deadline.on_expiry
+            );
+            self.channel
+                .publish_event(ChannelEvent::AckDeadlineExpired(delivery.clone()));
+            if let Some(ref mut cb) = self.callback {
+                cb.ack_deadline_expired(&self.channel, delivery).await;
+            }
+        }
     }
 
     async fn forward_deliver(&mut self, consumer_message: ConsumerMessage) {
-        let consumer_tag = consumer_message
-            .deliver
-            .as_ref()
-            .unwrap()
-            .consumer_tag()
-            .clone();
+        let (consumer_tag, delivery_tag) = match consumer_message.deliver.as_ref() {
+            Some(deliver) => (deliver.consumer_tag().clone(), deliver.delivery_tag()),
+            None => return,
+        };
+        // track the delivery unless its consumer is known to be in auto ack mode,
+        // it is forgotten once the consumer is registered in auto ack mode.
+        let (no_ack, ack_deadline) = {
+            let consumer = self.get_or_new_consumer_resource(&consumer_tag);
+            (consumer.no_ack, consumer.ack_deadline)
+        };
+        if no_ack != Some(true) {
+            let mut settlements = self.channel.shared.settlements.lock().await;
+            settlements.deliver(delivery_tag, Some(consumer_tag.clone()));
+            // deadline of buffered delivery is scheduled once the consumer is registered
+            if let Some(ack_deadline) = ack_deadline {
+                settlements.schedule_deadline(delivery_tag, time::Instant::now(), ack_deadline);
+            }
+        }
+        let expiry = self.orphan_handling.expiry;
         let consumer = self.get_or_new_consumer_resource(&consumer_tag);
         match consumer.get_tx() {
             Some(consumer_tx) => {
-                if (consumer_tx.send(consumer_message)).is_err() {
+                if let Err(err) = consumer_tx.send(consumer_message) {
                     #[cfg(feature = "traces")]
                     error!(
-                        "failed to dispatch message to consumer {} on channel {}",
-                        consumer_tag, self.channel
+                        "failed to dispatch message to consumer {}, message is orphaned",
+                        consumer_tag
                     );
+                    // consumer task has exited
+                    consumer.retire(expiry);
+                    consumer.push_message(err.0);
                 }
             }
             None => {
@@ -196,6 +456,8 @@ impl ChannelDispatcher {
         basic_properties: BasicProperties,
         content: Vec<u8>,
     ) {
+        self.channel
+            .publish_event(ChannelEvent::PublishReturned(ret.clone()));
         if let Some(ref mut cb) = self.callback {
             cb.publish_return(&self.channel, ret, basic_properties, content)
                 .await;
@@ -204,8 +466,324 @@ impl ChannelDispatcher {
             error!("callback not registered on channel {}", self.channel);
         }
     }
+    /// Send request to server after its responder is registered.
+    ///
+    /// Returns the error if failed to send.
+    async fn send_request(&mut self, request: Frame) -> Result<(), Error> {
+        self.channel
+            .shared
+            .outgoing_tx
+            .send((self.channel.channel_id(), request))
+            .await?;
+        Ok(())
+    }
+
+    /// Queue a synchronous request, it is sent immediately if no other request is in flight.
+    async fn submit_request(&mut self, request: PendingRequest) {
+        self.pending_requests.push_back(request);
+        if self.is_closing {
+            let channel_id = self.channel.channel_id();
+            self.abort_pending_requests(|| {
+                Error::ChannelUseError(format!("channel {} is closing", channel_id))
+            });
+            return;
+        }
+        if !self.is_request_in_flight {
+            self.send_next_request().await;
+        }
+    }
+
+    /// Abort all requests which are not sent yet.
+    fn abort_pending_requests(&mut self, err: impl Fn() -> Error) {
+        for request in self.pending_requests.drain(..) {
+            match request {
+                PendingRequest::Oneshot(cmd) => cmd.responder.send(Err(err())).ok(),
+                PendingRequest::GetContent(cmd) => cmd.tx.send(Err(err())).ok(),
+            };
+        }
+    }
+
+    /// Abort the in-flight request and all requests which are not sent yet.
+    fn abort_all_requests(&mut self, err: impl Fn() -> Error) {
+        // requests may have been cancelled, no one to notify then
+        for responder in self
+            .responders
+            .drain()
+            .flat_map(|(_, responders)| responders)
+        {
+            responder.send(Err(err())).ok();
+        }
+        for cmd in self.get_content_responders.drain(..) {
+            cmd.tx.send(Err(err())).ok();
+        }
+        self.is_request_in_flight = false;
+        self.abort_pending_requests(err);
+    }
+
+    /// Send `get` requests of the batch until `MAX_PIPELINED_GETS` are outstanding.
+    ///
+    /// No more is sent once the queue is found empty or the batch is cancelled.
+    async fn send_gets(&mut self, cmd: &mut RegisterGetContentResponder) -> Result<(), Error> {
+        while cmd.count > 0
+            && cmd.outstanding < MAX_PIPELINED_GETS
+            && !cmd.empty
+            && !cmd.tx.is_closed()
+        {
+            self.send_request(cmd.request.clone().into_frame()).await?;
+            cmd.count -= 1;
+            cmd.outstanding += 1;
+        }
+        Ok(())
+    }
+
+    /// Account a response to the earliest batch of `get` requests and send more requests of it.
+    ///
+    /// The batch is completed once no request is outstanding.
+    async fn complete_get_response(&mut self, empty: bool) {
+        let mut cmd = match self.get_content_responders.pop_front() {
+            Some(cmd) => cmd,
+            None => return,
+        };
+        cmd.outstanding = cmd.outstanding.saturating_sub(1);
+        cmd.empty |= empty;
+        if let Err(err) = self.send_gets(&mut cmd).await {
+            cmd.tx.send(Err(err)).ok();
+            cmd.count = 0;
+        }
+        if cmd.outstanding > 0 {
+            self.get_content_responders.push_front(cmd);
+        } else {
+            self.complete_request().await;
+        }
+    }
+
+    /// Forward the content of `get-ok` to the request, and account the response.
+    ///
+    /// If the request has been cancelled, the message is requeued if it is in manual ack mode,
+    /// because no one is able to settle it.
+    async fn complete_get(&mut self, content: Vec<u8>, delivery_tag: Option<u64>) {
+        if let Some(cmd) = self.get_content_responders.front() {
+            if cmd
+                .tx
+                .send(Ok(ContentBody::new(content).into_frame()))
+                .is_err()
+            {
+                #[cfg(feature = "traces")]
+                debug!(
+                    "discard get content of cancelled request on channel {}",
+                    self.channel
+                );
+                if let Some(delivery_tag) = delivery_tag {
+                    let settlement = Settlement::Nack {
+                        delivery_tag,
+                        multiple: false,
+                        requeue: true,
+                    };
+                    self.channel.shared.settle(settlement).await.ok();
+                }
+            }
+        }
+        self.complete_get_response(false).await;
+    }
+
+    /// Complete the in-flight request, and send the next pending request if any.
+    async fn complete_request(&mut self) {
+        self.is_request_in_flight = false;
+        self.send_next_request().await;
+    }
+
+    /// Send the earliest pending request which is not cancelled yet.
+    async fn send_next_request(&mut self) {
+        while let Some(request) = self.pending_requests.pop_front() {
+            match request {
+                PendingRequest::Oneshot(cmd) => {
+                    // the request is cancelled before its turn, skip it
+                    if cmd.responder.is_closed() {
+                        continue;
+                    }
+                    // unacked deliveries are redelivered with new delivery tags,
+                    // so send the accumulated acks and forget the old delivery tags
+                    if matches!(cmd.request, Frame::Recover(..)) {
+                        self.channel
+                            .shared
+                            .update_settlements(|settlements| {
+                                let frames = settlements.flush(true);
+                                settlements.forget_all();
+                                Ok((frames, ()))
+                            })
+                            .await
+                            .ok();
+                    }
+                    let consume_no_ack = match cmd.request {
+                        Frame::Consume(_, ref consume) => Some(consume.no_ack()),
+                        _ => None,
+                    };
+                    if let Err(err) = self.send_request(cmd.request).await {
+                        cmd.responder.send(Err(err)).ok();
+                        continue;
+                    }
+                    self.consume_no_ack = consume_no_ack;
+                    self.responders
+                        .entry(cmd.method_header)
+                        .or_default()
+                        .push_back(cmd.responder);
+                }
+                PendingRequest::GetContent(mut cmd) => {
+                    if cmd.tx.is_closed() {
+                        continue;
+                    }
+                    if let Err(err) = self.send_gets(&mut cmd).await {
+                        cmd.tx.send(Err(err)).ok();
+                        if cmd.outstanding == 0 {
+                            continue;
+                        }
+                        cmd.count = 0;
+                    }
+                    self.get_content_responders.push_back(cmd);
+                }
+            }
+            self.is_request_in_flight = true;
+            return;
+        }
+    }
+
+    /// Returns `true` if any request is waiting for the response.
+    fn is_requested(&self, method_header: &'static MethodHeader) -> bool {
+        self.responders
+            .get(method_header)
+            .map_or(false, |responders| !responders.is_empty())
+    }
+
+    /// Forward response to the earliest request waiting for it.
+    ///
+    /// If no request is waiting, server violates the protocol and the channel is closed.
+    async fn forward_response(&mut self, method_header: &'static MethodHeader, frame: Frame) {
+        match self
+            .responders
+            .get_mut(method_header)
+            .and_then(VecDeque::pop_front)
+        {
+            Some(responder) => {
+                // the request has been cancelled, discard its response
+                if responder.send(Ok(frame)).is_err() {
+                    #[cfg(feature = "traces")]
+                    debug!(
+                        "discard response {:?} of cancelled request on channel {}",
+                        method_header, self.channel
+                    );
+                }
+                self.complete_request().await;
+            }
+            None => {
+                self.close_on_protocol_error(
+                    UNEXPECTED_FRAME,
+                    format!("unexpected response {}", frame),
+                )
+                .await
+            }
+        }
+    }
+
+    /// Close the channel because server violates the protocol.
+    ///
+    /// Pending requests are aborted with [`Error::ProtocolError`] and the `close` callback is invoked
+    /// with the reply code and text sent to server. Afterwards, frames are discarded until server
+    /// responds `close-ok`.
+    async fn close_on_protocol_error(&mut self, reply_code: ShortUint, reply_text: String) {
+        #[cfg(feature = "traces")]
+        error!(
+            "close channel {} due to protocol error, '{}: {}'",
+            self.channel, reply_code, reply_text
+        );
+        self.is_closing = true;
+        self.state = State::Initial;
+        self.channel.set_is_open(false);
+        self.channel.notify_closed(CloseReason::ProtocolError {
+            reply_code,
+            reply_text: reply_text.clone(),
+        });
+
+        let reason = format!(
+            "channel {} is closed by client, '{}: {}'",
+            self.channel.channel_id(),
+            reply_code,
+            reply_text
+        );
+        self.abort_all_requests(|| Error::ProtocolError(reason.clone()));
+
+        if let Some(ref mut cb) = self.callback {
+            let close_channel = CloseChannel::new(reply_code, &reply_text);
+            if let Err(_err) = cb.close(&self.channel, close_channel).await {
+                #[cfg(feature = "traces")]
+                error!(
+                    "close callback returns error on channel {}, cause: {}",
+                    self.channel, _err
+                );
+            }
+        }
+        let close_channel = CloseChannel::new(reply_code, &reply_text).into_frame();
+        if let Err(_err) = self
+            .channel
+            .shared
+            .outgoing_tx
+            .send((self.channel.channel_id(), close_channel))
+            .await
+        {
+            #[cfg(feature = "traces")]
+            error!(
+                "failed to send close request of channel {}, cause: {}",
+                self.channel, _err
+            );
+        }
+    }
+
+    /// Handle frame received when client is closing the channel due to protocol error.
+    ///
+    /// Returns `true` if the close handshake is done, and the dispatcher should exit.
+    async fn handle_frame_on_closing(&mut self, frame: Frame) -> bool {
+        match frame {
+            // server may close the channel at the same time, respond to it
+            Frame::CloseChannel(..) => {
+                if let Err(_err) = self
+                    .channel
+                    .shared
+                    .outgoing_tx
+                    .send((self.channel.channel_id(), CloseChannelOk.into_frame()))
+                    .await
+                {
+                    #[cfg(feature = "traces")]
+                    error!(
+                        "failed to respond close request of channel {}, cause: {}",
+                        self.channel, _err
+                    );
+                }
+            }
+            Frame::CloseChannelOk(..) => {}
+            _frame => {
+                #[cfg(feature = "traces")]
+                debug!("discard {} on closing channel {}", _frame, self.channel);
+                return false;
+            }
+        }
+        self.deregister_channel().await;
+        true
+    }
+
+    /// Deregister channel resource from connection handler after close handshake is done,
+    /// so that the channel id can be reused.
+    async fn deregister_channel(&mut self) {
+        let cmd = ConnManagementCommand::DeregisterChannelResource(self.channel.channel_id());
+        if let Err(_err) = self.channel.shared.conn_mgmt_tx.send(cmd).await {
+            #[cfg(feature = "traces")]
+            error!(
+                "failed to deregister channel {}, cause: {}",
+                self.channel, _err
+            );
+        }
+    }
+
     /// Spawn dispatcher task.
-    pub(in crate::api) async fn spawn(mut self) {
+    pub(in crate::api) fn spawn(mut self) {
         tokio::spawn(async move {
             // aggregation buffer for `deliver + content` messages to a consumer
             let mut message_buffer = ConsumerMessage {
@@ -223,6 +801,7 @@ impl ChannelDispatcher {
             };
             // buffer for `getok + content` messages
             let mut getok_content_buffer = GetOkMessage {
+                delivery_tag: None,
                 content: None,
                 remaining: 0,
             };
@@ -234,6 +813,13 @@ impl ChannelDispatcher {
             purge_timer.tick().await;
             // main loop of dispatcher
             loop {
+                let (flush_deadline, ack_deadline) = {
+                    let settlements = self.channel.shared.settlements.lock().await;
+                    (
+                        settlements.flush_deadline(),
+                        settlements.next_deadline_due(),
+                    )
+                };
                 tokio::select! {
                     biased;
 
@@ -243,7 +829,9 @@ impl ChannelDispatcher {
                         // handle command channel error
                         let cmd = match command {
                             None => {
-                                unreachable!("dispatcher command channel closed, {}", self.channel);
+                                #[cfg(feature="traces")]
+                                error!("dispatcher command channel closed, {}", self.channel);
+                                break;
                             },
                             Some(v) => v,
                         };
@@ -253,33 +841,77 @@ impl ChannelDispatcher {
                                 #[cfg(feature="traces")]
                                 info!("register consumer {}", cmd.consumer_tag);
                                 let consumer = self.get_or_new_consumer_resource(&cmd.consumer_tag);
-                                consumer.register_tx(cmd.consumer_tx);
+                                consumer.register_tx(cmd.consumer_tx, cmd.no_ack, cmd.drain_tx, cmd.ack_deadline);
+                                let delivery_tags: Vec<u64> = consumer.fifo.iter()
+                                    .filter_map(|msg| msg.deliver.as_ref().map(|deliver| deliver.delivery_tag()))
+                                    .collect();
+                                let mut settlements = self.channel.shared.settlements.lock().await;
+                                for delivery_tag in delivery_tags {
+                                    // buffered deliveries of consumer in auto ack mode are never settled by client
+                                    if cmd.no_ack {
+                                        settlements.forget(delivery_tag);
+                                    } else if let Some(ack_deadline) = cmd.ack_deadline {
+                                        if let Some(delivery) = settlements.get(delivery_tag) {
+                                            let received_at = time::Instant::from_std(delivery.received_at);
+                                            settlements.schedule_deadline(delivery_tag, received_at, ack_deadline);
+                                        }
+                                    }
+                                }
+                                drop(settlements);
+                                let consumer = self.get_or_new_consumer_resource(&cmd.consumer_tag);
                                 // forward buffered messages
-                                while !consumer.fifo.is_empty() {
+                                while let Some(msg) = consumer.pop_message() {
                                     #[cfg(feature="traces")]
-                                    trace!("consumer {} total buffered messages: {}", cmd.consumer_tag, consumer.fifo.len());
-                                    let msg = consumer.pop_message().unwrap();
-                                    if let Err(_err) = consumer.get_tx().unwrap().send(msg) {
-                                        #[cfg(feature="traces")]
-                                        error!("failed to forward message to consumer {}", cmd.consumer_tag);
+                                    trace!("consumer {} total buffered messages: {}", cmd.consumer_tag, consumer.fifo.len() + 1);
+                                    if let Some(tx) = consumer.get_tx() {
+                                        if tx.send(msg).is_err() {
+                                            #[cfg(feature="traces")]
+                                            error!("failed to forward message to consumer {}", cmd.consumer_tag);
+                                        }
                                     }
                                 }
                             },
+                            DispatcherManagementCommand::SetConsumerAckMode(cmd) => {
+                                self.set_consumer_ack_mode(cmd.consumer_tag, cmd.no_ack);
+                            }
                             DispatcherManagementCommand::DeregisterContentConsumer(cmd) => {
-                                if let Some(consumer) = self.remove_consumer_resource(&cmd.consumer_tag) {
+                                if let Some(consumer) = self.retire_consumer_resource(&cmd.consumer_tag) {
                                     #[cfg(feature="traces")]
                                     info!("deregister consumer {}, total buffered messages: {}",
                                         cmd.consumer_tag, consumer.fifo.len()
                                     );
                                 }
                             },
+                            DispatcherManagementCommand::DrainContentConsumer(cmd) => {
+                                self.pending_drains.insert(cmd.consumer_tag, cmd.request);
+                            }
+                            DispatcherManagementCommand::AbortDrainContentConsumer(cmd) => {
+                                if self.pending_drains.get(&cmd.consumer_tag).map_or(false, |request| request.responder.is_closed()) {
+                                    self.pending_drains.remove(&cmd.consumer_tag);
+                                }
+                            }
                             DispatcherManagementCommand::RegisterGetContentResponder(cmd) => {
-                                self.get_content_responder.replace(cmd.tx);
+                                self.submit_request(PendingRequest::GetContent(cmd)).await;
                             }
                             DispatcherManagementCommand::RegisterOneshotResponder(cmd) => {
-                                self.responders.insert(cmd.method_header, cmd.responder);
-                                cmd.acker.send(()).unwrap();
+                                self.submit_request(PendingRequest::Oneshot(cmd)).await;
                             }
+                            DispatcherManagementCommand::RegisterFallbackConsumer(cmd) => {
+                                self.fallback_tx.replace(cmd.consumer_tx);
+                                #[cfg(feature="traces")]
+                                debug!("fallback consumer registered on channel {}", self.channel);
+                            }
+                            DispatcherManagementCommand::SetOrphanedDeliveryHandling(args) => {
+                                // check expiry at least as frequent as the expiry
+                                let period = CONSUMER_PURGE_INTERVAL.min(args.expiry).max(MIN_CONSUMER_PURGE_INTERVAL);
+                                if period != purge_timer.period() {
+                                    purge_timer = time::interval(period);
+                                    purge_timer.tick().await;
+                                }
+                                self.orphan_handling = args;
+                            }
+                            // the deadline of accumulated acks is read again by the loop
+                            DispatcherManagementCommand::ScheduleAckFlush => {}
                             DispatcherManagementCommand::RegisterChannelCallback(cmd) => {
                                 self.callback.replace(cmd.callback);
                                 #[cfg(feature="traces")]
@@ -300,24 +932,44 @@ impl ChannelDispatcher {
                             },
                             Some(v) => v,
                         };
+                        if self.is_closing {
+                            if self.handle_frame_on_closing(frame).await {
+                                break;
+                            }
+                            continue;
+                        }
+                        // content frames must follow its method frame without interleaving
+                        if !matches!(self.state, State::Initial)
+                            && !matches!(frame, Frame::ContentHeader(_) | Frame::ContentBody(_)) {
+                            self.close_on_protocol_error(UNEXPECTED_FRAME, format!("expect content frame, but received {}", frame)).await;
+                            continue;
+                        }
                         // handle frames
                         match frame {
                             ////////////////////////////////////////////////
                             // frames for closing channel
                             // channel.close-ok response from server
-                            Frame::CloseChannelOk(method_header, close_channel_ok) => {
-                                self.channel.set_is_open(false);
-
-                                match self.responders.remove(method_header) {
-                                    Some(responder) => responder.send(close_channel_ok.into_frame()).unwrap(),
-                                    None => unreachable!("responder must be registered for {} on channel {}",
-                                    close_channel_ok.into_frame(), self.channel),
+                            Frame::CloseChannelOk(method_header, _) => {
+                                if !self.is_requested(method_header) {
+                                    self.close_on_protocol_error(UNEXPECTED_FRAME, "unexpected close-ok".to_owned()).await;
+                                    continue;
                                 }
+                                self.channel.set_is_open(false);
+                                // requests queued after `close` are never sent
+                                let channel_id = self.channel.channel_id();
+                                self.abort_pending_requests(|| Error::ChannelUseError(format!("channel {} is closed", channel_id)));
+                                // deregister before responding, so the channel id is free once `close` returns
+                                self.deregister_channel().await;
+                                self.forward_response(method_header, frame).await;
                                 // exit
                                 break;
                             }
                             // channel.close request from server
                             Frame::CloseChannel(_, close_channel) => {
+                                self.channel.notify_closed(CloseReason::ServerInitiated {
+                                    reply_code: close_channel.reply_code(),
+                                    reply_text: close_channel.reply_text().clone(),
+                                });
                                 // callback
                                 if let Some(ref mut cb) = self.callback {
                                     if let Err(err) = cb.close(&self.channel, close_channel).await {
@@ -333,27 +985,45 @@ impl ChannelDispatcher {
                                 self.channel.set_is_open(false);
 
                                 // implictly respond OK to server
-                                self.channel.shared.outgoing_tx
+                                if let Err(_err) = self.channel.shared.outgoing_tx
                                 .send((self.channel.channel_id(), CloseChannelOk.into_frame()))
-                                .await.unwrap();
+                                .await {
+                                    #[cfg(feature="traces")]
+                                    error!("failed to respond close request of channel {}, cause: {}", self.channel, _err);
+                                }
+                                self.deregister_channel().await;
                                 // exit
                                 break;
                             }
                             ////////////////////////////////////////////////
                             // the method frames followed by content frames
                             Frame::GetEmpty(_, get_empty) => {
-                                self.state = State::GetEmpty;
-
-                                self.get_content_responder.take()
-                                .expect("get responder must be registered")
-                                .send(get_empty.into_frame()).unwrap();
+                                match self.get_content_responders.front() {
+                                    Some(cmd) => {
+                                        if cmd.tx.send(Ok(get_empty.into_frame())).is_err() {
+                                            #[cfg(feature="traces")]
+                                            debug!("discard get-empty of cancelled request on channel {}", self.channel);
+                                        }
+                                        self.complete_get_response(true).await;
+                                    }
+                                    None => self.close_on_protocol_error(UNEXPECTED_FRAME, "unexpected get-empty".to_owned()).await,
+                                }
                             }
                             Frame::GetOk(_, get_ok) => {
-                                self.state = State::GetOk;
-
-                                self.get_content_responder.as_ref()
-                                .expect("get responder must be registered")
-                                .send(get_ok.into_frame()).unwrap();
+                                match self.get_content_responders.front() {
+                                    Some(cmd) => {
+                                        self.state = State::GetOk;
+                                        getok_content_buffer.delivery_tag = (!cmd.no_ack).then(|| get_ok.delivery_tag());
+                                        if !cmd.no_ack {
+                                            self.channel.shared.settlements.lock().await.deliver(get_ok.delivery_tag(), None);
+                                        }
+                                        if cmd.tx.send(Ok(get_ok.into_frame())).is_err() {
+                                            #[cfg(feature="traces")]
+                                            debug!("discard get-ok of cancelled request on channel {}", self.channel);
+                                        }
+                                    }
+                                    None => self.close_on_protocol_error(UNEXPECTED_FRAME, "unexpected get-ok".to_owned()).await,
+                                }
                             }
                             Frame::Return(_, ret) => {
                                 self.state = State::Return;
@@ -364,11 +1034,19 @@ impl ChannelDispatcher {
                                 message_buffer.deliver = Some(deliver);
                             }
                             Frame::ContentHeader(header) => {
+                                let body_size = match usize::try_from(header.common.body_size) {
+                                    Ok(body_size) => body_size,
+                                    Err(_) => {
+                                        self.close_on_protocol_error(FRAME_ERROR, format!("content body size {} is too large", header.common.body_size)).await;
+                                        continue;
+                                    }
+                                };
                                 match self.state {
-                                    State::Deliver => {
-                                        message_buffer.remaining = header.common.body_size.try_into().unwrap();
+                                    State::Deliver if message_buffer.content.is_none() => {
+                                        message_buffer.remaining = body_size;
                                         // do not wait for content body frame if content body size is zero
                                         if message_buffer.remaining == 0 {
+                                            self.state = State::Initial;
                                             let consumer_message  = ConsumerMessage {
                                                 deliver: message_buffer.deliver.take(),
                                                 basic_properties: Some(header.basic_properties),
@@ -381,42 +1059,50 @@ impl ChannelDispatcher {
                                             message_buffer.content = Some(Vec::new());
                                         }
                                     },
-                                    State::GetOk => {
-                                        getok_content_buffer.remaining = header.common.body_size.try_into().unwrap();
+                                    State::GetOk if getok_content_buffer.content.is_none() => {
+                                        getok_content_buffer.remaining = body_size;
 
-                                        let responder = self.get_content_responder.as_ref().expect("get responder must be registered");
-                                        responder.send(header.into_frame()).unwrap();
+                                        if let Some(cmd) = self.get_content_responders.front() {
+                                            // the request may have been cancelled
+                                            cmd.tx.send(Ok(header.into_frame())).ok();
+                                        }
                                         // do not wait for content body frame if content body size is zero
                                         if getok_content_buffer.remaining  == 0 {
-                                            responder.send(ContentBody::new(Vec::new()).into_frame()).unwrap();
+                                            self.state = State::Initial;
+                                            self.complete_get(Vec::new(), getok_content_buffer.delivery_tag.take()).await;
                                         } else {
                                             getok_content_buffer.content = Some(Vec::new());
                                         }
                                     },
-                                    State::Return => {
-                                        return_buffer.remaining = header.common.body_size.try_into().unwrap();
+                                    State::Return if return_buffer.content.is_none() => {
+                                        return_buffer.remaining = body_size;
 
                                         if return_buffer.remaining == 0 {
+                                            self.state = State::Initial;
                                             // do not wait for content body frame if content body size is zero
-                                            self.handle_return(return_buffer.ret.take().unwrap(), header.basic_properties, Vec::new()).await;
+                                            if let Some(ret) = return_buffer.ret.take() {
+                                                self.handle_return(ret, header.basic_properties, Vec::new()).await;
+                                            }
                                         } else {
                                             return_buffer.basic_properties = Some(header.basic_properties);
                                             return_buffer.content = Some(Vec::new());
                                         }
                                     },
-                                    _  => unreachable!("invalid dispatcher state"),
+                                    _  => self.close_on_protocol_error(UNEXPECTED_FRAME, "unexpected content header".to_owned()).await,
                                 }
                             }
                             Frame::ContentBody(body) => {
-                                match self.state {
-                                    State::Deliver => {
-                                        let mut content_buffer = message_buffer.content.take().unwrap();
-                                        content_buffer.extend_from_slice(&body.inner);
-                                        message_buffer.content.replace(content_buffer);
-                                        // calculate remaining size of content body
-                                        message_buffer.remaining = message_buffer.remaining.checked_sub(body.inner.len()).expect("should never overflow");
-
-                                        if message_buffer.remaining == 0 {
+                                let result = match self.state {
+                                    State::Deliver => append_content_body(&mut message_buffer.content, &mut message_buffer.remaining, &body.inner),
+                                    State::GetOk => append_content_body(&mut getok_content_buffer.content, &mut getok_content_buffer.remaining, &body.inner),
+                                    State::Return => append_content_body(&mut return_buffer.content, &mut return_buffer.remaining, &body.inner),
+                                    State::Initial => Err((UNEXPECTED_FRAME, "unexpected content body")),
+                                };
+                                match result {
+                                    // wait for more content body frames
+                                    Ok(false) => {},
+                                    Ok(true) => match std::mem::replace(&mut self.state, State::Initial) {
+                                        State::Deliver => {
                                             let consumer_message  = ConsumerMessage {
                                                 deliver: message_buffer.deliver.take(),
                                                 basic_properties: message_buffer.basic_properties.take(),
@@ -425,33 +1111,21 @@ impl ChannelDispatcher {
                                             };
                                             self.forward_deliver(consumer_message).await;
                                         }
-                                    }
-                                    State::GetOk => {
-                                        let mut content_buffer = getok_content_buffer.content.take().unwrap();
-                                        content_buffer.extend_from_slice(&body.inner);
-                                        getok_content_buffer.content.replace(content_buffer);
-                                        getok_content_buffer.remaining = getok_content_buffer.remaining.checked_sub(body.inner.len()).expect("should never overflow");
-                                        if getok_content_buffer.remaining == 0 {
-                                            let content = getok_content_buffer.content.take().unwrap();
-                                            self.get_content_responder.take()
-                                            .expect("get responder must be registered")
-                                            .send(ContentBody::new(content).into_frame()).unwrap();
-                                        }
+                                        State::GetOk => {
+                                            let content = getok_content_buffer.content.take().unwrap_or_default();
+                                            self.complete_get(content, getok_content_buffer.delivery_tag.take()).await;
+                                        },
+                                        State::Return => {
+                                            if let (Some(ret), Some(basic_properties), Some(content)) = (
+                                                return_buffer.ret.take(),
+                                                return_buffer.basic_properties.take(),
+                                                return_buffer.content.take()) {
+                                                self.handle_return(ret, basic_properties, content).await;
+                                            }
+                                        },
+                                        State::Initial => {},
                                     },
-                                    State::Return => {
-                                        let mut content_buffer = return_buffer.content.take().unwrap();
-                                        content_buffer.extend_from_slice(&body.inner);
-                                        return_buffer.content.replace(content_buffer);
-                                        return_buffer.remaining = return_buffer.remaining.checked_sub(body.inner.len()).expect("should never overflow");
-
-                                        if return_buffer.remaining == 0 {
-                                            self.handle_return(
-                                                return_buffer.ret.take().unwrap(),
-                                                return_buffer.basic_properties.take().unwrap(),
-                                                return_buffer.content.take().unwrap()).await;
-                                        }
-                                    },
-                                    State::Initial | State::GetEmpty  => unreachable!("invalid dispatcher state on channel {}", self.channel),
+                                    Err((reply_code, reply_text)) => self.close_on_protocol_error(reply_code, reply_text.to_owned()).await,
                                 }
                             }
                             ////////////////////////////////////////////////
@@ -468,34 +1142,38 @@ impl ChannelDispatcher {
                             | Frame::DeleteQueueOk(method_header, _)
                             | Frame::UnbindQueueOk(method_header, _)
                             | Frame::QosOk(method_header, _)
-                            | Frame::ConsumeOk(method_header, _)
-                            | Frame::CancelOk(method_header, _)
                             | Frame::RecoverOk(method_header, _)
                             | Frame::SelectOk(method_header, _)
                             | Frame::TxSelectOk(method_header, _)
                             | Frame::TxCommitOk(method_header, _)
                             | Frame::TxRollbackOk(method_header, _) => {
                                 // handle synchronous response
-                                match self.responders.remove(method_header)
-                                {
-                                    Some(responder) => {
-                                        if let Err(response) = responder.send(frame) {
-                                            #[cfg(feature="traces")]
-                                            error!(
-                                                "failed to dispatch {} to channel {}",
-                                                response, self.channel
-                                            );
-                                        }
+                                self.forward_response(method_header, frame).await;
+                            }
+                            Frame::ConsumeOk(method_header, ref consume_ok) => {
+                                // record ack mode here in case the `consume` request is cancelled
+                                // before the consumer is registered
+                                if self.is_requested(method_header) {
+                                    if let Some(no_ack) = self.consume_no_ack.take() {
+                                        self.set_consumer_ack_mode(consume_ok.consumer_tag.clone().into(), no_ack);
                                     }
-                                    None => unreachable!(
-                                        "responder must be registered for {} on channel {}",
-                                        frame, self.channel
-                                    ),
                                 }
+                                self.forward_response(method_header, frame).await;
+                            }
+                            Frame::CancelOk(method_header, ref cancel_ok) => {
+                                // deregister the consumer here in case the `cancel` request is cancelled
+                                let consumer_tag: String = cancel_ok.consumer_tag.clone().into();
+                                self.consumer_ack_modes.remove(&consumer_tag);
+                                // no more delivery of the consumer, so it is safe to start draining
+                                self.start_drain(&consumer_tag);
+                                self.retire_consumer_resource(&consumer_tag);
+                                self.forward_response(method_header, frame).await;
                             }
                             //////////////////////////////////////////////////////////
                             // asynchronous request frames
                             Frame::Flow(_, flow) => {
+                                self.channel.publish_event(ChannelEvent::FlowChanged(flow.active));
+                                self.channel.set_flow_active(flow.active);
                                 // callback
                                 if let Some(ref mut cb) = self.callback {
                                     match cb.flow(&self.channel, flow.active).await {
@@ -505,9 +1183,12 @@ impl ChannelDispatcher {
                                       }
                                       Ok(active) => {
                                          // respond to server that we have handled the request
-                                         self.channel.shared.outgoing_tx
+                                         if let Err(_err) = self.channel.shared.outgoing_tx
                                          .send((self.channel.channel_id(), FlowOk::new(active).into_frame()))
-                                         .await.unwrap();
+                                         .await {
+                                            #[cfg(feature="traces")]
+                                            error!("failed to respond flow request on channel {}, cause: {}", self.channel, _err);
+                                         }
                                       }
                                     };
                                 } else {
@@ -516,9 +1197,13 @@ impl ChannelDispatcher {
                                 }
                             }
                             Frame::Cancel(_, cancel) => {
+                                // no more delivery of the consumer after server cancels it
+                                self.consumer_ack_modes.remove(cancel.consumer_tag());
+                                self.channel.publish_event(ChannelEvent::ConsumerCancelled(cancel.consumer_tag().clone()));
                                 // callback
                                 if let Some(ref mut cb) = self.callback {
                                     let consumer_tag = cancel.consumer_tag().clone();
+                                    let cancel_ok = CancelOk::new(cancel.consumer_tag.clone());
                                     let no_wait = cancel.no_wait();
                                     match cb.cancel(&self.channel, cancel).await {
                                       Err(err) => {
@@ -526,13 +1211,16 @@ impl ChannelDispatcher {
                                         error!("cancel callback error on channel {}, cause: '{}'.", self.channel, err);
                                       }
                                       Ok(_) => {
-                                        self.remove_consumer_resource(&consumer_tag);
+                                        self.retire_consumer_resource(&consumer_tag);
 
                                         // respond to server that we have handled the request
                                         if !no_wait  {
-                                            self.channel.shared.outgoing_tx
-                                            .send((self.channel.channel_id(), CancelOk::new(consumer_tag.try_into().unwrap()).into_frame()))
-                                            .await.unwrap();
+                                            if let Err(_err) = self.channel.shared.outgoing_tx
+                                            .send((self.channel.channel_id(), cancel_ok.into_frame()))
+                                            .await {
+                                                #[cfg(feature="traces")]
+                                                error!("failed to respond cancel request on channel {}, cause: {}", self.channel, _err);
+                                            }
                                         }
                                       }
                                     };
@@ -543,6 +1231,7 @@ impl ChannelDispatcher {
                             }
                             // in confirmed mode
                             Frame::Ack(_, ack) => {
+                                self.channel.publish_event(ChannelEvent::PublishAcked(ack.clone()));
                                 if let Some(ref mut cb) = self.callback {
                                     cb.publish_ack(&self.channel, ack).await;
                                 } else {
@@ -551,18 +1240,27 @@ impl ChannelDispatcher {
                                 }
                             }
                             Frame::Nack(_, nack) => {
+                                self.channel.publish_event(ChannelEvent::PublishNacked(nack.clone()));
                                 if let Some(ref mut cb) = self.callback {
                                     cb.publish_nack(&self.channel, nack).await;
                                 } else {
                                     #[cfg(feature="traces")]
                                     error!("callback not registered on channel {}", self.channel);
                                 }                            }
-                            _ => unreachable!("dispatcher of channel {} receive unexpected frame {}", self.channel, frame),
+                            _ => self.close_on_protocol_error(UNEXPECTED_FRAME, format!("unexpected frame {}", frame)).await,
                         }
                     }
+                    // flush accumulated acks
+                    _ = time::sleep_until(flush_deadline.unwrap_or_else(time::Instant::now)), if flush_deadline.is_some() => {
+                        self.channel.shared.flush_acks().await.ok();
+                    }
+                    // warn or settle deliveries of ack deadlines
+                    _ = time::sleep_until(ack_deadline.unwrap_or_else(time::Instant::now)), if ack_deadline.is_some() => {
+                        self.handle_ack_deadlines().await;
+                    }
                     // purge stale consumer resource
                     _ = purge_timer.tick() => {
-                        self.purge_consumer_resource();
+                        self.purge_consumer_resource().await;
                     }
                     else => {
                         break;
@@ -570,6 +1268,29 @@ impl ChannelDispatcher {
                 }
             }
             self.channel.set_is_open(false);
+            // requests are never responded if connection is closed due to protocol error
+            if let Some(CloseReason::ProtocolError {
+                reply_code,
+                reply_text,
+            }) = self.channel.connection_close_reason()
+            {
+                let reason = format!(
+                    "connection of channel {} is closed by client, '{}: {}'",
+                    self.channel.channel_id(),
+                    reply_code,
+                    reply_text
+                );
+                self.abort_all_requests(|| Error::ProtocolError(reason.clone()));
+            }
+            // the channel is closed along with its connection if no other reason reported
+            self.channel.notify_closed(
+                self.channel
+                    .connection_close_reason()
+                    .unwrap_or(CloseReason::ClientInitiated),
+            );
+            if let Some(reason) = self.channel.close_reason() {
+                self.channel.publish_event(ChannelEvent::Closed(reason));
+            }
 
             #[cfg(feature = "traces")]
             info!("exit dispatcher of channel {}", self.channel);
@@ -579,17 +1300,20 @@ impl ChannelDispatcher {
 
 #[cfg(test)]
 mod tests {
-    use tokio::time;
+    use super::append_content_body;
+
+    use async_trait::async_trait;
+    use tokio::{sync::mpsc, time};
 
     use crate::{
         channel::{
-            BasicCancelArguments, BasicConsumeArguments, BasicPublishArguments, QueueBindArguments,
-            QueueDeclareArguments,
+            BasicAckArguments, BasicCancelArguments, BasicConsumeArguments, BasicPublishArguments,
+            Channel, OrphanedDeliveryArguments, QueueBindArguments, QueueDeclareArguments,
         },
         connection::{Connection, OpenConnectionArguments},
-        consumer::DefaultConsumer,
+        consumer::{AsyncConsumer, DefaultConsumer},
         test_utils::setup_logging,
-        BasicProperties,
+        BasicProperties, Deliver,
     };
 
     use super::{CONSUMER_EXPIRY_PERIOD, CONSUMER_PURGE_INTERVAL};
@@ -660,4 +1384,96 @@ mod tests {
         // the consumer resource should be purged within `CONSUMER_PURGE_INTERVAL + CONSUMER_EXPIRY_PERIOD`
         time::sleep(CONSUMER_PURGE_INTERVAL + CONSUMER_EXPIRY_PERIOD).await;
     }
+
+    #[tokio::test]
+    async fn test_orphaned_delivery_fallback() {
+        setup_logging();
+
+        struct ForwardConsumer(mpsc::UnboundedSender<String>);
+        #[async_trait]
+        impl AsyncConsumer for ForwardConsumer {
+            async fn consume(
+                &mut self,
+                channel: &Channel,
+                deliver: Deliver,
+                _basic_properties: BasicProperties,
+                _content: Vec<u8>,
+            ) {
+                channel
+                    .basic_ack(BasicAckArguments::new(deliver.delivery_tag(), false))
+                    .await
+                    .unwrap();
+                self.0.send(deliver.consumer_tag().clone()).unwrap();
+            }
+        }
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        channel
+            .set_orphaned_delivery_handling(
+                OrphanedDeliveryArguments::new()
+                    .expiry(time::Duration::from_millis(100))
+                    .finish(),
+            )
+            .await
+            .unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        channel
+            .set_fallback_consumer(ForwardConsumer(tx))
+            .await
+            .unwrap();
+
+        let (queue_name, _, _) = channel
+            .queue_declare(QueueDeclareArguments::default())
+            .await
+            .unwrap()
+            .unwrap();
+        let (consumer_tag, consumer_rx) = channel
+            .basic_consume_rx(BasicConsumeArguments::new(&queue_name, "orphan-tester"))
+            .await
+            .unwrap();
+        // deliveries are orphaned once consumer is gone
+        drop(consumer_rx);
+
+        channel
+            .basic_publish(
+                BasicProperties::default(),
+                String::from("orphaned message").into_bytes(),
+                BasicPublishArguments::new("", &queue_name),
+            )
+            .await
+            .unwrap();
+
+        let received = time::timeout(time::Duration::from_secs(2), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(consumer_tag, received);
+
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[test]
+    fn test_append_content_body() {
+        let mut content = None;
+        let mut remaining = 4;
+        assert!(append_content_body(&mut content, &mut remaining, b"ab").is_err());
+
+        content = Some(Vec::new());
+        assert_eq!(
+            Ok(false),
+            append_content_body(&mut content, &mut remaining, b"ab")
+        );
+        assert_eq!(
+            Ok(true),
+            append_content_body(&mut content, &mut remaining, b"cd")
+        );
+        assert_eq!(Some(b"abcd".to_vec()), content);
+
+        let mut remaining = 1;
+        let (reply_code, _) = append_content_body(&mut content, &mut remaining, b"ef").unwrap_err();
+        assert_eq!(crate::frame::FRAME_ERROR, reply_code);
+    }
 }
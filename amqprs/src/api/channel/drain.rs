@@ -0,0 +1,148 @@
+use std::time::Instant;
+
+use tokio::sync::oneshot;
+
+////////////////////////////////////////////////////////////////////////////////
+/// Summary of [`basic_cancel_graceful`].
+///
+/// [`basic_cancel_graceful`]: struct.Channel.html#method.basic_cancel_graceful
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrainSummary {
+    /// Number of deliveries processed by the consumer after server confirmed the cancellation.
+    pub processed: usize,
+    /// Number of deliveries requeued because their processing did not start before the deadline.
+    pub requeued: usize,
+}
+
+/// Request to drain a consumer task.
+///
+/// It is sent to the consumer task by dispatcher once server confirms `cancel-ok`,
+/// so no more delivery arrives for the consumer afterwards.
+pub(crate) struct DrainRequest {
+    pub deadline: Instant,
+    pub responder: oneshot::Sender<DrainSummary>,
+}
+
+/// Draining state of a consumer task.
+///
+/// The consumer task asks [`Drain::should_requeue`] before processing each delivery, and reports
+/// each processed delivery by [`Drain::processed`]. Once the consumer task exits, it calls
+/// [`Drain::finish`] to respond the summary if draining is requested.
+pub(crate) struct Drain {
+    rx: oneshot::Receiver<DrainRequest>,
+    request: Option<DrainRequest>,
+    /// deliveries of consumer in auto ack mode can not be requeued, so all of them are processed.
+    no_ack: bool,
+    summary: DrainSummary,
+}
+
+impl Drain {
+    /// Returns the tx half to request draining, and the draining state to be held by consumer task.
+    pub fn new(no_ack: bool) -> (oneshot::Sender<DrainRequest>, Self) {
+        let (tx, rx) = oneshot::channel();
+        let drain = Self {
+            rx,
+            request: None,
+            no_ack,
+            summary: DrainSummary::default(),
+        };
+        (tx, drain)
+    }
+
+    /// Returns `true` if draining is requested.
+    fn is_draining(&mut self) -> bool {
+        if self.request.is_none() {
+            self.request = self.rx.try_recv().ok();
+        }
+        self.request.is_some()
+    }
+
+    /// Returns `true` if the delivery should be requeued instead of processed,
+    /// because the deadline of draining has passed.
+    pub fn should_requeue(&mut self) -> bool {
+        if self.no_ack || !self.is_draining() {
+            return false;
+        }
+        match self.request {
+            Some(ref request) if Instant::now() >= request.deadline => {
+                self.summary.requeued += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Report a processed delivery.
+    pub fn processed(&mut self) {
+        if self.is_draining() {
+            self.summary.processed += 1;
+        }
+    }
+
+    /// Respond the summary if draining is requested.
+    pub fn finish(mut self) {
+        if self.is_draining() {
+            if let Some(request) = self.request.take() {
+                request.responder.send(self.summary).ok();
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio::sync::oneshot;
+
+    use super::{Drain, DrainRequest, DrainSummary};
+
+    #[tokio::test]
+    async fn test_drain() {
+        let (drain_tx, mut drain) = Drain::new(false);
+        // processed before draining is requested
+        assert!(!drain.should_requeue());
+        drain.processed();
+
+        let (responder, responder_rx) = oneshot::channel();
+        drain_tx
+            .send(DrainRequest {
+                deadline: Instant::now() + Duration::from_millis(50),
+                responder,
+            })
+            .ok();
+        assert!(!drain.should_requeue());
+        drain.processed();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(drain.should_requeue());
+        assert!(drain.should_requeue());
+        drain.finish();
+        assert_eq!(
+            DrainSummary {
+                processed: 1,
+                requeued: 2
+            },
+            responder_rx.await.unwrap()
+        );
+
+        // deliveries in auto ack mode are never requeued
+        let (drain_tx, mut drain) = Drain::new(true);
+        let (responder, responder_rx) = oneshot::channel();
+        drain_tx
+            .send(DrainRequest {
+                deadline: Instant::now(),
+                responder,
+            })
+            .ok();
+        assert!(!drain.should_requeue());
+        drain.processed();
+        drain.finish();
+        assert_eq!(1, responder_rx.await.unwrap().processed);
+
+        // no summary if draining is not requested
+        let (drain_tx, drain) = Drain::new(false);
+        drop(drain_tx);
+        drain.finish();
+    }
+}
@@ -1,18 +1,29 @@
 use crate::{
-    api::{error::Error, FieldTable},
+    api::{
+        error::Error,
+        validation::{
+            check_exchange_name, check_flags, check_not_empty, check_short_str, to_short_str,
+        },
+        FieldTable,
+    },
     frame::{Bind, BindOk, Declare, DeclareOk, Delete, DeleteOk, Frame, Unbind, UnbindOk},
 };
 use std::borrow::ToOwned;
 use std::fmt::{Debug, Display, Formatter};
 
-use super::{Channel, Result};
+use super::{
+    arguments::check_binding_arguments, Channel, ExchangeArguments, HeadersBindingArguments, Result,
+};
 
-#[cfg(feature = "compliance_assert")]
-use crate::api::compliance_asserts::assert_exchange_name;
+#[cfg(feature = "traces")]
+use tracing::warn;
 
 /// Exchange types. Most variants are for exchange types included with modern RabbitMQ distributions.
 /// For custom types provided by 3rd party plugins, use the `Plugin(String)` variant.
-#[derive(Debug, PartialEq, Eq)]
+///
+/// More exchange types may be added in future, so matching on it requires a wildcard arm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ExchangeType {
     /// Fanout exchange
     Fanout,
@@ -32,6 +43,11 @@ pub enum ExchangeType {
     JmsTopic,
     /// Recent history exchange
     RecentHistory,
+    /// Delayed message exchange, ships with the 'rabbitmq-delayed-message-exchange' plugin.
+    /// It requires `x-delayed-type` argument, see [`ExchangeArguments`].
+    ///
+    /// [`ExchangeArguments`]: struct.ExchangeArguments.html
+    DelayedMessage,
     /// All other x-* exchange types, for example, those provided by plugins
     Plugin(String),
 }
@@ -45,6 +61,7 @@ const EXCHANGE_TYPE_MODULUS_HASH: &str = "x-modulus-hash";
 const EXCHANGE_TYPE_RANDOM: &str = "x-random";
 const EXCHANGE_TYPE_JMS_TOPIC: &str = "x-jms-topic";
 const EXCHANGE_TYPE_RECENT_HISTORY: &str = "x-recent-history";
+const EXCHANGE_TYPE_DELAYED_MESSAGE: &str = "x-delayed-message";
 
 impl From<&str> for ExchangeType {
     fn from(value: &str) -> Self {
@@ -58,6 +75,7 @@ impl From<&str> for ExchangeType {
             EXCHANGE_TYPE_RANDOM => ExchangeType::Random,
             EXCHANGE_TYPE_JMS_TOPIC => ExchangeType::JmsTopic,
             EXCHANGE_TYPE_RECENT_HISTORY => ExchangeType::RecentHistory,
+            EXCHANGE_TYPE_DELAYED_MESSAGE => ExchangeType::DelayedMessage,
             other => ExchangeType::Plugin(other.to_owned()),
         }
     }
@@ -81,6 +99,7 @@ impl From<ExchangeType> for String {
             ExchangeType::Random => EXCHANGE_TYPE_RANDOM.to_owned(),
             ExchangeType::JmsTopic => EXCHANGE_TYPE_JMS_TOPIC.to_owned(),
             ExchangeType::RecentHistory => EXCHANGE_TYPE_RECENT_HISTORY.to_owned(),
+            ExchangeType::DelayedMessage => EXCHANGE_TYPE_DELAYED_MESSAGE.to_owned(),
             ExchangeType::Plugin(exchange_type) => exchange_type,
         }
     }
@@ -98,6 +117,7 @@ impl Display for ExchangeType {
             ExchangeType::Random => Display::fmt(&EXCHANGE_TYPE_RANDOM, f),
             ExchangeType::JmsTopic => Display::fmt(&EXCHANGE_TYPE_JMS_TOPIC, f),
             ExchangeType::RecentHistory => Display::fmt(&EXCHANGE_TYPE_RECENT_HISTORY, f),
+            ExchangeType::DelayedMessage => Display::fmt(&EXCHANGE_TYPE_DELAYED_MESSAGE, f),
             ExchangeType::Plugin(exchange_type) => Display::fmt(&exchange_type, f),
         }
     }
@@ -134,6 +154,11 @@ pub struct ExchangeDeclareArguments {
     pub no_wait: bool,
     /// Default: empty table.
     pub arguments: FieldTable,
+    /// Default: `false`. If set and the exchange exists with different flags or arguments,
+    /// it is passively declared instead with a warning, see [`exchange_declare`].
+    ///
+    /// [`exchange_declare`]: struct.Channel.html#method.exchange_declare
+    pub passive_fallback: bool,
 }
 
 impl Default for ExchangeDeclareArguments {
@@ -147,6 +172,7 @@ impl Default for ExchangeDeclareArguments {
             internal: Default::default(),
             no_wait: Default::default(),
             arguments: Default::default(),
+            passive_fallback: Default::default(),
         }
     }
 }
@@ -154,9 +180,6 @@ impl Default for ExchangeDeclareArguments {
 impl ExchangeDeclareArguments {
     /// Creates new arguments with defaults
     pub fn new(exchange: &str, exchange_type: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_exchange_name(exchange);
-
         Self {
             exchange: exchange.to_string(),
             exchange_type: exchange_type.to_string(),
@@ -166,6 +189,7 @@ impl ExchangeDeclareArguments {
             internal: false,
             no_wait: false,
             arguments: FieldTable::new(),
+            passive_fallback: false,
         }
     }
 
@@ -207,13 +231,59 @@ impl ExchangeDeclareArguments {
         /// Chainable setter method.
         arguments, FieldTable
     }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        passive_fallback, bool
+    }
+    /// Chainable setter method of typed arguments, which replaces `arguments`.
+    ///
+    /// See [`ExchangeArguments`] for details.
+    ///
+    /// [`ExchangeArguments`]: struct.ExchangeArguments.html
+    pub fn exchange_arguments(&mut self, exchange_arguments: ExchangeArguments) -> &mut Self {
+        self.arguments = exchange_arguments.into();
+        self
+    }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_exchange_name(&self.exchange);
-
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`exchange_declare`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes,
+    /// or if not passive, the exchange is the default exchange or its type is empty,
+    /// or the arguments are invalid as [`ExchangeArguments`] or not supported by the type.
+    /// Also returns it if `passive_fallback` is set with `no_wait`, because inequivalent arguments
+    /// are only detected by the reply.
+    ///
+    /// [`exchange_declare`]: struct.Channel.html#method.exchange_declare
+    /// [`ExchangeArguments`]: struct.ExchangeArguments.html
+    pub fn validate(&self) -> Result<()> {
+        check_exchange_name("exchange", &self.exchange)?;
+        check_short_str("exchange_type", &self.exchange_type)?;
+        check_flags(
+            !(self.passive_fallback && self.no_wait),
+            "passive_fallback: requires waiting for the reply",
+        )?;
+        if !self.passive {
+            check_not_empty(
+                "exchange",
+                &self.exchange,
+                "can not declare the default exchange",
+            )?;
+            check_flags(
+                !self.exchange_type.is_empty(),
+                "exchange_type: must not be empty",
+            )?;
+            let exchange_arguments = ExchangeArguments::try_from(&self.arguments)?;
+            exchange_arguments.validate()?;
+            exchange_arguments.check_exchange_type(&self.exchange_type.as_str().into())?;
+        }
+        Ok(())
+    }
 }
 
 /// Arguments for [`exchange_delete`]
@@ -234,9 +304,6 @@ pub struct ExchangeDeleteArguments {
 impl ExchangeDeleteArguments {
     /// Create new arguments with defaults
     pub fn new(exchange: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_exchange_name(exchange);
-
         Self {
             exchange: exchange.to_owned(),
             if_unused: false,
@@ -257,11 +324,25 @@ impl ExchangeDeleteArguments {
     }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_exchange_name(&self.exchange);
-
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`exchange_delete`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if exchange name is longer than 255 bytes,
+    /// or is the default exchange.
+    ///
+    /// [`exchange_delete`]: struct.Channel.html#method.exchange_delete
+    pub fn validate(&self) -> Result<()> {
+        check_exchange_name("exchange", &self.exchange)?;
+        check_not_empty(
+            "exchange",
+            &self.exchange,
+            "can not delete the default exchange",
+        )
+    }
 }
 
 /// Arguments for [`exchange_bind`]
@@ -286,11 +367,6 @@ pub struct ExchangeBindArguments {
 impl ExchangeBindArguments {
     /// Create arguments with defaults
     pub fn new(destination: &str, source: &str, routing_key: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        {
-            assert_exchange_name(destination);
-            assert_exchange_name(source);
-        }
         Self {
             destination: destination.to_owned(),
             source: source.to_owned(),
@@ -319,15 +395,34 @@ impl ExchangeBindArguments {
         /// Chainable setter method.
         arguments, FieldTable
     }
+    /// Chainable setter method of typed arguments of headers exchange, which replaces `arguments`.
+    ///
+    /// See [`HeadersBindingArguments`] for details.
+    ///
+    /// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+    pub fn headers_arguments(&mut self, headers_arguments: HeadersBindingArguments) -> &mut Self {
+        self.arguments = headers_arguments.into();
+        self
+    }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        {
-            assert_exchange_name(&self.destination);
-            assert_exchange_name(&self.source);
-        }
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`exchange_bind`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes,
+    /// or source or destination is the default exchange,
+    /// or `x-match` is present but the arguments are invalid as [`HeadersBindingArguments`].
+    ///
+    /// [`exchange_bind`]: struct.Channel.html#method.exchange_bind
+    /// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+    pub fn validate(&self) -> Result<()> {
+        check_binding(&self.destination, &self.source, &self.routing_key)?;
+        check_binding_arguments(&self.arguments)
+    }
 }
 
 /// Arguments for [`exchange_unbind`]
@@ -352,11 +447,6 @@ pub struct ExchangeUnbindArguments {
 impl ExchangeUnbindArguments {
     /// Create arguments with defaults
     pub fn new(destination: &str, source: &str, routing_key: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        {
-            assert_exchange_name(destination);
-            assert_exchange_name(source);
-        }
         Self {
             destination: destination.to_string(),
             source: source.to_string(),
@@ -385,29 +475,99 @@ impl ExchangeUnbindArguments {
         /// Chainable setter method.
         arguments, FieldTable
     }
+    /// Chainable setter method of typed arguments of headers exchange, which replaces `arguments`.
+    ///
+    /// See [`HeadersBindingArguments`] for details.
+    ///
+    /// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+    pub fn headers_arguments(&mut self, headers_arguments: HeadersBindingArguments) -> &mut Self {
+        self.arguments = headers_arguments.into();
+        self
+    }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        {
-            assert_exchange_name(&self.destination);
-            assert_exchange_name(&self.source);
-        }
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`exchange_unbind`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes,
+    /// or source or destination is the default exchange,
+    /// or `x-match` is present but the arguments are invalid as [`HeadersBindingArguments`].
+    ///
+    /// [`exchange_unbind`]: struct.Channel.html#method.exchange_unbind
+    /// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+    pub fn validate(&self) -> Result<()> {
+        check_binding(&self.destination, &self.source, &self.routing_key)?;
+        check_binding_arguments(&self.arguments)
+    }
+}
+
+/// Common checks of exchange-to-exchange binding.
+fn check_binding(destination: &str, source: &str, routing_key: &str) -> Result<()> {
+    check_exchange_name("destination", destination)?;
+    check_exchange_name("source", source)?;
+    check_not_empty(
+        "destination",
+        destination,
+        "the default exchange can not be bound",
+    )?;
+    check_not_empty("source", source, "the default exchange can not be bound")?;
+    check_short_str("routing_key", routing_key)
 }
 /////////////////////////////////////////////////////////////////////////////
 /// APIs for AMQP exchange class
 impl Channel {
     /// See [AMQP_0-9-1 Reference](https://www.rabbitmq.com/amqp-0-9-1-reference.html#exchange.declaure)
     ///
+    /// If the exchange exists with different flags or arguments, server closes the channel.
+    /// With `passive_fallback` of the arguments, the exchange is first declared on a short-lived
+    /// channel, and if it exists with different settings, it is passively declared on this channel
+    /// instead with a warning, so that this channel is kept open.
+    ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::InequivalentArguments`] if the exchange exists with different flags or arguments,
+    /// unless `passive_fallback` is set.
     /// Returns error if any failure in comunication with server.
+    ///
+    /// [`Error::InequivalentArguments`]: ../error/enum.Error.html#variant.InequivalentArguments
     pub async fn exchange_declare(&self, args: ExchangeDeclareArguments) -> Result<()> {
+        args.validate()?;
+        if !args.passive_fallback || args.passive {
+            return self.request_exchange_declare(args).await;
+        }
+        let exchange = args.exchange.clone();
+        let exchange_type = args.exchange_type.clone();
+        let declared = self
+            .connection
+            .on_short_lived_channel(|channel| async move {
+                channel.request_exchange_declare(args).await
+            })
+            .await;
+        match declared {
+            Err(Error::InequivalentArguments(_details)) => {
+                #[cfg(feature = "traces")]
+                warn!("{}, declare it passively on channel {}", _details, self);
+                self.request_exchange_declare(
+                    ExchangeDeclareArguments::new(&exchange, &exchange_type)
+                        .passive(true)
+                        .finish(),
+                )
+                .await
+            }
+            declared => declared,
+        }
+    }
+
+    async fn request_exchange_declare(&self, args: ExchangeDeclareArguments) -> Result<()> {
         let mut declare = Declare::new(
             0,
-            args.exchange.try_into().unwrap(),
-            args.exchange_type.try_into().unwrap(),
+            to_short_str("exchange", args.exchange)?,
+            to_short_str("exchange_type", args.exchange_type)?,
             args.arguments,
         );
 
@@ -424,15 +584,13 @@ impl Channel {
                 .await?;
             Ok(())
         } else {
-            let responder_rx = self.register_responder(DeclareOk::header()).await?;
-
-            let _method = synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.shared.channel_id, declare.into_frame()),
-                responder_rx,
-                Frame::DeclareOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(DeclareOk::header(), declare.into_frame())?;
+            let _method = async {
+                // server closes the channel if the arguments are inequivalent
+                synchronous_request!(responder_rx, Frame::DeclareOk, Error::ChannelUseError)
+            }
+            .await
+            .map_err(|err| self.check_inequivalent(err))?;
             Ok(())
         }
     }
@@ -440,9 +598,11 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns error if any failure in comunication with server.
     pub async fn exchange_delete(&self, args: ExchangeDeleteArguments) -> Result<()> {
-        let mut delete = Delete::new(0, args.exchange.try_into().unwrap());
+        args.validate()?;
+        let mut delete = Delete::new(0, to_short_str("exchange", args.exchange)?);
         delete.set_if_unused(args.if_unused);
         delete.set_no_wait(args.no_wait);
         if args.no_wait {
@@ -452,15 +612,9 @@ impl Channel {
                 .await?;
             Ok(())
         } else {
-            let responder_rx = self.register_responder(DeleteOk::header()).await?;
-
-            let _method = synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.shared.channel_id, delete.into_frame()),
-                responder_rx,
-                Frame::DeleteOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(DeleteOk::header(), delete.into_frame())?;
+            let _method =
+                synchronous_request!(responder_rx, Frame::DeleteOk, Error::ChannelUseError)?;
             Ok(())
         }
     }
@@ -468,13 +622,15 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns error if any failure in comunication with server.
     pub async fn exchange_bind(&self, args: ExchangeBindArguments) -> Result<()> {
+        args.validate()?;
         let bind = Bind::new(
             0,
-            args.destination.try_into().unwrap(),
-            args.source.try_into().unwrap(),
-            args.routing_key.try_into().unwrap(),
+            to_short_str("destination", args.destination)?,
+            to_short_str("source", args.source)?,
+            to_short_str("routing_key", args.routing_key)?,
             args.no_wait,
             args.arguments,
         );
@@ -485,15 +641,8 @@ impl Channel {
                 .await?;
             Ok(())
         } else {
-            let responder_rx = self.register_responder(BindOk::header()).await?;
-
-            synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.shared.channel_id, bind.into_frame()),
-                responder_rx,
-                Frame::BindOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(BindOk::header(), bind.into_frame())?;
+            synchronous_request!(responder_rx, Frame::BindOk, Error::ChannelUseError)?;
             Ok(())
         }
     }
@@ -501,13 +650,15 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns error if any failure in comunication with server.
     pub async fn exchange_unbind(&self, args: ExchangeUnbindArguments) -> Result<()> {
+        args.validate()?;
         let unbind = Unbind::new(
             0,
-            args.destination.try_into().unwrap(),
-            args.source.try_into().unwrap(),
-            args.routing_key.try_into().unwrap(),
+            to_short_str("destination", args.destination)?,
+            to_short_str("source", args.source)?,
+            to_short_str("routing_key", args.routing_key)?,
             args.no_wait,
             args.arguments,
         );
@@ -518,15 +669,8 @@ impl Channel {
                 .await?;
             Ok(())
         } else {
-            let responder_rx = self.register_responder(UnbindOk::header()).await?;
-
-            synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.shared.channel_id, unbind.into_frame()),
-                responder_rx,
-                Frame::UnbindOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(UnbindOk::header(), unbind.into_frame())?;
+            synchronous_request!(responder_rx, Frame::UnbindOk, Error::ChannelUseError)?;
             Ok(())
         }
     }
@@ -538,8 +682,12 @@ mod tests {
         ExchangeBindArguments, ExchangeDeclareArguments, ExchangeDeleteArguments, ExchangeType,
         ExchangeUnbindArguments,
     };
+    use crate::api::channel::{ExchangeArguments, HeadersBindingArguments, HeadersMatch};
     use crate::{
-        api::connection::{Connection, OpenConnectionArguments},
+        api::{
+            connection::{Connection, OpenConnectionArguments},
+            error::Error,
+        },
         callbacks::{DefaultChannelCallback, DefaultConnectionCallback},
         test_utils,
     };
@@ -558,6 +706,10 @@ mod tests {
         assert_eq!(ExchangeType::JmsTopic.to_string(), "x-jms-topic");
         assert_eq!(ExchangeType::RecentHistory.to_string(), "x-recent-history");
         assert_eq!(ExchangeType::ModulusHash.to_string(), "x-modulus-hash");
+        assert_eq!(
+            ExchangeType::DelayedMessage.to_string(),
+            "x-delayed-message"
+        );
         assert_eq!(
             ExchangeType::Plugin(String::from("x-custom-exchange-2")).to_string(),
             "x-custom-exchange-2"
@@ -577,6 +729,10 @@ mod tests {
             ExchangeType::from("x-modulus-hash"),
             ExchangeType::ModulusHash
         );
+        assert_eq!(
+            ExchangeType::from("x-delayed-message"),
+            ExchangeType::DelayedMessage
+        );
         assert_eq!(
             ExchangeType::from("x-custom-exchange-2"),
             ExchangeType::Plugin(String::from("x-custom-exchange-2"))
@@ -698,4 +854,141 @@ mod tests {
             .await
             .unwrap();
     }
+
+    #[test]
+    fn test_validate_arguments() {
+        assert!(ExchangeDeclareArguments::new("amq.topic", "topic")
+            .validate()
+            .is_ok());
+        assert!(ExchangeDeclareArguments::new("", "topic")
+            .validate()
+            .is_err());
+        assert!(ExchangeDeclareArguments::new("", "")
+            .passive(true)
+            .finish()
+            .validate()
+            .is_ok());
+        assert!(ExchangeDeclareArguments::new("amqprs.x", "")
+            .validate()
+            .is_err());
+
+        assert!(ExchangeDeleteArguments::new("").validate().is_err());
+        assert!(ExchangeBindArguments::new("amqprs.x", "", "k")
+            .validate()
+            .is_err());
+        let routing_key = "k".repeat(256);
+        assert!(
+            ExchangeUnbindArguments::new("amqprs.x", "amq.topic", &routing_key)
+                .validate()
+                .is_err()
+        );
+
+        // delayed message exchange requires x-delayed-type
+        assert!(
+            ExchangeDeclareArguments::of_type("amqprs.x", ExchangeType::DelayedMessage)
+                .validate()
+                .is_err()
+        );
+        let delayed = ExchangeArguments::new()
+            .delayed_type(ExchangeType::Direct)
+            .finish();
+        assert!(
+            ExchangeDeclareArguments::of_type("amqprs.x", ExchangeType::DelayedMessage)
+                .exchange_arguments(delayed.clone())
+                .finish()
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            ExchangeDeclareArguments::of_type("amqprs.x", ExchangeType::Direct)
+                .exchange_arguments(delayed)
+                .finish()
+                .validate()
+                .is_err()
+        );
+        // hash header only for consistent hash exchange
+        let hash_header = ExchangeArguments::new()
+            .hash_header("user".to_owned())
+            .finish();
+        assert!(
+            ExchangeDeclareArguments::of_type("amqprs.x", ExchangeType::ConsistentHashing)
+                .exchange_arguments(hash_header.clone())
+                .finish()
+                .validate()
+                .is_ok()
+        );
+        assert!(
+            ExchangeDeclareArguments::of_type("amqprs.x", ExchangeType::Topic)
+                .exchange_arguments(hash_header)
+                .finish()
+                .validate()
+                .is_err()
+        );
+
+        // x- headers are ignored unless matched with x
+        let mut headers = HeadersBindingArguments::new(HeadersMatch::All)
+            .header("x-tenant", "a")
+            .finish();
+        assert!(ExchangeBindArguments::new("amqprs.x", "amq.headers", "")
+            .headers_arguments(headers.clone())
+            .finish()
+            .validate()
+            .is_err());
+        assert!(ExchangeBindArguments::new("amqprs.x", "amq.headers", "")
+            .headers_arguments(headers.x_match(HeadersMatch::AllWithX).finish())
+            .finish()
+            .validate()
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_exchange_declare_inequivalent_arguments() {
+        test_utils::setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let exchange = "amqprs.test_exchange_declare_inequivalent_arguments";
+
+        let channel = connection.open_channel(None).await.unwrap();
+        channel
+            .exchange_declare(ExchangeDeclareArguments::of_type(
+                exchange,
+                ExchangeType::Direct,
+            ))
+            .await
+            .unwrap();
+        match channel
+            .exchange_declare(ExchangeDeclareArguments::of_type(
+                exchange,
+                ExchangeType::Topic,
+            ))
+            .await
+        {
+            Err(Error::InequivalentArguments(details)) => {
+                assert_eq!("exchange", details.entity_type);
+                assert_eq!("type", details.argument);
+                assert_eq!(Some("direct".to_owned()), details.existing);
+                assert_eq!(Some("topic".to_owned()), details.requested);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+
+        let channel = connection.open_channel(None).await.unwrap();
+        channel
+            .exchange_declare(
+                ExchangeDeclareArguments::of_type(exchange, ExchangeType::Topic)
+                    .passive_fallback(true)
+                    .finish(),
+            )
+            .await
+            .unwrap();
+        assert!(channel.is_open());
+
+        channel
+            .exchange_delete(ExchangeDeleteArguments::new(exchange))
+            .await
+            .unwrap();
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
 }
@@ -0,0 +1,640 @@
+use std::{
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+
+use crate::{
+    api::{
+        error::Error,
+        validation::{check_flags, check_queue_name, to_short_str},
+        Result,
+    },
+    frame::{Frame, Get},
+    net::IncomingResponse,
+};
+
+use super::{
+    BasicGetArguments, Channel, DispatcherManagementCommand, GetMessage,
+    RegisterGetContentResponder,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+/// Messages returned by [`basic_get_batch`].
+///
+/// [`basic_get_batch`]: struct.Channel.html#method.basic_get_batch
+#[derive(Debug, Default)]
+pub struct GetBatch {
+    /// Messages in the order of delivery, at most the requested number.
+    pub messages: Vec<GetMessage>,
+    /// Number of messages left in the queue, as reported by the last `get-ok`.
+    /// It is `0` if the queue was found empty.
+    pub message_count: u32,
+}
+
+impl GetBatch {
+    /// Returns `true` if no message was got.
+    pub fn is_empty(&self) -> bool {
+        self.messages.is_empty()
+    }
+
+    /// Total size of the message bodies in bytes.
+    pub fn content_size(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|(_, _, content)| content.len())
+            .sum()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Arguments for [`basic_get_drain`]
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::BasicGetDrainArguments;
+/// let args = BasicGetDrainArguments::new("reports")
+///     .batch_size(50)
+///     .max_bytes(16 * 1024 * 1024)
+///     .finish();
+/// assert!(args.validate().is_ok());
+/// ```
+///
+/// [`basic_get_drain`]: struct.Channel.html#method.basic_get_drain
+#[derive(Debug, Clone)]
+pub struct BasicGetDrainArguments {
+    /// Queue name. Default: "".
+    pub queue: String,
+    /// Default: `false`.
+    pub no_ack: bool,
+    /// Max number of messages got by a batch, must be greater than 0. Default: `100`.
+    pub batch_size: usize,
+    /// Budget of message bodies in bytes, must be greater than 0.
+    /// No more batch is got once the budget is used up. Default: `usize::MAX`.
+    pub max_bytes: usize,
+}
+
+impl Default for BasicGetDrainArguments {
+    fn default() -> Self {
+        Self {
+            queue: String::new(),
+            no_ack: false,
+            batch_size: 100,
+            max_bytes: usize::MAX,
+        }
+    }
+}
+
+impl BasicGetDrainArguments {
+    /// Create new arguments with defaults.
+    pub fn new(queue: &str) -> Self {
+        Self {
+            queue: queue.to_owned(),
+            ..Default::default()
+        }
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        queue, String
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        no_ack, bool
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        batch_size, usize
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        max_bytes, usize
+    }
+    /// Finish chained configuration and return new arguments.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the arguments, it is also done by [`basic_get_drain`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if queue name is longer than 255 bytes,
+    /// or `batch_size` or `max_bytes` is 0.
+    ///
+    /// [`basic_get_drain`]: struct.Channel.html#method.basic_get_drain
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)?;
+        check_flags(self.batch_size > 0, "batch_size: must be greater than 0")?;
+        check_flags(self.max_bytes > 0, "max_bytes: must be greater than 0")
+    }
+
+    fn get_arguments(&self) -> BasicGetArguments {
+        BasicGetArguments {
+            queue: self.queue.clone(),
+            no_ack: self.no_ack,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Drain of a queue returned by [`basic_get_drain`], a [`Stream`] of messages.
+///
+/// It gets messages in batches by [`basic_get_batch`] and yields them one by one,
+/// until the queue is found empty or the byte budget is used up.
+///
+/// The budget is checked before each batch, because the size of a message is not known
+/// before it is got, so the drain may exceed the budget by up to one batch.
+/// Use a smaller `batch_size` for a tighter bound.
+///
+/// Messages got but not yet yielded when the drain is dropped are lost if `no_ack` is `true`,
+/// because server considers them acknowledged once delivered. Otherwise they are still
+/// unacknowledged deliveries of the channel, which server redelivers only after the channel
+/// is closed, or they can be requeued by [`nack_all_unsettled`].
+///
+/// A batch being got is kept by the drain if the future of [`GetDrain::next`] is dropped,
+/// so no message is lost by cancelling it.
+///
+/// [`Stream`]: https://docs.rs/futures-core/latest/futures_core/stream/trait.Stream.html
+/// [`basic_get_drain`]: struct.Channel.html#method.basic_get_drain
+/// [`basic_get_batch`]: struct.Channel.html#method.basic_get_batch
+/// [`nack_all_unsettled`]: struct.Channel.html#method.nack_all_unsettled
+/// [`GetDrain::next`]: struct.GetDrain.html#method.next
+pub struct GetDrain<'a> {
+    channel: &'a Channel,
+    args: BasicGetDrainArguments,
+    buffer: VecDeque<GetMessage>,
+    /// batch being got
+    pending: Option<Pin<Box<dyn Future<Output = Result<GetBatch>> + Send + 'a>>>,
+    content_size: usize,
+    message_count: Option<u32>,
+    exhausted: bool,
+}
+
+impl<'a> GetDrain<'a> {
+    /// Returns the next message, or `None` once the queue is empty or the budget is used up.
+    ///
+    /// Same as the next item of the [`Stream`], without requiring a stream extension trait.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if any failure in comunication with server.
+    ///
+    /// [`Stream`]: https://docs.rs/futures-core/latest/futures_core/stream/trait.Stream.html
+    pub async fn next(&mut self) -> Result<Option<GetMessage>> {
+        NextMessage(self).await
+    }
+
+    /// Returns `true` if no more batch will be got.
+    fn is_done(&self) -> bool {
+        self.exhausted || self.content_size >= self.args.max_bytes
+    }
+
+    /// Total size of the message bodies got so far in bytes.
+    pub fn content_size(&self) -> usize {
+        self.content_size
+    }
+
+    /// Number of messages left in the queue reported by the last batch,
+    /// `None` if no batch is got yet.
+    pub fn message_count(&self) -> Option<u32> {
+        self.message_count
+    }
+}
+
+impl<'a> Stream for GetDrain<'a> {
+    type Item = Result<GetMessage>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        if this.buffer.is_empty() && this.pending.is_none() && !this.is_done() {
+            let channel = this.channel;
+            let args = this.args.get_arguments();
+            let batch_size = this.args.batch_size;
+            this.pending = Some(Box::pin(async move {
+                channel.basic_get_batch(args, batch_size).await
+            }));
+        }
+        if let Some(pending) = this.pending.as_mut() {
+            let batch = match pending.as_mut().poll(cx) {
+                Poll::Pending => return Poll::Pending,
+                Poll::Ready(batch) => batch,
+            };
+            this.pending = None;
+            let batch = match batch {
+                Ok(batch) => batch,
+                Err(err) => return Poll::Ready(Some(Err(err))),
+            };
+            // the queue was empty when the last message was got
+            this.exhausted =
+                batch.messages.len() < this.args.batch_size || batch.message_count == 0;
+            this.content_size = this.content_size.saturating_add(batch.content_size());
+            this.message_count = Some(batch.message_count);
+            this.buffer.extend(batch.messages);
+        }
+        Poll::Ready(this.buffer.pop_front().map(Ok))
+    }
+}
+
+impl<'a> fmt::Debug for GetDrain<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GetDrain")
+            .field("args", &self.args)
+            .field("buffered", &self.buffer.len())
+            .field("content_size", &self.content_size)
+            .field("message_count", &self.message_count)
+            .finish()
+    }
+}
+
+/// Future of [`GetDrain::next`].
+struct NextMessage<'b, 'a>(&'b mut GetDrain<'a>);
+
+impl<'b, 'a> Future for NextMessage<'b, 'a> {
+    type Output = Result<Option<GetMessage>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.0).poll_next(cx).map(Option::transpose)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+impl Channel {
+    /// Get up to `max` messages from a queue.
+    ///
+    /// The `get` requests are pipelined by the channel's dispatcher, up to 10 requests are
+    /// outstanding at a time, and another is sent once a message arrives without waiting for
+    /// the caller. No more is sent once server responds `get-empty`, but the outstanding
+    /// requests may still get messages published in the meantime, which are also returned.
+    /// Requests of other tasks sharing the channel are sent after the batch is done.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid or `max` is 0.
+    /// Returns error if any failure in comunication with server.
+    pub async fn basic_get_batch(&self, args: BasicGetArguments, max: usize) -> Result<GetBatch> {
+        args.validate()?;
+        check_flags(max > 0, "max: must be greater than 0")?;
+
+        let mut rx = self.register_get(args, max)?;
+        let mut batch = GetBatch::default();
+        let mut empty = false;
+        for _ in 0..max {
+            let response = match rx.recv().await {
+                Some(response) => response,
+                // no request is outstanding after `get-empty`
+                None if empty => break,
+                None => {
+                    return Err(Error::InternalChannelError(
+                        "failed to receive response to Get".to_string(),
+                    ))
+                }
+            };
+            match recv_get_content(response, &mut rx).await? {
+                Some(message) => {
+                    batch.message_count = message.0.message_count();
+                    batch.messages.push(message);
+                }
+                None => {
+                    batch.message_count = 0;
+                    empty = true;
+                }
+            }
+        }
+        Ok(batch)
+    }
+
+    /// Drain a queue by [`GetDrain`], which gets messages in batches until the queue is empty,
+    /// honouring the byte budget.
+    ///
+    /// # Usage
+    ///
+    /// ```no_run
+    /// # use amqprs::channel::{BasicAckArguments, BasicGetDrainArguments, Channel};
+    /// # async fn drain(channel: &Channel) -> Result<(), amqprs::error::Error> {
+    /// let args = BasicGetDrainArguments::new("reports").max_bytes(1 << 20).finish();
+    /// let mut drain = channel.basic_get_drain(args)?;
+    /// while let Some((get_ok, _props, _content)) = drain.next().await? {
+    ///     channel
+    ///         .basic_ack(BasicAckArguments::new(get_ok.delivery_tag(), false))
+    ///         .await?;
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    pub fn basic_get_drain(&self, args: BasicGetDrainArguments) -> Result<GetDrain<'_>> {
+        args.validate()?;
+        Ok(GetDrain {
+            channel: self,
+            args,
+            buffer: VecDeque::new(),
+            pending: None,
+            content_size: 0,
+            message_count: None,
+            exhausted: false,
+        })
+    }
+
+    /// Register responder of `count` chained `get` requests, dispatcher sends the requests
+    /// once the responder is registered, so it is cancel safe.
+    pub(super) fn register_get(
+        &self,
+        args: BasicGetArguments,
+        count: usize,
+    ) -> Result<mpsc::UnboundedReceiver<IncomingResponse>> {
+        let get = Get::new(0, to_short_str("queue", args.queue)?, args.no_ack);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let command = RegisterGetContentResponder {
+            tx,
+            no_ack: args.no_ack,
+            request: get,
+            count,
+            outstanding: 0,
+            empty: false,
+        };
+        self.shared.dispatcher_mgmt_tx.send(
+            DispatcherManagementCommand::RegisterGetContentResponder(command),
+        )?;
+        Ok(rx)
+    }
+}
+
+/// Receive the response to a `get` request, `None` if server responds `get-empty`.
+pub(super) async fn recv_get_message(
+    rx: &mut mpsc::UnboundedReceiver<IncomingResponse>,
+) -> Result<Option<GetMessage>> {
+    let response = rx.recv().await.ok_or_else(|| {
+        Error::InternalChannelError("failed to receive response to Get".to_string())
+    })?;
+    recv_get_content(response, rx).await
+}
+
+/// Receive the content following the response to a `get` request,
+/// `None` if server responds `get-empty`.
+async fn recv_get_content(
+    response: IncomingResponse,
+    rx: &mut mpsc::UnboundedReceiver<IncomingResponse>,
+) -> Result<Option<GetMessage>> {
+    let get_ok = match response? {
+        Frame::GetEmpty(_, _) => return Ok(None),
+        Frame::GetOk(_, get_ok) => get_ok,
+        frame => return Err(Error::ChannelUseError(frame.to_string())),
+    };
+
+    let basic_properties = match rx.recv().await.ok_or_else(|| {
+        Error::InternalChannelError("failed to receive Get ContentHeader".to_string())
+    })?? {
+        Frame::ContentHeader(header) => header.basic_properties,
+        frame => return Err(Error::ChannelUseError(frame.to_string())),
+    };
+
+    let content = match rx.recv().await.ok_or_else(|| {
+        Error::InternalChannelError("failed to receive Get ContentBody".to_string())
+    })?? {
+        Frame::ContentBody(content) => content.inner,
+        frame => return Err(Error::ChannelUseError(frame.to_string())),
+    };
+    Ok(Some((get_ok, basic_properties, content)))
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time;
+
+    use super::BasicGetDrainArguments;
+    use crate::{
+        api::{
+            channel::{
+                BasicAckArguments, BasicGetArguments, BasicPublishArguments, QueueDeclareArguments,
+                QueueDeleteArguments, QueuePurgeArguments,
+            },
+            connection::{Connection, OpenConnectionArguments},
+            error::Error,
+        },
+        test_utils::setup_logging,
+        BasicProperties,
+    };
+
+    #[test]
+    fn test_validate_drain_arguments() {
+        assert!(BasicGetDrainArguments::new("q").validate().is_ok());
+        assert!(matches!(
+            BasicGetDrainArguments::new("q").batch_size(0).validate(),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            BasicGetDrainArguments::new("q").max_bytes(0).validate(),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert!(matches!(
+            BasicGetDrainArguments::new(&"q".repeat(256)).validate(),
+            Err(Error::InvalidArgument(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_get_batch_and_drain() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let queue = "amqprs.test_get_batch_and_drain";
+        channel
+            .queue_declare(QueueDeclareArguments::new(queue))
+            .await
+            .unwrap();
+        channel
+            .queue_purge(QueuePurgeArguments::new(queue))
+            .await
+            .unwrap();
+        for i in 0..10u8 {
+            channel
+                .basic_publish(
+                    BasicProperties::default(),
+                    vec![i; 10],
+                    BasicPublishArguments::new("", queue),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert!(matches!(
+            channel
+                .basic_get_batch(BasicGetArguments::new(queue), 0)
+                .await,
+            Err(Error::InvalidArgument(_))
+        ));
+        let batch = channel
+            .basic_get_batch(BasicGetArguments::new(queue), 3)
+            .await
+            .unwrap();
+        assert_eq!(3, batch.messages.len());
+        assert_eq!(7, batch.message_count);
+        assert_eq!(vec![0u8; 10], batch.messages[0].2);
+        let last_tag = batch.messages[2].0.delivery_tag();
+        channel
+            .basic_ack(BasicAckArguments::new(last_tag, true))
+            .await
+            .unwrap();
+
+        // the budget is used up by the first batch
+        let mut drain = channel
+            .basic_get_drain(
+                BasicGetDrainArguments::new(queue)
+                    .no_ack(true)
+                    .batch_size(2)
+                    .max_bytes(15)
+                    .finish(),
+            )
+            .unwrap();
+        let mut drained = 0;
+        while let Some((_, _, content)) = drain.next().await.unwrap() {
+            assert_eq!(vec![3 + drained; 10], content);
+            drained += 1;
+        }
+        assert_eq!(2, drained);
+        assert_eq!(20, drain.content_size());
+        assert_eq!(Some(5), drain.message_count());
+        drop(drain);
+
+        // the batch stops at the empty queue
+        let batch = channel
+            .basic_get_batch(BasicGetArguments::new(queue).no_ack(true).finish(), 100)
+            .await
+            .unwrap();
+        assert_eq!(5, batch.messages.len());
+        assert_eq!(0, batch.message_count);
+        assert!(channel
+            .basic_get_batch(BasicGetArguments::new(queue), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        channel
+            .queue_delete(QueueDeleteArguments::new(queue))
+            .await
+            .unwrap();
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_get_batch_pipelined() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let queue = "amqprs.test_get_batch_pipelined";
+        channel
+            .queue_declare(QueueDeclareArguments::new(queue))
+            .await
+            .unwrap();
+        channel
+            .queue_purge(QueuePurgeArguments::new(queue))
+            .await
+            .unwrap();
+        for i in 0..25u8 {
+            channel
+                .basic_publish(
+                    BasicProperties::default(),
+                    vec![i],
+                    BasicPublishArguments::new("", queue),
+                )
+                .await
+                .unwrap();
+        }
+
+        // more than the outstanding requests, and beyond the empty queue
+        let batch = channel
+            .basic_get_batch(BasicGetArguments::new(queue).no_ack(true).finish(), 30)
+            .await
+            .unwrap();
+        assert_eq!(25, batch.messages.len());
+        assert_eq!(0, batch.message_count);
+        for (i, (_, _, content)) in batch.messages.iter().enumerate() {
+            assert_eq!(vec![i as u8], *content);
+        }
+        // the channel is still usable after the outstanding get-empty responses
+        assert!(channel
+            .basic_get(BasicGetArguments::new(queue))
+            .await
+            .unwrap()
+            .is_none());
+
+        channel
+            .queue_delete(QueueDeleteArguments::new(queue))
+            .await
+            .unwrap();
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_get_is_requeued() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let queue = "amqprs.test_cancelled_get_is_requeued";
+        channel
+            .queue_declare(QueueDeclareArguments::new(queue))
+            .await
+            .unwrap();
+        channel
+            .queue_purge(QueuePurgeArguments::new(queue))
+            .await
+            .unwrap();
+        channel
+            .basic_publish(
+                BasicProperties::default(),
+                b"hello".to_vec(),
+                BasicPublishArguments::new("", queue),
+            )
+            .await
+            .unwrap();
+
+        // the request is sent at the first poll, and dropped before the response
+        assert!(time::timeout(
+            Duration::ZERO,
+            channel.basic_get(BasicGetArguments::new(queue))
+        )
+        .await
+        .is_err());
+
+        let (get_ok, _, content) = channel
+            .basic_get(BasicGetArguments::new(queue))
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(get_ok.redelivered());
+        assert_eq!(b"hello".to_vec(), content);
+        let unsettled = channel.unsettled_deliveries().await.unwrap();
+        assert_eq!(1, unsettled.len());
+        channel
+            .basic_ack(BasicAckArguments::new(get_ok.delivery_tag(), false))
+            .await
+            .unwrap();
+
+        channel
+            .queue_delete(QueueDeleteArguments::new(queue))
+            .await
+            .unwrap();
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+}
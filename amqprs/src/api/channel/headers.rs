@@ -0,0 +1,342 @@
+use std::fmt::{self, Display};
+
+use amqp_serde::types::{FieldValue, TimeStamp};
+
+use super::arguments::{i64_value, invalid_type, invalid_value, str_value, u64_value};
+use crate::{
+    api::{error::Error, FieldTable, Result},
+    BasicProperties,
+};
+
+const X_DEATH: &str = "x-death";
+const X_FIRST_DEATH_QUEUE: &str = "x-first-death-queue";
+const X_FIRST_DEATH_REASON: &str = "x-first-death-reason";
+const X_FIRST_DEATH_EXCHANGE: &str = "x-first-death-exchange";
+const X_DELIVERY_COUNT: &str = "x-delivery-count";
+const CC: &str = "CC";
+
+////////////////////////////////////////////////////////////////////////////////
+/// Reason of dead-lettering, see [Dead Lettering](https://www.rabbitmq.com/dlx.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DeathReason {
+    /// Rejected or negatively acknowledged without requeue.
+    Rejected,
+    /// Expired because of message TTL.
+    Expired,
+    /// Dropped because of queue length limit.
+    Maxlen,
+    /// Returned more times than the delivery limit of quorum queue.
+    DeliveryLimit,
+    /// Any other reason, for example, added by newer server versions.
+    Other(String),
+}
+
+impl Display for DeathReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeathReason::Rejected => write!(f, "rejected"),
+            DeathReason::Expired => write!(f, "expired"),
+            DeathReason::Maxlen => write!(f, "maxlen"),
+            DeathReason::DeliveryLimit => write!(f, "delivery_limit"),
+            DeathReason::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+impl From<&str> for DeathReason {
+    fn from(value: &str) -> Self {
+        match value {
+            "rejected" => DeathReason::Rejected,
+            "expired" => DeathReason::Expired,
+            "maxlen" => DeathReason::Maxlen,
+            "delivery_limit" => DeathReason::DeliveryLimit,
+            other => DeathReason::Other(other.to_owned()),
+        }
+    }
+}
+
+/// An entry of `x-death` header, which server adds per queue and reason a message
+/// is dead-lettered for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct XDeath {
+    /// Queue the message was in before it was dead-lettered.
+    pub queue: String,
+    /// Reason of dead-lettering.
+    pub reason: DeathReason,
+    /// How many times the message was dead-lettered from the queue for the reason.
+    pub count: u64,
+    /// Exchange the message was published to.
+    pub exchange: String,
+    /// Routing keys of the message, including `CC` but not `BCC`.
+    pub routing_keys: Vec<String>,
+    /// When the message was first dead-lettered from the queue for the reason, in seconds.
+    pub time: TimeStamp,
+    /// Original expiration property of the message, which is removed on dead-lettering.
+    pub original_expiration: Option<String>,
+}
+
+impl TryFrom<&FieldTable> for XDeath {
+    type Error = Error;
+
+    /// Parse an entry of `x-death` header, unknown keys are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if a key is missing or has a value of wrong type.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    fn try_from(table: &FieldTable) -> Result<Self> {
+        let field = |key: &str| {
+            table
+                .get(&key.try_into().unwrap())
+                .ok_or_else(|| Error::InvalidArgument(format!("{}: missing {}", X_DEATH, key)))
+        };
+        let routing_keys = match field("routing-keys")? {
+            FieldValue::A(keys) => {
+                let keys: Vec<FieldValue> = keys.clone().into();
+                keys.iter()
+                    .map(|key| str_value("routing-keys", key).map(str::to_owned))
+                    .collect::<Result<_>>()?
+            }
+            other => return Err(invalid_type("routing-keys", "array", other)),
+        };
+        let time = match field("time")? {
+            FieldValue::T(v) => *v,
+            other => u64_value("time", other)?,
+        };
+        let original_expiration = match field("original-expiration") {
+            Ok(v) => Some(str_value("original-expiration", v)?.to_owned()),
+            Err(_) => None,
+        };
+        Ok(Self {
+            queue: str_value("queue", field("queue")?)?.to_owned(),
+            reason: str_value("reason", field("reason")?)?.into(),
+            count: u64_value("count", field("count")?)?,
+            exchange: str_value("exchange", field("exchange")?)?.to_owned(),
+            routing_keys,
+            time,
+            original_expiration,
+        })
+    }
+}
+
+/// Where a message was first dead-lettered, from `x-first-death-*` headers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FirstDeath {
+    /// `x-first-death-queue`.
+    pub queue: String,
+    /// `x-first-death-reason`.
+    pub reason: DeathReason,
+    /// `x-first-death-exchange`.
+    pub exchange: String,
+}
+
+/// Typed accessors of headers set by server, and of sender-selected distribution.
+impl BasicProperties {
+    /// Entries of `x-death` header, the most recent first.
+    ///
+    /// Returns empty if the message has never been dead-lettered.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if the header is malformed.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn x_death(&self) -> Result<Vec<XDeath>> {
+        let deaths = match self.header(X_DEATH) {
+            None => return Ok(vec![]),
+            Some(FieldValue::A(deaths)) => deaths,
+            Some(other) => return Err(invalid_type(X_DEATH, "array", other)),
+        };
+        let deaths: Vec<FieldValue> = deaths.clone().into();
+        deaths
+            .iter()
+            .map(|death| match death {
+                FieldValue::F(table) => XDeath::try_from(table),
+                other => Err(invalid_type(X_DEATH, "table", other)),
+            })
+            .collect()
+    }
+
+    /// How many times the message was dead-lettered from the queue, for any reason.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `x-death` header is malformed.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn death_count(&self, queue: &str) -> Result<u64> {
+        Ok(self
+            .x_death()?
+            .iter()
+            .filter(|death| death.queue == queue)
+            .map(|death| death.count)
+            .sum())
+    }
+
+    /// Where the message was first dead-lettered, `None` if it has never been dead-lettered.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any of `x-first-death-*` headers is malformed or missing.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn first_death(&self) -> Result<Option<FirstDeath>> {
+        let queue = match self.header(X_FIRST_DEATH_QUEUE) {
+            None => return Ok(None),
+            Some(v) => str_value(X_FIRST_DEATH_QUEUE, v)?.to_owned(),
+        };
+        let field = |key: &str| {
+            self.header(key)
+                .ok_or_else(|| Error::InvalidArgument(format!("{}: missing", key)))
+        };
+        Ok(Some(FirstDeath {
+            queue,
+            reason: str_value(X_FIRST_DEATH_REASON, field(X_FIRST_DEATH_REASON)?)?.into(),
+            exchange: str_value(X_FIRST_DEATH_EXCHANGE, field(X_FIRST_DEATH_EXCHANGE)?)?.to_owned(),
+        }))
+    }
+
+    /// `x-delivery-count` header, which quorum queues set on redelivery.
+    ///
+    /// Returns `None` if the message is delivered for the first time, or not from a quorum queue.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if the header is not a non-negative integer.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn delivery_count(&self) -> Result<Option<u64>> {
+        self.header(X_DELIVERY_COUNT)
+            .map(|v| {
+                let count = i64_value(X_DELIVERY_COUNT, v)?;
+                u64::try_from(count)
+                    .map_err(|_| invalid_value(X_DELIVERY_COUNT, &count.to_string()))
+            })
+            .transpose()
+    }
+
+    /// Routing keys of `CC` header, see [Sender-selected Distribution](https://www.rabbitmq.com/sender-selected.html).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if the header is not an array of strings.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn cc(&self) -> Result<Vec<String>> {
+        match self.header(CC) {
+            None => Ok(vec![]),
+            Some(FieldValue::A(keys)) => {
+                let keys: Vec<FieldValue> = keys.clone().into();
+                keys.iter()
+                    .map(|key| str_value(CC, key).map(str::to_owned))
+                    .collect()
+            }
+            Some(other) => Err(invalid_type(CC, "array", other)),
+        }
+    }
+
+    fn header(&self, key: &str) -> Option<&FieldValue> {
+        self.headers()?.get(&key.try_into().unwrap())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use amqp_serde::types::{FieldArray, FieldTable, FieldValue};
+
+    use super::{DeathReason, FirstDeath, XDeath};
+    use crate::BasicProperties;
+
+    fn death(queue: &str, reason: &str, count: i64) -> FieldValue {
+        let mut table = FieldTable::new();
+        table.insert("queue".try_into().unwrap(), queue.into());
+        table.insert("reason".try_into().unwrap(), reason.into());
+        table.insert("count".try_into().unwrap(), FieldValue::l(count));
+        table.insert("exchange".try_into().unwrap(), "work".into());
+        table.insert(
+            "routing-keys".try_into().unwrap(),
+            FieldValue::A(FieldArray::try_from(vec!["a".into(), "b".into()]).unwrap()),
+        );
+        table.insert("time".try_into().unwrap(), FieldValue::T(1_700_000_000));
+        FieldValue::F(table)
+    }
+
+    fn properties(headers: Vec<(&str, FieldValue)>) -> BasicProperties {
+        let mut table = FieldTable::new();
+        for (key, value) in headers {
+            table.insert(key.try_into().unwrap(), value);
+        }
+        BasicProperties::default().with_headers(table).finish()
+    }
+
+    #[test]
+    fn test_x_death() {
+        assert!(BasicProperties::default().x_death().unwrap().is_empty());
+
+        let deaths = FieldArray::try_from(vec![
+            death("retry", "expired", 2),
+            death("work", "rejected", 3),
+        ])
+        .unwrap();
+        let props = properties(vec![("x-death", FieldValue::A(deaths))]);
+        let deaths = props.x_death().unwrap();
+        assert_eq!(
+            XDeath {
+                queue: "retry".to_owned(),
+                reason: DeathReason::Expired,
+                count: 2,
+                exchange: "work".to_owned(),
+                routing_keys: vec!["a".to_owned(), "b".to_owned()],
+                time: 1_700_000_000,
+                original_expiration: None,
+            },
+            deaths[0]
+        );
+        assert_eq!(DeathReason::Rejected, deaths[1].reason);
+        assert_eq!(3, props.death_count("work").unwrap());
+        assert_eq!(0, props.death_count("other").unwrap());
+
+        let props = properties(vec![(
+            "x-death",
+            FieldValue::A(FieldArray::try_from(vec![death("q", "unknown", 1)]).unwrap()),
+        )]);
+        assert_eq!(
+            DeathReason::Other("unknown".to_owned()),
+            props.x_death().unwrap()[0].reason
+        );
+        let props = properties(vec![("x-death", "oops".into())]);
+        assert!(props.x_death().is_err());
+    }
+
+    #[test]
+    fn test_first_death_and_delivery_count() {
+        assert_eq!(None, BasicProperties::default().first_death().unwrap());
+        assert_eq!(None, BasicProperties::default().delivery_count().unwrap());
+
+        let props = properties(vec![
+            ("x-first-death-queue", "work".into()),
+            ("x-first-death-reason", "delivery_limit".into()),
+            ("x-first-death-exchange", "".into()),
+            ("x-delivery-count", FieldValue::I(4)),
+        ]);
+        assert_eq!(
+            Some(FirstDeath {
+                queue: "work".to_owned(),
+                reason: DeathReason::DeliveryLimit,
+                exchange: "".to_owned(),
+            }),
+            props.first_death().unwrap()
+        );
+        assert_eq!(Some(4), props.delivery_count().unwrap());
+
+        let props = properties(vec![
+            ("x-first-death-queue", "work".into()),
+            ("x-delivery-count", FieldValue::l(-1)),
+        ]);
+        assert!(props.first_death().is_err());
+        assert!(props.delivery_count().is_err());
+    }
+}
@@ -28,14 +28,21 @@ use std::{
 };
 
 use amqp_serde::types::AmqpChannelId;
-use tokio::sync::{mpsc, oneshot};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, Mutex};
 
 use super::callbacks::ChannelCallback;
 use crate::{
-    api::{error::Error, Result},
-    connection::Connection,
-    frame::{CloseChannel, CloseChannelOk, Deliver, Flow, FlowOk, Frame, MethodHeader, Return},
-    net::{ConnManagementCommand, IncomingMessage, OutgoingMessage},
+    api::{
+        error::{Error, InequivalentArguments},
+        events::{ChannelEvent, EventSender},
+        Result,
+    },
+    connection::{CloseNotifier, CloseReason, Connection, PublishGating},
+    frame::{
+        CloseChannel, CloseChannelOk, Deliver, Flow, FlowOk, Frame, Get, MethodHeader, Return,
+        PRECONDITION_FAILED,
+    },
+    net::{ConnManagementCommand, IncomingResponse, OutgoingMessage},
     BasicProperties,
 };
 #[cfg(feature = "traces")]
@@ -63,6 +70,8 @@ pub(crate) struct ReturnMessage {
 
 /// Message buffer for a `GetOk + content` sequence from server.
 pub(crate) struct GetOkMessage {
+    /// delivery tag of the message if it is in manual ack mode.
+    delivery_tag: Option<u64>,
     content: Option<Vec<u8>>,
     remaining: usize,
 }
@@ -70,6 +79,23 @@ pub(crate) struct GetOkMessage {
 /// Command to register consumer of asynchronous delivered contents.
 pub(crate) struct RegisterContentConsumer {
     consumer_tag: String,
+    /// `true` if the consumer is in auto ack mode.
+    no_ack: bool,
+    consumer_tx: mpsc::UnboundedSender<ConsumerMessage>,
+    /// tx half to request draining of the consumer task, `None` if the consumer does not support it.
+    drain_tx: Option<oneshot::Sender<DrainRequest>>,
+    ack_deadline: Option<AckDeadline>,
+}
+
+/// Command to record ack mode of a consumer requested with `no_wait`, before its deliveries arrive.
+pub(crate) struct SetConsumerAckMode {
+    consumer_tag: String,
+    /// `true` if the consumer is in auto ack mode.
+    no_ack: bool,
+}
+
+/// Command to register fallback consumer of orphaned deliveries.
+pub(crate) struct RegisterFallbackConsumer {
     consumer_tx: mpsc::UnboundedSender<ConsumerMessage>,
 }
 
@@ -80,21 +106,47 @@ pub(crate) struct DeregisterContentConsumer {
     consumer_tag: String,
 }
 
+/// Command to drain consumer once server confirms the cancellation of the consumer.
+pub(crate) struct DrainContentConsumer {
+    consumer_tag: String,
+    request: DrainRequest,
+}
+
+/// Command to abort draining consumer if the cancellation of the consumer fails.
+///
+/// The drain request is removed only if its requester has given up.
+pub(crate) struct AbortDrainContentConsumer {
+    consumer_tag: String,
+}
+
 /// Command to register sender to forward server's response to `get` request.
 ///
 /// Server will respond `get-ok` + `message propertities` + `content body` in sequence,
 /// so the sender should be mpsc instead of oneshot.
+///
+/// Dispatcher pipelines up to `count` requests, sending another after each `get-ok`
+/// until server responds `get-empty`, and drops the sender once all sent requests are responded.
 pub(crate) struct RegisterGetContentResponder {
-    tx: mpsc::UnboundedSender<IncomingMessage>,
+    tx: mpsc::UnboundedSender<IncomingResponse>,
+    /// `true` if the delivered message need not be acknowledged.
+    no_ack: bool,
+    /// `get` request sent to server by dispatcher once the responder is registered.
+    request: Get,
+    /// Number of `get` requests left to send, as long as messages are available.
+    count: usize,
+    /// Number of `get` requests sent and not yet responded.
+    outstanding: usize,
+    /// `true` once server responds `get-empty`, no more request is sent then.
+    empty: bool,
 }
 
 /// Command to register oneshot sender for response from server.
 pub(crate) struct RegisterOneshotResponder {
     pub method_header: &'static MethodHeader,
     /// oneshot sender to forward response message from server.
-    pub responder: oneshot::Sender<IncomingMessage>,
-    // oneshot sender to acknowledge registration is done.
-    pub acker: oneshot::Sender<()>,
+    pub responder: oneshot::Sender<IncomingResponse>,
+    /// request sent to server by dispatcher once the responder is registered.
+    pub request: Frame,
 }
 
 /// Command to register channel callbacks
@@ -105,10 +157,17 @@ pub(crate) struct RegisterChannelCallback {
 /// List of management commands for channel dispatcher.
 pub(crate) enum DispatcherManagementCommand {
     RegisterContentConsumer(RegisterContentConsumer),
+    SetConsumerAckMode(SetConsumerAckMode),
     DeregisterContentConsumer(DeregisterContentConsumer),
+    DrainContentConsumer(DrainContentConsumer),
+    AbortDrainContentConsumer(AbortDrainContentConsumer),
     RegisterGetContentResponder(RegisterGetContentResponder),
     RegisterOneshotResponder(RegisterOneshotResponder),
     RegisterChannelCallback(RegisterChannelCallback),
+    RegisterFallbackConsumer(RegisterFallbackConsumer),
+    SetOrphanedDeliveryHandling(OrphanedDeliveryArguments),
+    /// wake up dispatcher to flush the accumulated acks by their deadline.
+    ScheduleAckFlush,
 }
 
 /// Type represents an AMQP Channel.
@@ -121,14 +180,19 @@ pub(crate) enum DispatcherManagementCommand {
 ///
 /// # Concurrency
 ///
-/// It is not recommended to share same `Channel` object between tasks/threads, because it allows
-/// interleaving the AMQP protocol messages in same channel in concurrent setup.
-/// Applications should be aware of the this limitation in AMQP protocol itself.
+/// `Channel` can be cloned and shared between tasks/threads. Synchronous requests such as
+/// [`Channel::queue_declare`] are queued internally, only one is in flight per channel at a time
+/// as the protocol requires, so concurrent requests never receive each other's response.
+/// Asynchronous methods such as publishes and acknowledgements are not queued and flow
+/// concurrently, each publish is sent as a whole without interleaving with others.
 ///
-/// See detailed explanation in [`Java Client`], it applies to the library also.
+/// Nevertheless, a synchronous request waits for all the requests queued before it, and
+/// a channel-level error closes the channel for all its users. Using a channel per task is still
+/// preferable for throughput and isolation, see detailed explanation in [`Java Client`].
 ///
 /// [`Connection::open_channel`]: ../connection/struct.Connection.html#method.open_channel
 /// [`Channel::register_callback`]: struct.Channel.html#method.register_callback
+/// [`Channel::queue_declare`]: struct.Channel.html#method.queue_declare
 /// [`Java Client`]: https://www.rabbitmq.com/api-guide.html#concurrency
 #[derive(Clone)]
 pub struct Channel {
@@ -152,41 +216,110 @@ pub(crate) struct SharedChannelInner {
     conn_mgmt_tx: mpsc::Sender<ConnManagementCommand>,
     /// tx half to send management command to `ChannelDispatcher` task
     dispatcher_mgmt_tx: mpsc::UnboundedSender<DispatcherManagementCommand>,
+    /// reason of closure
+    close_notifier: CloseNotifier,
+    /// subscribable events
+    events: EventSender<ChannelEvent>,
+    /// `false` if server pauses the flow of content data
+    flow_active: watch::Sender<bool>,
+    /// `true` if ack batching is enabled, then accumulated acks are flushed before `cancel` and `close`
+    ack_batching: AtomicBool,
+    /// unsettled deliveries and accumulated acks, shared by dispatcher and user tasks,
+    /// so settling never waits for dispatcher, which may be running a callback.
+    settlements: Mutex<Settlements>,
 }
 
 impl SharedChannelInner {
-    /// Register oneshot responder for single message.
+    /// Send a synchronous request, returns the receiver of its response.
+    ///
+    /// The request is handed over to dispatcher, which registers the responder and then sends
+    /// the request to server, so the response is always routed to the responder of its own request.
     ///
-    /// Used for synchronous request/response protocol.
-    async fn register_responder(
+    /// It is cancel safe: the request is either not sent at all, or sent along with its responder.
+    /// If the receiver is dropped before response, the response is discarded by dispatcher.
+    fn send_request(
         &self,
         method_header: &'static MethodHeader,
-    ) -> Result<oneshot::Receiver<IncomingMessage>> {
+        request: Frame,
+    ) -> Result<oneshot::Receiver<IncomingResponse>> {
         let (responder, responder_rx) = oneshot::channel();
-        let (acker, acker_rx) = oneshot::channel();
         let cmd = RegisterOneshotResponder {
             method_header,
             responder,
-            acker,
+            request,
         };
         self.dispatcher_mgmt_tx
             .send(DispatcherManagementCommand::RegisterOneshotResponder(cmd))?;
-        acker_rx.await?;
         Ok(responder_rx)
     }
+
+    /// Update the settlements by `f`, and send the returned frames to server.
+    ///
+    /// The settlements are locked until the frames are sent, so frames of concurrent updates
+    /// reach server in order. Dispatcher is woken up if acks start to accumulate.
+    async fn update_settlements<T>(
+        &self,
+        f: impl FnOnce(&mut Settlements) -> Result<(Vec<Frame>, T)>,
+    ) -> Result<T> {
+        let mut settlements = self.settlements.lock().await;
+        let scheduled = settlements.flush_deadline().is_some();
+        let (frames, value) = f(&mut settlements)?;
+        for frame in frames {
+            self.outgoing_tx.send((self.channel_id, frame)).await?;
+        }
+        if !scheduled && settlements.flush_deadline().is_some() {
+            self.dispatcher_mgmt_tx
+                .send(DispatcherManagementCommand::ScheduleAckFlush)?;
+        }
+        Ok(value)
+    }
+
+    /// Blocking version of `update_settlements`, it must not be called in async context.
+    fn update_settlements_blocking<T>(
+        &self,
+        f: impl FnOnce(&mut Settlements) -> Result<(Vec<Frame>, T)>,
+    ) -> Result<T> {
+        let mut settlements = self.settlements.blocking_lock();
+        let scheduled = settlements.flush_deadline().is_some();
+        let (frames, value) = f(&mut settlements)?;
+        for frame in frames {
+            self.outgoing_tx.blocking_send((self.channel_id, frame))?;
+        }
+        if !scheduled && settlements.flush_deadline().is_some() {
+            self.dispatcher_mgmt_tx
+                .send(DispatcherManagementCommand::ScheduleAckFlush)?;
+        }
+        Ok(value)
+    }
+
+    /// Settle deliveries, acks may be accumulated if ack batching is enabled.
+    async fn settle(&self, settlement: Settlement) -> Result<()> {
+        self.update_settlements(|settlements| Ok((settlements.settle(settlement)?, ())))
+            .await
+    }
+
+    /// Send all accumulated acks to server.
+    async fn flush_acks(&self) -> Result<()> {
+        if self.ack_batching.load(Ordering::Acquire) {
+            self.update_settlements(|settlements| Ok((settlements.flush(true), ())))
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Close the channel, the channel resource is deregistered by dispatcher once server responds `close-ok`.
     async fn close_handshake(&self) -> Result<()> {
-        let responder_rx = self.register_responder(CloseChannelOk::header()).await?;
+        // accumulated acks are sent before `close`
+        self.flush_acks().await?;
+        let responder_rx = self.send_request(
+            CloseChannelOk::header(),
+            CloseChannel::default().into_frame(),
+        )?;
         synchronous_request!(
-            self.outgoing_tx,
-            (self.channel_id, CloseChannel::default().into_frame()),
             responder_rx,
             Frame::CloseChannelOk,
             Error::ChannelCloseError
         )?;
-        // deregister channel resource from connection handler,
-        // so that dispatcher will exit automatically.
-        let cmd = ConnManagementCommand::DeregisterChannelResource(self.channel_id);
-        self.conn_mgmt_tx.send(cmd).await?;
         Ok(())
     }
 }
@@ -244,25 +377,15 @@ impl Channel {
         Ok(())
     }
 
-    /// Register oneshot responder for single message.
+    /// Send a synchronous request, returns the receiver of its response.
     ///
-    /// Used for synchronous request/response protocol.
-    async fn register_responder(
+    /// See [`SharedChannelInner::send_request`].
+    fn send_request(
         &self,
         method_header: &'static MethodHeader,
-    ) -> Result<oneshot::Receiver<IncomingMessage>> {
-        let (responder, responder_rx) = oneshot::channel();
-        let (acker, acker_rx) = oneshot::channel();
-        let cmd = RegisterOneshotResponder {
-            method_header,
-            responder,
-            acker,
-        };
-        self.shared
-            .dispatcher_mgmt_tx
-            .send(DispatcherManagementCommand::RegisterOneshotResponder(cmd))?;
-        acker_rx.await?;
-        Ok(responder_rx)
+        request: Frame,
+    ) -> Result<oneshot::Receiver<IncomingResponse>> {
+        self.shared.send_request(method_header, request)
     }
 
     pub fn channel_id(&self) -> AmqpChannelId {
@@ -293,14 +416,8 @@ impl Channel {
     ///
     /// Returns error if any failure in communication with server.
     pub async fn flow(&self, active: bool) -> Result<bool> {
-        let responder_rx = self.register_responder(FlowOk::header()).await?;
-        let flow_ok = synchronous_request!(
-            self.shared.outgoing_tx,
-            (self.shared.channel_id, Flow::new(active).into_frame()),
-            responder_rx,
-            Frame::FlowOk,
-            Error::ChannelUseError
-        )?;
+        let responder_rx = self.send_request(FlowOk::header(), Flow::new(active).into_frame())?;
+        let flow_ok = synchronous_request!(responder_rx, Frame::FlowOk, Error::ChannelUseError)?;
         Ok(flow_ok.active)
     }
 
@@ -328,12 +445,119 @@ impl Channel {
             ) {
                 #[cfg(feature = "traces")]
                 info!("close channel {}", self);
+                self.notify_closed(CloseReason::ClientInitiated);
                 self.shared.close_handshake().await?;
             }
         }
         Ok(())
     }
 
+    /// Wait until the channel is closed, and returns the reason.
+    ///
+    /// Returns immediately if the channel has been closed.
+    /// If the channel is closed because its connection is closed, returns the reason
+    /// of the connection.
+    ///
+    /// It does not require a registered [`ChannelCallback`].
+    ///
+    /// [`ChannelCallback`]: ../callbacks/trait.ChannelCallback.html
+    pub async fn closed(&self) -> CloseReason {
+        self.shared.close_notifier.closed().await
+    }
+
+    /// Returns the reason if the channel has been closed, otherwise returns [`None`].
+    pub fn close_reason(&self) -> Option<CloseReason> {
+        self.shared.close_notifier.reason()
+    }
+
+    /// Returns [`Error::InequivalentArguments`] if the error is caused by server closing the channel
+    /// due to inequivalent arguments, otherwise returns the error as it is.
+    fn check_inequivalent(&self, err: Error) -> Error {
+        match self.close_reason() {
+            Some(CloseReason::ServerInitiated {
+                reply_code,
+                reply_text,
+            }) if reply_code == PRECONDITION_FAILED => InequivalentArguments::parse(&reply_text)
+                .map(|details| Error::InequivalentArguments(Box::new(details)))
+                .unwrap_or(err),
+            _ => err,
+        }
+    }
+
+    pub(crate) fn notify_closed(&self, reason: CloseReason) {
+        self.shared.close_notifier.notify(reason);
+    }
+
+    /// Subscribe to events of the channel.
+    ///
+    /// Each call returns a new receiver which gets all events published after subscription.
+    /// See [`events`] documentation.
+    ///
+    /// [`events`]: ../events/index.html
+    pub fn events(&self) -> broadcast::Receiver<ChannelEvent> {
+        self.shared.events.subscribe()
+    }
+
+    pub(crate) fn publish_event(&self, event: ChannelEvent) {
+        self.shared.events.send(event);
+    }
+
+    pub(crate) fn set_flow_active(&self, active: bool) {
+        self.shared.flow_active.send_replace(active);
+    }
+
+    /// Returns the reason if publishing is not permitted by server, otherwise returns [`None`].
+    fn publish_blocked_reason(&self) -> Option<String> {
+        if let Some(reason) = self.connection.blocked_reason() {
+            return Some(format!(
+                "connection is blocked by server, reason: {}",
+                reason
+            ));
+        }
+        if !*self.shared.flow_active.borrow() {
+            return Some(format!("flow of channel {} is paused by server", self));
+        }
+        None
+    }
+
+    /// Check if publishing is permitted according to [`PublishGating`] of the connection.
+    async fn check_publish_gate(&self) -> Result<()> {
+        match self.connection.publish_gating() {
+            PublishGating::Disabled => Ok(()),
+            PublishGating::FailFast => match self.publish_blocked_reason() {
+                Some(reason) => Err(Error::Blocked(reason)),
+                None => Ok(()),
+            },
+            PublishGating::Wait => {
+                let mut blocked_rx = self.connection.subscribe_blocked();
+                let mut flow_rx = self.shared.flow_active.subscribe();
+                loop {
+                    let is_blocked = blocked_rx.borrow_and_update().is_some();
+                    let is_flow_active = *flow_rx.borrow_and_update();
+                    if !is_blocked && is_flow_active {
+                        return Ok(());
+                    }
+                    // never fail because the sender halves are owned by the connection and channel
+                    tokio::select! {
+                        _ = blocked_rx.changed() => {}
+                        _ = flow_rx.changed() => {}
+                        reason = self.closed() => {
+                            return Err(Error::ChannelUseError(format!(
+                                "channel {} is closed while waiting to publish, {}",
+                                self, reason
+                            )));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the close reason of the connection associated with the channel.
+    pub(crate) fn connection_close_reason(&self) -> Option<CloseReason> {
+        self.connection.close_reason()
+    }
+
     pub(crate) fn clone_as_secondary(&self) -> Self {
         Self {
             shared: self.shared.clone(),
@@ -360,6 +584,7 @@ impl Drop for DropGuard {
             #[cfg(feature = "traces")]
             trace!("drop channel {}", self.0.channel_id);
 
+            self.0.close_notifier.notify(CloseReason::ClientInitiated);
             let inner = self.0.clone();
             tokio::spawn(async move {
                 #[cfg(feature = "traces")]
@@ -407,6 +632,11 @@ impl SharedChannelInner {
             outgoing_tx,
             conn_mgmt_tx,
             dispatcher_mgmt_tx,
+            close_notifier: CloseNotifier::new(),
+            events: EventSender::new(),
+            flow_active: watch::channel(true).0,
+            ack_batching: AtomicBool::new(false),
+            settlements: Mutex::new(Settlements::default()),
         }
     }
 }
@@ -416,7 +646,7 @@ mod tests {
     use tokio::time;
 
     use crate::{
-        channel::Channel,
+        channel::{Channel, QueueDeclareArguments, QueueDeleteArguments},
         connection::{Connection, OpenConnectionArguments},
         test_utils::setup_logging,
     };
@@ -465,22 +695,76 @@ mod tests {
         time::sleep(time::Duration::from_millis(50)).await;
         conn.close().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_concurrent_requests_on_shared_channel() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let conn = Connection::open(&args).await.unwrap();
+        let channel = conn.open_channel(None).await.unwrap();
+
+        // each task should get the response of its own request
+        let handles: Vec<_> = (0..10)
+            .map(|i| {
+                let channel = channel.clone();
+                tokio::spawn(async move {
+                    let name = format!("amqprs.test.shared.{}", i);
+                    let (queue_name, ..) = channel
+                        .queue_declare(QueueDeclareArguments::transient_autodelete(&name))
+                        .await
+                        .unwrap()
+                        .unwrap();
+                    assert_eq!(name, queue_name);
+                    channel
+                        .queue_delete(QueueDeleteArguments::new(&name))
+                        .await
+                        .unwrap();
+                })
+            })
+            .collect();
+        for h in handles {
+            h.await.unwrap();
+        }
+        channel.close().await.unwrap();
+        conn.close().await.unwrap();
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////////
 mod dispatcher;
 pub(crate) use dispatcher::*;
 
+mod arguments;
 mod basic;
 mod confim;
+mod deadline;
+mod drain;
 mod exchange;
+mod get;
+mod headers;
+mod outcome;
 mod queue;
+mod retry;
+mod settlement;
+mod stream;
+mod topology;
 mod tx;
 
 // public APIs
+pub use arguments::*;
 pub use basic::*;
 pub use confim::*;
+pub use deadline::*;
+pub use drain::*;
 pub use exchange::*;
+pub use get::*;
+pub use headers::*;
+pub use outcome::*;
 pub use queue::*;
+pub use retry::*;
+pub use settlement::*;
+pub use stream::*;
+pub use topology::*;
 #[allow(unused_imports)] // clippy false positive
 pub use tx::*;
@@ -0,0 +1,682 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeSet},
+    future::Future,
+    hash::{Hash, Hasher},
+    panic::{catch_unwind, AssertUnwindSafe},
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use tokio::sync::{
+    mpsc::{self, error::TryRecvError},
+    oneshot,
+};
+#[cfg(feature = "traces")]
+use tracing::{debug, error, trace};
+
+use crate::api::{
+    consumer::{HandlerError, Outcome, OutcomeConsumer},
+    validation::{check_flags, check_short_str},
+    Result,
+};
+
+use super::{
+    BasicAckArguments, BasicNackArguments, BasicRejectArguments, Channel, ConsumerMessage, Drain,
+    DrainRequest,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+/// Options for [`basic_consume_with_outcome`]
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::ConsumerOptions;
+/// # use amqprs::consumer::Outcome;
+/// # use amqprs::channel::OrderingKey;
+/// let x = ConsumerOptions::new()
+///     .default_outcome(Outcome::Reject { requeue: false })
+///     .max_ack_batch(50)
+///     .concurrency(8)
+///     .ordering_key(Some(OrderingKey::RoutingKey))
+///     .finish();
+/// ```
+///
+/// [`basic_consume_with_outcome`]: struct.Channel.html#method.basic_consume_with_outcome
+#[derive(Debug, Clone)]
+pub struct ConsumerOptions {
+    /// Outcome of the delivery if handler returns error or panics.
+    /// Default: `Nack { requeue: true }`.
+    pub default_outcome: Outcome,
+    /// Max number of consecutive acks coalesced into one ack with `multiple = true`.
+    /// Default: 1, i.e. every delivery is acked separately.
+    ///
+    /// Acks are sent once the batch is full, or no more delivery is ready to be handled.
+    /// Because delivery tags are scoped to the channel, an ack with `multiple = true` also
+    /// acknowledges the unacked deliveries of other consumers on the same channel,
+    /// so only coalesce acks if the consumer does not share its channel.
+    pub max_ack_batch: usize,
+    /// Max number of deliveries handled concurrently, only used by [`basic_consume_concurrent`].
+    /// Default: 1.
+    ///
+    /// Set `prefetch_count` of [`basic_qos`] accordingly, otherwise the concurrency is limited
+    /// by the deliveries sent by server.
+    ///
+    /// [`basic_consume_concurrent`]: struct.Channel.html#method.basic_consume_concurrent
+    /// [`basic_qos`]: struct.Channel.html#method.basic_qos
+    pub concurrency: usize,
+    /// If set, deliveries of same key are handled one by one in order of delivery,
+    /// only used by [`basic_consume_concurrent`]. Default: `None`.
+    ///
+    /// [`basic_consume_concurrent`]: struct.Channel.html#method.basic_consume_concurrent
+    pub ordering_key: Option<OrderingKey>,
+}
+
+/// Key of delivery to keep order of handling, see [`ConsumerOptions::ordering_key`].
+///
+/// Deliveries without the key are handled in any order.
+///
+/// [`ConsumerOptions::ordering_key`]: struct.ConsumerOptions.html#structfield.ordering_key
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum OrderingKey {
+    /// The routing key of the delivery.
+    RoutingKey,
+    /// The value of the header with given name.
+    Header(String),
+}
+
+impl Default for ConsumerOptions {
+    fn default() -> Self {
+        Self {
+            default_outcome: Outcome::Nack { requeue: true },
+            max_ack_batch: 1,
+            concurrency: 1,
+            ordering_key: None,
+        }
+    }
+}
+
+impl ConsumerOptions {
+    /// Create new options with defaults.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        default_outcome, Outcome
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        max_ack_batch, usize
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        concurrency, usize
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        ordering_key, Option<OrderingKey>
+    }
+
+    /// Finish chained configuration and return new options.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the options, it is also done by [`basic_consume_with_outcome`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `max_ack_batch` or `concurrency` is 0,
+    /// or the ordering header name is longer than 255 bytes.
+    ///
+    /// [`basic_consume_with_outcome`]: struct.Channel.html#method.basic_consume_with_outcome
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn validate(&self) -> Result<()> {
+        check_flags(self.max_ack_batch > 0, "max_ack_batch: must be at least 1")?;
+        check_flags(self.concurrency > 0, "concurrency: must be at least 1")?;
+        if let Some(OrderingKey::Header(ref name)) = self.ordering_key {
+            check_short_str("ordering_key", name)?;
+        }
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Future which catches panic of the inner future.
+struct CatchUnwind<F>(F);
+
+impl<F: Future + Unpin> Future for CatchUnwind<F> {
+    type Output = std::thread::Result<F::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let inner = &mut self.0;
+        match catch_unwind(AssertUnwindSafe(|| Pin::new(inner).poll(cx))) {
+            Ok(Poll::Pending) => Poll::Pending,
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Err(panic) => Poll::Ready(Err(panic)),
+        }
+    }
+}
+
+/// Acknowledgement of a delivery to be sent to server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Settlement {
+    Ack { delivery_tag: u64, multiple: bool },
+    Nack { delivery_tag: u64, requeue: bool },
+    Reject { delivery_tag: u64, requeue: bool },
+}
+
+/// Track deliveries being handled, and decide the acknowledgements of their outcomes.
+///
+/// Acks are coalesced into one ack with `multiple = true` only up to the lowest delivery tag still
+/// being handled, so that an incomplete delivery is never acknowledged.
+struct AckTracker {
+    max_ack_batch: usize,
+    /// delivery tags being handled
+    in_flight: BTreeSet<u64>,
+    /// delivery tags of the acks not yet sent
+    pending_acks: BTreeSet<u64>,
+}
+
+impl AckTracker {
+    fn new(max_ack_batch: usize) -> Self {
+        Self {
+            max_ack_batch,
+            in_flight: BTreeSet::new(),
+            pending_acks: BTreeSet::new(),
+        }
+    }
+
+    fn start(&mut self, delivery_tag: u64) {
+        self.in_flight.insert(delivery_tag);
+    }
+
+    /// Returns the acknowledgements to send for the outcome of a delivery.
+    fn settle(&mut self, delivery_tag: u64, outcome: Outcome) -> Vec<Settlement> {
+        self.in_flight.remove(&delivery_tag);
+        match outcome {
+            Outcome::Ack if self.max_ack_batch > 1 => {
+                self.pending_acks.insert(delivery_tag);
+                if self.pending_acks.len() >= self.max_ack_batch {
+                    self.flush()
+                } else {
+                    vec![]
+                }
+            }
+            Outcome::Ack => vec![Settlement::Ack {
+                delivery_tag,
+                multiple: false,
+            }],
+            // a settled delivery is not affected by later acks with `multiple = true`
+            Outcome::Nack { requeue } => vec![Settlement::Nack {
+                delivery_tag,
+                requeue,
+            }],
+            Outcome::Reject { requeue } => vec![Settlement::Reject {
+                delivery_tag,
+                requeue,
+            }],
+        }
+    }
+
+    /// Returns the pending acks to send. Acks below the lowest delivery tag being handled are
+    /// coalesced, the others are sent one by one.
+    fn flush(&mut self) -> Vec<Settlement> {
+        let coalesced = take_acks_below_in_flight(&mut self.pending_acks, &self.in_flight);
+        let mut settlements = Vec::with_capacity(self.pending_acks.len() + 1);
+        if let Some(&delivery_tag) = coalesced.iter().next_back() {
+            settlements.push(Settlement::Ack {
+                delivery_tag,
+                multiple: coalesced.len() > 1,
+            });
+        }
+        for delivery_tag in std::mem::take(&mut self.pending_acks) {
+            settlements.push(Settlement::Ack {
+                delivery_tag,
+                multiple: false,
+            });
+        }
+        settlements
+    }
+}
+
+/// Send the acknowledgements to server.
+async fn send_settlements(channel: &Channel, settlements: Vec<Settlement>) {
+    for settlement in settlements {
+        let result = match settlement {
+            Settlement::Ack {
+                delivery_tag,
+                multiple,
+            } => {
+                channel
+                    .basic_ack(BasicAckArguments::new(delivery_tag, multiple))
+                    .await
+            }
+            Settlement::Nack {
+                delivery_tag,
+                requeue,
+            } => {
+                channel
+                    .basic_nack(BasicNackArguments::new(delivery_tag, false, requeue))
+                    .await
+            }
+            Settlement::Reject {
+                delivery_tag,
+                requeue,
+            } => {
+                channel
+                    .basic_reject(BasicRejectArguments::new(delivery_tag, requeue))
+                    .await
+            }
+        };
+        if let Err(_err) = result {
+            #[cfg(feature = "traces")]
+            error!(
+                "failed to send {:?} on channel {}, cause: {}",
+                settlement, channel, _err
+            );
+        }
+    }
+}
+
+/// Take the pending acks below the lowest delivery tag being handled, which are safe to be
+/// coalesced into one ack with `multiple = true`.
+fn take_acks_below_in_flight(
+    pending_acks: &mut BTreeSet<u64>,
+    in_flight: &BTreeSet<u64>,
+) -> BTreeSet<u64> {
+    match in_flight.iter().next() {
+        Some(lowest) => {
+            let above = pending_acks.split_off(lowest);
+            std::mem::replace(pending_acks, above)
+        }
+        None => std::mem::take(pending_acks),
+    }
+}
+
+/// Outcome of a delivery reported by worker.
+struct Completion {
+    worker: usize,
+    delivery_tag: u64,
+    outcome: Outcome,
+}
+
+/// Returns the ordering key of the delivery if any.
+fn ordering_key(ordering: &OrderingKey, msg: &ConsumerMessage) -> Option<String> {
+    match ordering {
+        OrderingKey::RoutingKey => msg.deliver.as_ref().map(|d| d.routing_key().clone()),
+        OrderingKey::Header(name) => {
+            let name = name.clone().try_into().ok()?;
+            msg.basic_properties
+                .as_ref()?
+                .headers()?
+                .get(&name)
+                .map(ToString::to_string)
+        }
+    }
+}
+
+/// Returns the outcome of handling a delivery, which is `default_outcome` if handler
+/// returns error or panics.
+fn resolve_outcome(
+    handled: std::thread::Result<std::result::Result<Outcome, HandlerError>>,
+    default_outcome: Outcome,
+    _consumer_tag: &str,
+    _delivery_tag: u64,
+) -> Outcome {
+    match handled {
+        Ok(Ok(outcome)) => outcome,
+        Ok(Err(_err)) => {
+            #[cfg(feature = "traces")]
+            error!(
+                "consumer {} failed to handle delivery {}, cause: {}",
+                _consumer_tag, _delivery_tag, _err
+            );
+            default_outcome
+        }
+        Err(_) => {
+            #[cfg(feature = "traces")]
+            error!(
+                "consumer {} panicked in handling delivery {}",
+                _consumer_tag, _delivery_tag
+            );
+            default_outcome
+        }
+    }
+}
+
+/// Spawn worker task, which handles deliveries one by one and reports the outcomes.
+fn spawn_worker<F>(
+    channel: Channel,
+    consumer_tag: String,
+    worker: usize,
+    mut consumer: F,
+    default_outcome: Outcome,
+    done_tx: mpsc::UnboundedSender<Completion>,
+) -> mpsc::UnboundedSender<ConsumerMessage>
+where
+    F: OutcomeConsumer + Send + 'static,
+{
+    let (job_tx, mut job_rx) = mpsc::unbounded_channel::<ConsumerMessage>();
+    tokio::spawn(async move {
+        while let Some(mut msg) = job_rx.recv().await {
+            let deliver = msg.deliver.take().unwrap();
+            let delivery_tag = deliver.delivery_tag();
+            let handling = consumer.handle(
+                &channel,
+                deliver,
+                msg.basic_properties.take().unwrap(),
+                msg.content.take().unwrap(),
+            );
+            let outcome = resolve_outcome(
+                CatchUnwind(handling).await,
+                default_outcome,
+                &consumer_tag,
+                delivery_tag,
+            );
+            let completion = Completion {
+                worker,
+                delivery_tag,
+                outcome,
+            };
+            if done_tx.send(completion).is_err() {
+                break;
+            }
+        }
+    });
+    job_tx
+}
+
+/// Spawn tasks to run outcome consumers, returns the tx halves to forward deliveries to the tasks,
+/// and to request draining of the tasks.
+///
+/// Each consumer runs in its own worker task, so deliveries are handled concurrently by
+/// as many workers. A coordinator task dispatches deliveries to the workers and acknowledges
+/// the outcomes.
+pub(super) fn spawn_outcome_consumer<F>(
+    channel: Channel,
+    consumer_tag: String,
+    consumers: Vec<F>,
+    options: ConsumerOptions,
+) -> (
+    mpsc::UnboundedSender<ConsumerMessage>,
+    oneshot::Sender<DrainRequest>,
+)
+where
+    F: OutcomeConsumer + Send + 'static,
+{
+    let (consumer_tx, mut consumer_rx) = mpsc::unbounded_channel::<ConsumerMessage>();
+    let (drain_tx, mut drain) = Drain::new(false);
+    let (done_tx, mut done_rx) = mpsc::unbounded_channel::<Completion>();
+
+    let mut workers: Vec<_> = consumers
+        .into_iter()
+        .enumerate()
+        .map(|(worker, consumer)| {
+            spawn_worker(
+                channel.clone(),
+                consumer_tag.clone(),
+                worker,
+                consumer,
+                options.default_outcome,
+                done_tx.clone(),
+            )
+        })
+        .collect();
+    // workers hold the tx halves, so `done_rx` returns `None` once all workers exit
+    drop(done_tx);
+    let concurrency = workers.len();
+
+    tokio::spawn(async move {
+        #[cfg(feature = "traces")]
+        trace!(
+            "starts task for outcome consumer {} with {} workers on channel {}",
+            consumer_tag,
+            concurrency,
+            channel
+        );
+        let mut tracker = AckTracker::new(options.max_ack_batch);
+        // number of deliveries dispatched to each worker but not completed
+        let mut loads = vec![0usize; concurrency];
+        let mut outstanding = 0;
+
+        loop {
+            // accept new delivery only if any worker is available
+            let accepting = !workers.is_empty() && outstanding < concurrency;
+            // handle everything ready before flushing the coalesced acks
+            let event = match done_rx.try_recv() {
+                Ok(completion) => Ok(Some(completion)),
+                Err(_) if accepting => match consumer_rx.try_recv() {
+                    Ok(msg) => Err(Some(msg)),
+                    Err(TryRecvError::Disconnected) => Err(None),
+                    Err(TryRecvError::Empty) => {
+                        send_settlements(&channel, tracker.flush()).await;
+                        tokio::select! {
+                            completion = done_rx.recv() => Ok(completion),
+                            msg = consumer_rx.recv() => Err(msg),
+                        }
+                    }
+                },
+                Err(_) => {
+                    send_settlements(&channel, tracker.flush()).await;
+                    Ok(done_rx.recv().await)
+                }
+            };
+            match event {
+                Ok(Some(completion)) => {
+                    loads[completion.worker] -= 1;
+                    outstanding -= 1;
+                    let settlements = tracker.settle(completion.delivery_tag, completion.outcome);
+                    send_settlements(&channel, settlements).await;
+                    drain.processed();
+                }
+                // all workers exited
+                Ok(None) => break,
+                Err(Some(msg)) => {
+                    let delivery_tag = match msg.deliver {
+                        Some(ref deliver) => deliver.delivery_tag(),
+                        None => continue,
+                    };
+                    if drain.should_requeue() {
+                        let settlements =
+                            tracker.settle(delivery_tag, Outcome::Nack { requeue: true });
+                        send_settlements(&channel, settlements).await;
+                        continue;
+                    }
+                    // deliveries of same key are handled by same worker in order,
+                    // others go to the least loaded worker
+                    let worker = match options
+                        .ordering_key
+                        .as_ref()
+                        .and_then(|ordering| ordering_key(ordering, &msg))
+                    {
+                        Some(key) => {
+                            let mut hasher = DefaultHasher::new();
+                            key.hash(&mut hasher);
+                            (hasher.finish() % concurrency as u64) as usize
+                        }
+                        None => (0..concurrency).min_by_key(|&i| loads[i]).unwrap_or(0),
+                    };
+                    tracker.start(delivery_tag);
+                    loads[worker] += 1;
+                    outstanding += 1;
+                    if workers[worker].send(msg).is_err() {
+                        // should not happen, worker task never exits before its tx half drops
+                        loads[worker] -= 1;
+                        outstanding -= 1;
+                        let settlements = tracker.settle(delivery_tag, options.default_outcome);
+                        send_settlements(&channel, settlements).await;
+                    }
+                }
+                // consumer is cancelled, let workers finish the dispatched deliveries
+                Err(None) => workers.clear(),
+            }
+        }
+        send_settlements(&channel, tracker.flush()).await;
+        drain.finish();
+        #[cfg(feature = "traces")]
+        debug!("exit task of outcome consumer {}", consumer_tag);
+    });
+
+    (consumer_tx, drain_tx)
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use crate::api::consumer::{HandlerError, Outcome};
+
+    use super::{
+        resolve_outcome, take_acks_below_in_flight, AckTracker, CatchUnwind, ConsumerOptions,
+        OrderingKey, Settlement,
+    };
+
+    #[tokio::test]
+    async fn test_catch_unwind() {
+        let ok = CatchUnwind(Box::pin(async { 1 })).await;
+        assert_eq!(1, ok.unwrap());
+
+        let panicked = CatchUnwind(Box::pin(async {
+            panic!("handler panicked");
+        }))
+        .await;
+        assert!(panicked.is_err());
+    }
+
+    #[test]
+    fn test_take_acks_below_in_flight() {
+        let mut pending: BTreeSet<u64> = [1, 2, 4, 6].into_iter().collect();
+        let in_flight: BTreeSet<u64> = [3, 5].into_iter().collect();
+        let coalesced = take_acks_below_in_flight(&mut pending, &in_flight);
+        assert_eq!(vec![1, 2], coalesced.into_iter().collect::<Vec<_>>());
+        assert_eq!(vec![4, 6], pending.iter().copied().collect::<Vec<_>>());
+
+        let coalesced = take_acks_below_in_flight(&mut pending, &BTreeSet::new());
+        assert_eq!(vec![4, 6], coalesced.into_iter().collect::<Vec<_>>());
+        assert!(pending.is_empty());
+    }
+
+    fn ack(delivery_tag: u64, multiple: bool) -> Settlement {
+        Settlement::Ack {
+            delivery_tag,
+            multiple,
+        }
+    }
+
+    #[test]
+    fn test_settle_without_coalescing() {
+        let mut tracker = AckTracker::new(1);
+        for delivery_tag in 1..=3 {
+            tracker.start(delivery_tag);
+        }
+        assert_eq!(vec![ack(2, false)], tracker.settle(2, Outcome::Ack));
+        assert_eq!(
+            vec![Settlement::Nack {
+                delivery_tag: 1,
+                requeue: true
+            }],
+            tracker.settle(1, Outcome::Nack { requeue: true })
+        );
+        assert_eq!(
+            vec![Settlement::Reject {
+                delivery_tag: 3,
+                requeue: false
+            }],
+            tracker.settle(3, Outcome::Reject { requeue: false })
+        );
+        assert!(tracker.flush().is_empty());
+    }
+
+    #[test]
+    fn test_settle_coalesces_acks() {
+        let mut tracker = AckTracker::new(3);
+        for delivery_tag in 1..=5 {
+            tracker.start(delivery_tag);
+        }
+        // acks are pending until the batch is full
+        assert!(tracker.settle(1, Outcome::Ack).is_empty());
+        assert!(tracker.settle(2, Outcome::Ack).is_empty());
+        // nack is sent at once and not covered by the coalesced ack
+        assert_eq!(
+            vec![Settlement::Nack {
+                delivery_tag: 3,
+                requeue: false
+            }],
+            tracker.settle(3, Outcome::Nack { requeue: false })
+        );
+        // 4 is still being handled, so only 1 and 2 are coalesced, 5 is acked alone
+        assert_eq!(
+            vec![ack(2, true), ack(5, false)],
+            tracker.settle(5, Outcome::Ack)
+        );
+        assert!(tracker.flush().is_empty());
+
+        // single pending ack below in flight is not sent with `multiple = true`
+        assert!(tracker.settle(4, Outcome::Ack).is_empty());
+        assert_eq!(vec![ack(4, false)], tracker.flush());
+    }
+
+    #[test]
+    fn test_flush_coalesces_all_when_none_in_flight() {
+        let mut tracker = AckTracker::new(10);
+        for delivery_tag in 1..=3 {
+            tracker.start(delivery_tag);
+        }
+        for delivery_tag in [3, 1, 2] {
+            assert!(tracker.settle(delivery_tag, Outcome::Ack).is_empty());
+        }
+        assert_eq!(vec![ack(3, true)], tracker.flush());
+        assert!(tracker.flush().is_empty());
+    }
+
+    #[test]
+    fn test_resolve_outcome() {
+        let default_outcome = Outcome::Reject { requeue: false };
+        assert_eq!(
+            Outcome::Nack { requeue: true },
+            resolve_outcome(
+                Ok(Ok(Outcome::Nack { requeue: true })),
+                default_outcome,
+                "ctag",
+                1
+            )
+        );
+        let err: HandlerError = "failed".into();
+        assert_eq!(
+            default_outcome,
+            resolve_outcome(Ok(Err(err)), default_outcome, "ctag", 2)
+        );
+        assert_eq!(
+            default_outcome,
+            resolve_outcome(Err(Box::new("panicked")), default_outcome, "ctag", 3)
+        );
+    }
+
+    #[test]
+    fn test_validate_options() {
+        assert!(ConsumerOptions::default().validate().is_ok());
+        assert!(ConsumerOptions::new()
+            .max_ack_batch(0)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(ConsumerOptions::new()
+            .concurrency(0)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(ConsumerOptions::new()
+            .ordering_key(Some(OrderingKey::Header("h".repeat(256))))
+            .finish()
+            .validate()
+            .is_err());
+    }
+}
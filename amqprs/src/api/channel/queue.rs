@@ -1,16 +1,25 @@
 use amqp_serde::types::AmqpMessageCount;
 
-use super::Channel;
+use super::{
+    arguments::check_binding_arguments, Channel, HeadersBindingArguments, QueueArguments, QueueType,
+};
 use crate::{
-    api::{error::Error, FieldTable, Result},
+    api::{
+        error::Error,
+        validation::{
+            check_exchange_name, check_flags, check_not_empty, check_not_reserved,
+            check_queue_name, check_short_str, to_short_str,
+        },
+        FieldTable, Result,
+    },
     frame::{
         BindQueue, BindQueueOk, DeclareQueue, DeclareQueueOk, DeleteQueue, DeleteQueueOk, Frame,
         PurgeQueue, PurgeQueueOk, UnbindQueue, UnbindQueueOk,
     },
 };
 
-#[cfg(feature = "compliance_assert")]
-use crate::api::compliance_asserts::{assert_exchange_name, assert_queue_name};
+#[cfg(feature = "traces")]
+use tracing::warn;
 
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`queue_declare`]
@@ -44,14 +53,16 @@ pub struct QueueDeclareArguments {
     no_wait: bool,
     /// Default: empty table.
     arguments: FieldTable,
+    /// Default: `false`. If set and the queue exists with different flags or arguments,
+    /// it is passively declared instead with a warning, see [`queue_declare`].
+    ///
+    /// [`queue_declare`]: struct.Channel.html#method.queue_declare
+    passive_fallback: bool,
 }
 
 impl QueueDeclareArguments {
     /// Default arguments: declares a transient, client-named, non-exclusive and non-autodelete queue
     pub fn new(queue: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(queue);
-
         Self {
             queue: queue.to_owned(),
             passive: false,
@@ -60,15 +71,13 @@ impl QueueDeclareArguments {
             auto_delete: false,
             no_wait: false,
             arguments: FieldTable::new(),
+            passive_fallback: false,
         }
     }
 
     /// Arguments of a durable, non-exclusive, non-autodelete
     /// queue. Usually a good fit for queues with well-known names.
     pub fn durable_client_named(queue: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(queue);
-
         Self {
             queue: queue.to_owned(),
             passive: false,
@@ -77,6 +86,7 @@ impl QueueDeclareArguments {
             auto_delete: false,
             no_wait: false,
             arguments: FieldTable::new(),
+            passive_fallback: false,
         }
     }
 
@@ -91,6 +101,7 @@ impl QueueDeclareArguments {
             auto_delete: false,
             no_wait: false,
             arguments: FieldTable::new(),
+            passive_fallback: false,
         }
     }
 
@@ -106,6 +117,7 @@ impl QueueDeclareArguments {
             auto_delete: true,
             no_wait: false,
             arguments: FieldTable::new(),
+            passive_fallback: false,
         }
     }
 
@@ -138,13 +150,70 @@ impl QueueDeclareArguments {
         /// Chainable setter method.
         arguments, FieldTable
     }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        passive_fallback, bool
+    }
+    /// Chainable setter method of typed `x-` arguments, which replaces `arguments`.
+    ///
+    /// See [`QueueArguments`] for details.
+    ///
+    /// [`QueueArguments`]: struct.QueueArguments.html
+    pub fn queue_arguments(&mut self, queue_arguments: QueueArguments) -> &mut Self {
+        self.arguments = queue_arguments.into();
+        self
+    }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(&self.queue);
-
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`queue_declare`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if queue name is longer than 255 bytes,
+    /// uses the reserved prefix "amq." unless passive, or is server-named with `no_wait`,
+    /// because the generated name can not be returned, or `passive_fallback` is set with
+    /// `no_wait`, because inequivalent arguments are only detected by the reply.
+    ///
+    /// Unless passive, the `x-` arguments are also validated as [`QueueArguments`], and a quorum
+    /// queue or stream must be durable, non-exclusive and non-autodelete.
+    ///
+    /// [`queue_declare`]: struct.Channel.html#method.queue_declare
+    /// [`QueueArguments`]: struct.QueueArguments.html
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)?;
+        if !self.passive {
+            check_not_reserved("queue", &self.queue)?;
+        }
+        check_flags(
+            !(self.queue.is_empty() && self.no_wait),
+            "no_wait: can not declare a server-named queue without waiting for its name",
+        )?;
+        check_flags(
+            !(self.passive_fallback && self.no_wait),
+            "passive_fallback: requires waiting for the reply",
+        )?;
+        // arguments are ignored by server in passive mode
+        if self.passive {
+            return Ok(());
+        }
+        let queue_arguments = QueueArguments::try_from(&self.arguments)?;
+        queue_arguments.validate()?;
+        if let Some(queue_type @ (QueueType::Quorum | QueueType::Stream)) =
+            queue_arguments.queue_type
+        {
+            check_flags(
+                self.durable && !self.exclusive && !self.auto_delete,
+                &format!(
+                    "x-queue-type: {} queue must be durable, non-exclusive and non-autodelete",
+                    queue_type
+                ),
+            )?;
+        }
+        Ok(())
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`queue_bind`]
@@ -169,12 +238,6 @@ pub struct QueueBindArguments {
 impl QueueBindArguments {
     /// Create new arguments with defaults.
     pub fn new(queue: &str, exchange: &str, routing_key: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        {
-            assert_queue_name(queue);
-            assert_exchange_name(exchange);
-        }
-
         Self {
             queue: queue.to_owned(),
             exchange: exchange.to_owned(),
@@ -204,16 +267,41 @@ impl QueueBindArguments {
         /// Chainable setter method.
         arguments, FieldTable
     }
+    /// Chainable setter method of typed arguments of headers exchange, which replaces `arguments`.
+    ///
+    /// See [`HeadersBindingArguments`] for details.
+    ///
+    /// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+    pub fn headers_arguments(&mut self, headers_arguments: HeadersBindingArguments) -> &mut Self {
+        self.arguments = headers_arguments.into();
+        self
+    }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        {
-            assert_queue_name(&self.queue);
-            assert_exchange_name(&self.exchange);
-        }
-
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`queue_bind`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes,
+    /// or exchange is the default exchange, to which no binding can be added,
+    /// or `x-match` is present but the arguments are invalid as [`HeadersBindingArguments`].
+    ///
+    /// [`queue_bind`]: struct.Channel.html#method.queue_bind
+    /// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)?;
+        check_exchange_name("exchange", &self.exchange)?;
+        check_not_empty(
+            "exchange",
+            &self.exchange,
+            "can not bind to the default exchange",
+        )?;
+        check_short_str("routing_key", &self.routing_key)?;
+        check_binding_arguments(&self.arguments)
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`queue_purge`]
@@ -232,14 +320,22 @@ pub struct QueuePurgeArguments {
 impl QueuePurgeArguments {
     /// Create new arguments with defaults.
     pub fn new(queue: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(queue);
-
         Self {
             queue: queue.to_owned(),
             no_wait: false,
         }
     }
+
+    /// Validate the arguments, it is also done by [`queue_purge`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if queue name is longer than 255 bytes.
+    ///
+    /// [`queue_purge`]: struct.Channel.html#method.queue_purge
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`queue_delete`]
@@ -262,9 +358,6 @@ pub struct QueueDeleteArguments {
 impl QueueDeleteArguments {
     /// Create new arguments with defaults.
     pub fn new(queue: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(queue);
-
         Self {
             queue: queue.to_owned(),
             if_unused: false,
@@ -290,11 +383,19 @@ impl QueueDeleteArguments {
     }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        assert_queue_name(&self.queue);
-
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`queue_delete`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if queue name is longer than 255 bytes.
+    ///
+    /// [`queue_delete`]: struct.Channel.html#method.queue_delete
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)
+    }
 }
 ////////////////////////////////////////////////////////////////////////////////
 /// Arguments for [`queue_unbind`]
@@ -317,12 +418,6 @@ pub struct QueueUnbindArguments {
 impl QueueUnbindArguments {
     /// Create new arguments with defaults.
     pub fn new(queue: &str, exchange: &str, routing_key: &str) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        {
-            assert_queue_name(queue);
-            assert_exchange_name(exchange);
-        }
-
         Self {
             queue: queue.to_owned(),
             exchange: exchange.to_owned(),
@@ -346,16 +441,41 @@ impl QueueUnbindArguments {
         /// Chainable setter method.
         arguments, FieldTable
     }
+    /// Chainable setter method of typed arguments of headers exchange, which replaces `arguments`.
+    ///
+    /// See [`HeadersBindingArguments`] for details.
+    ///
+    /// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+    pub fn headers_arguments(&mut self, headers_arguments: HeadersBindingArguments) -> &mut Self {
+        self.arguments = headers_arguments.into();
+        self
+    }
     /// Finish chained configuration and return new arguments.
     pub fn finish(&mut self) -> Self {
-        #[cfg(feature = "compliance_assert")]
-        {
-            assert_queue_name(&self.queue);
-            assert_exchange_name(&self.exchange);
-        }
-
         self.clone()
     }
+
+    /// Validate the arguments, it is also done by [`queue_unbind`] before sending request.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes,
+    /// or exchange is the default exchange,
+    /// or `x-match` is present but the arguments are invalid as [`HeadersBindingArguments`].
+    ///
+    /// [`queue_unbind`]: struct.Channel.html#method.queue_unbind
+    /// [`HeadersBindingArguments`]: struct.HeadersBindingArguments.html
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)?;
+        check_exchange_name("exchange", &self.exchange)?;
+        check_not_empty(
+            "exchange",
+            &self.exchange,
+            "can not unbind from the default exchange",
+        )?;
+        check_short_str("routing_key", &self.routing_key)?;
+        check_binding_arguments(&self.arguments)
+    }
 }
 
 /////////////////////////////////////////////////////////////////////////////
@@ -368,14 +488,53 @@ impl Channel {
     /// Returns a tuple `(queue_name, message_count, consumer_count)`
     /// if `no_wait` argument is `false`, otherwise returns [`None`].
     ///
+    /// If the queue exists with different flags or arguments, server closes the channel.
+    /// With `passive_fallback` of the arguments, the queue is first declared on a short-lived
+    /// channel, and if it exists with different settings, it is passively declared on this channel
+    /// instead with a warning, so that this channel is kept open.
+    ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns [`Error::InequivalentArguments`] if the queue exists with different flags or arguments,
+    /// unless `passive_fallback` is set.
     /// Returns error if any failure in comunication with server.
+    ///
+    /// [`Error::InequivalentArguments`]: ../error/enum.Error.html#variant.InequivalentArguments
     pub async fn queue_declare(
         &self,
         args: QueueDeclareArguments,
     ) -> Result<Option<(String, AmqpMessageCount, u32)>> {
-        let mut declare = DeclareQueue::new(0, args.queue.try_into().unwrap(), args.arguments);
+        args.validate()?;
+        // a passive or server-named declaration never conflicts with an existing queue
+        if !args.passive_fallback || args.passive || args.queue.is_empty() {
+            return self.request_queue_declare(args).await;
+        }
+        let queue = args.queue.clone();
+        let declared = self
+            .connection
+            .on_short_lived_channel(
+                |channel| async move { channel.request_queue_declare(args).await },
+            )
+            .await;
+        match declared {
+            Err(Error::InequivalentArguments(_details)) => {
+                #[cfg(feature = "traces")]
+                warn!("{}, declare it passively on channel {}", _details, self);
+                self.request_queue_declare(
+                    QueueDeclareArguments::new(&queue).passive(true).finish(),
+                )
+                .await
+            }
+            declared => declared,
+        }
+    }
+
+    async fn request_queue_declare(
+        &self,
+        args: QueueDeclareArguments,
+    ) -> Result<Option<(String, AmqpMessageCount, u32)>> {
+        let mut declare = DeclareQueue::new(0, to_short_str("queue", args.queue)?, args.arguments);
         declare.set_passive(args.passive);
         declare.set_durable(args.durable);
         declare.set_exclusive(args.exclusive);
@@ -388,14 +547,13 @@ impl Channel {
                 .await?;
             Ok(None)
         } else {
-            let responder_rx = self.register_responder(DeclareQueueOk::header()).await?;
-            let declare_ok = synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.channel_id(), declare.into_frame()),
-                responder_rx,
-                Frame::DeclareQueueOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(DeclareQueueOk::header(), declare.into_frame())?;
+            let declare_ok = async {
+                // server closes the channel if the arguments are inequivalent
+                synchronous_request!(responder_rx, Frame::DeclareQueueOk, Error::ChannelUseError)
+            }
+            .await
+            .map_err(|err| self.check_inequivalent(err))?;
             Ok(Some((
                 declare_ok.queue.into(),
                 declare_ok.message_count,
@@ -408,13 +566,15 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns error if any failure in comunication with server.
     pub async fn queue_bind(&self, args: QueueBindArguments) -> Result<()> {
+        args.validate()?;
         let bind = BindQueue::new(
             0,
-            args.queue.try_into().unwrap(),
-            args.exchange.try_into().unwrap(),
-            args.routing_key.try_into().unwrap(),
+            to_short_str("queue", args.queue)?,
+            to_short_str("exchange", args.exchange)?,
+            to_short_str("routing_key", args.routing_key)?,
             args.no_wait,
             args.arguments,
         );
@@ -425,15 +585,8 @@ impl Channel {
                 .send((self.channel_id(), bind.into_frame()))
                 .await?;
         } else {
-            let responder_rx = self.register_responder(BindQueueOk::header()).await?;
-
-            synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.channel_id(), bind.into_frame()),
-                responder_rx,
-                Frame::BindQueueOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(BindQueueOk::header(), bind.into_frame())?;
+            synchronous_request!(responder_rx, Frame::BindQueueOk, Error::ChannelUseError)?;
         }
         Ok(())
     }
@@ -446,9 +599,11 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns error if any failure in comunication with server.
     pub async fn queue_purge(&self, args: QueuePurgeArguments) -> Result<Option<AmqpMessageCount>> {
-        let purge = PurgeQueue::new(0, args.queue.try_into().unwrap(), args.no_wait);
+        args.validate()?;
+        let purge = PurgeQueue::new(0, to_short_str("queue", args.queue)?, args.no_wait);
 
         if args.no_wait {
             self.shared
@@ -457,15 +612,9 @@ impl Channel {
                 .await?;
             Ok(None)
         } else {
-            let responder_rx = self.register_responder(PurgeQueueOk::header()).await?;
-
-            let purge_ok = synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.channel_id(), purge.into_frame()),
-                responder_rx,
-                Frame::PurgeQueueOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(PurgeQueueOk::header(), purge.into_frame())?;
+            let purge_ok =
+                synchronous_request!(responder_rx, Frame::PurgeQueueOk, Error::ChannelUseError)?;
             Ok(Some(purge_ok.message_count))
         }
     }
@@ -477,12 +626,14 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns error if any failure in comunication with server.
     pub async fn queue_delete(
         &self,
         args: QueueDeleteArguments,
     ) -> Result<Option<AmqpMessageCount>> {
-        let mut delete = DeleteQueue::new(0, args.queue.try_into().unwrap());
+        args.validate()?;
+        let mut delete = DeleteQueue::new(0, to_short_str("queue", args.queue)?);
         delete.set_if_unused(args.if_unused);
         delete.set_if_empty(args.if_empty);
         delete.set_no_wait(args.no_wait);
@@ -493,15 +644,9 @@ impl Channel {
                 .await?;
             Ok(None)
         } else {
-            let responder_rx = self.register_responder(DeleteQueueOk::header()).await?;
-
-            let delete_ok = synchronous_request!(
-                self.shared.outgoing_tx,
-                (self.channel_id(), delete.into_frame()),
-                responder_rx,
-                Frame::DeleteQueueOk,
-                Error::ChannelUseError
-            )?;
+            let responder_rx = self.send_request(DeleteQueueOk::header(), delete.into_frame())?;
+            let delete_ok =
+                synchronous_request!(responder_rx, Frame::DeleteQueueOk, Error::ChannelUseError)?;
             Ok(Some(delete_ok.message_count))
         }
     }
@@ -509,25 +654,20 @@ impl Channel {
     ///
     /// # Errors
     ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
     /// Returns error if any failure in comunication with server.
     pub async fn queue_unbind(&self, args: QueueUnbindArguments) -> Result<()> {
+        args.validate()?;
         let unbind = UnbindQueue::new(
             0,
-            args.queue.try_into().unwrap(),
-            args.exchange.try_into().unwrap(),
-            args.routing_key.try_into().unwrap(),
+            to_short_str("queue", args.queue)?,
+            to_short_str("exchange", args.exchange)?,
+            to_short_str("routing_key", args.routing_key)?,
             args.arguments,
         );
 
-        let responder_rx = self.register_responder(UnbindQueueOk::header()).await?;
-
-        synchronous_request!(
-            self.shared.outgoing_tx,
-            (self.channel_id(), unbind.into_frame()),
-            responder_rx,
-            Frame::UnbindQueueOk,
-            Error::ChannelUseError
-        )?;
+        let responder_rx = self.send_request(UnbindQueueOk::header(), unbind.into_frame())?;
+        synchronous_request!(responder_rx, Frame::UnbindQueueOk, Error::ChannelUseError)?;
         Ok(())
     }
 }
@@ -537,13 +677,21 @@ mod tests {
 
     use crate::{
         callbacks::{DefaultChannelCallback, DefaultConnectionCallback},
-        connection::{Connection, OpenConnectionArguments},
+        connection::{CloseReason, Connection, OpenConnectionArguments},
     };
 
+    use std::time::Duration;
+    use tokio::time::timeout;
+
     use super::{
         QueueBindArguments, QueueDeclareArguments, QueueDeleteArguments, QueuePurgeArguments,
         QueueUnbindArguments,
     };
+    use crate::api::channel::{
+        BasicGetArguments, BasicPublishArguments, HeadersBindingArguments, HeadersMatch, Overflow,
+        QueueArguments, QueueType,
+    };
+    use crate::{error::Error, test_utils::setup_logging, BasicProperties, FieldTable, FieldValue};
 
     #[tokio::test]
     async fn test_queue_apis() {
@@ -600,4 +748,241 @@ mod tests {
         channel.close().await.unwrap();
         connection.close().await.unwrap();
     }
+
+    #[tokio::test]
+    async fn test_cancelled_request() {
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+
+        // cancel open channel and queue declare before response arrives,
+        // later requests should still get their own responses.
+        let _ = timeout(Duration::ZERO, connection.open_channel(None)).await;
+        let channel = connection.open_channel(None).await.unwrap();
+
+        for _ in 0..3 {
+            let _ = timeout(
+                Duration::ZERO,
+                channel.queue_declare(QueueDeclareArguments::default()),
+            )
+            .await;
+        }
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::new("amqprs.test.cancelled"))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!("amqprs.test.cancelled", queue_name);
+        channel
+            .queue_delete(QueueDeleteArguments::new(&queue_name))
+            .await
+            .unwrap();
+
+        // cancelled close still completes the handshake
+        let _ = timeout(Duration::ZERO, channel.clone().close()).await;
+        assert_eq!(CloseReason::ClientInitiated, channel.closed().await);
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queue_declare_typed_arguments() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+
+        let queue_arguments = QueueArguments::new()
+            .queue_type(QueueType::Classic)
+            .message_ttl(Duration::from_secs(60))
+            .expires(Duration::from_secs(60))
+            .max_length(10)
+            .overflow(Overflow::RejectPublishDlx)
+            .dead_letter_exchange("amq.fanout".to_owned())
+            .max_priority(10)
+            .finish();
+        let (queue_name, ..) = channel
+            .queue_declare(
+                QueueDeclareArguments::default()
+                    .queue_arguments(queue_arguments)
+                    .finish(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        channel
+            .queue_delete(QueueDeleteArguments::new(&queue_name))
+            .await
+            .unwrap();
+
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queue_bind_headers_arguments() {
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+
+        let (queue_name, ..) = channel
+            .queue_declare(QueueDeclareArguments::exclusive_server_named())
+            .await
+            .unwrap()
+            .unwrap();
+        channel
+            .queue_bind(
+                QueueBindArguments::new(&queue_name, "amq.headers", "")
+                    .headers_arguments(
+                        HeadersBindingArguments::new(HeadersMatch::Any)
+                            .header("queue", queue_name.as_str())
+                            .header("version", 2)
+                            .finish(),
+                    )
+                    .finish(),
+            )
+            .await
+            .unwrap();
+
+        let mut headers = FieldTable::new();
+        headers.insert("queue".try_into().unwrap(), queue_name.as_str().into());
+        channel
+            .basic_publish(
+                BasicProperties::default().with_headers(headers).finish(),
+                b"matched".to_vec(),
+                BasicPublishArguments::new("amq.headers", ""),
+            )
+            .await
+            .unwrap();
+        let mut message = None;
+        for _ in 0..10 {
+            message = channel
+                .basic_get(BasicGetArguments::new(&queue_name).no_ack(true).finish())
+                .await
+                .unwrap();
+            if message.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        assert_eq!(b"matched".to_vec(), message.unwrap().2);
+
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+
+    #[test]
+    fn test_validate_arguments() {
+        assert!(QueueDeclareArguments::default().validate().is_ok());
+        assert!(QueueDeclareArguments::default()
+            .no_wait(true)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(QueueDeclareArguments::new("amq.q").validate().is_err());
+        assert!(QueueDeclareArguments::new("amq.q")
+            .passive(true)
+            .finish()
+            .validate()
+            .is_ok());
+
+        // quorum queue must be durable
+        let quorum = QueueArguments::new().queue_type(QueueType::Quorum).finish();
+        assert!(QueueDeclareArguments::new("q")
+            .queue_arguments(quorum.clone())
+            .finish()
+            .validate()
+            .is_err());
+        assert!(QueueDeclareArguments::durable_client_named("q")
+            .queue_arguments(quorum)
+            .finish()
+            .validate()
+            .is_ok());
+        // raw arguments of wrong type
+        let mut arguments = FieldTable::new();
+        arguments.insert("x-max-length".try_into().unwrap(), FieldValue::t(true));
+        assert!(QueueDeclareArguments::new("q")
+            .arguments(arguments.clone())
+            .finish()
+            .validate()
+            .is_err());
+        // ignored in passive mode
+        assert!(QueueDeclareArguments::new("q")
+            .arguments(arguments)
+            .passive(true)
+            .finish()
+            .validate()
+            .is_ok());
+
+        assert!(QueueBindArguments::new("q", "", "k").validate().is_err());
+        assert!(QueueUnbindArguments::new("q", "", "k").validate().is_err());
+        // builders never panic, the violation is returned by `validate`
+        let queue = "q".repeat(256);
+        assert!(QueuePurgeArguments::new(&queue).validate().is_err());
+        assert!(QueueDeleteArguments::new(&queue).validate().is_err());
+    }
+
+    #[cfg(feature = "compliance_assert")]
+    #[test]
+    fn test_compliance_violation_is_error() {
+        let result = QueueDeclareArguments::new("bad queue!").finish().validate();
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+        let result = QueueBindArguments::new("q", "bad exchange!", "k")
+            .finish()
+            .validate();
+        assert!(matches!(result, Err(Error::InvalidArgument(_))));
+    }
+
+    #[tokio::test]
+    async fn test_queue_declare_inequivalent_arguments() {
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let queue = "amqprs.test_queue_declare_inequivalent_arguments";
+
+        let channel = connection.open_channel(None).await.unwrap();
+        channel
+            .queue_declare(QueueDeclareArguments::durable_client_named(queue))
+            .await
+            .unwrap();
+        match channel
+            .queue_declare(QueueDeclareArguments::new(queue))
+            .await
+        {
+            Err(Error::InequivalentArguments(details)) => {
+                assert_eq!("queue", details.entity_type);
+                assert_eq!(queue, details.entity);
+                assert_eq!("durable", details.argument);
+                assert_eq!(Some("true".to_owned()), details.existing);
+                assert_eq!(Some("false".to_owned()), details.requested);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        assert!(!channel.is_open());
+
+        // fall back to passive declaration, and the channel is kept open
+        let channel = connection.open_channel(None).await.unwrap();
+        let (name, _, _) = channel
+            .queue_declare(
+                QueueDeclareArguments::new(queue)
+                    .passive_fallback(true)
+                    .finish(),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(queue, name);
+        assert!(channel.is_open());
+        assert!(QueueDeclareArguments::new(queue)
+            .passive_fallback(true)
+            .no_wait(true)
+            .finish()
+            .validate()
+            .is_err());
+
+        channel
+            .queue_delete(QueueDeleteArguments::new(queue))
+            .await
+            .unwrap();
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
 }
@@ -0,0 +1,821 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::{
+    sync::broadcast::{self, error::RecvError},
+    time,
+};
+#[cfg(feature = "traces")]
+use tracing::{debug, warn};
+
+use super::{
+    BasicPublishArguments, Channel, ConfirmSelectArguments, DeathReason, ExchangeDeclareArguments,
+    ExchangeType, QueueArguments, QueueBindArguments, QueueDeclareArguments,
+};
+use crate::{
+    api::{
+        consumer::{HandlerError, Outcome, OutcomeConsumer},
+        events::ChannelEvent,
+        validation::{check_exchange_name, check_flags, check_not_empty, check_queue_name},
+        Result,
+    },
+    BasicProperties, Deliver,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+/// Policy of retrying failed messages of a work queue with delayed backoff.
+///
+/// The retry topology, declared by [`declare_retry_topology`], consists of
+/// - a direct `retry_exchange`,
+/// - a retry queue `<queue>.retry.<n>` per delay, whose messages expire after the delay and
+///   are dead-lettered back to the work queue via the default exchange,
+/// - the `parking_queue`, where messages are kept once all attempts are used.
+///
+/// Each retry queue is bound to `retry_exchange` with its name as routing key, and so is
+/// the parking queue. A failed message is published to the retry queue of its attempt,
+/// and the attempt is counted from the expirations of retry queues in its `x-death` header.
+/// The last delay is used by the attempts beyond the number of delays.
+///
+/// Retry queues are declared with the delays as message TTL, so they must be deleted
+/// before the delays are changed, otherwise server rejects the declaration.
+///
+/// # Usage
+///
+/// ```
+/// # use std::time::Duration;
+/// # use amqprs::channel::RetryPolicy;
+/// let policy = RetryPolicy::new(
+///     "orders",
+///     vec![Duration::from_secs(1), Duration::from_secs(10), Duration::from_secs(60)],
+/// )
+/// .max_attempts(5)
+/// .finish();
+/// assert!(policy.validate().is_ok());
+/// assert_eq!("orders.parking", policy.parking_queue);
+/// ```
+///
+/// [`declare_retry_topology`]: struct.Channel.html#method.declare_retry_topology
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Work queue. Default: "".
+    pub queue: String,
+    /// Delay of each attempt, must not be empty and each must be within
+    /// 1 millisecond and `u32::MAX` milliseconds. Default: empty.
+    pub delays: Vec<Duration>,
+    /// Max number of retries before parking. Default: the number of delays.
+    pub max_attempts: u32,
+    /// Queue of messages which used all attempts. Default: "<queue>.parking".
+    pub parking_queue: String,
+    /// Exchange routing messages to retry queues and parking queue. Default: "<queue>.retry".
+    pub retry_exchange: String,
+    /// Max time to wait for server to confirm a routed message. Default: 30 seconds.
+    pub confirm_timeout: Duration,
+}
+
+/// Where a failed message goes according to [`RetryPolicy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RetryDecision {
+    /// Retry after the delay, via the retry queue.
+    Retry {
+        /// Retry queue of the attempt.
+        queue: String,
+        /// Delay before retry.
+        delay: Duration,
+        /// Attempt of retry, starting from 1.
+        attempt: u32,
+    },
+    /// Park the message in the parking queue.
+    Park {
+        /// Parking queue.
+        queue: String,
+    },
+}
+
+impl RetryPolicy {
+    /// Create new policy with defaults derived from the work queue.
+    pub fn new(queue: &str, delays: Vec<Duration>) -> Self {
+        Self {
+            queue: queue.to_owned(),
+            max_attempts: delays.len().try_into().unwrap_or(u32::MAX),
+            delays,
+            parking_queue: format!("{}.parking", queue),
+            retry_exchange: format!("{}.retry", queue),
+            confirm_timeout: Duration::from_secs(30),
+        }
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        delays, Vec<Duration>
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        max_attempts, u32
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        parking_queue, String
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        retry_exchange, String
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        confirm_timeout, Duration
+    }
+
+    /// Finish chained configuration and return new policy.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the policy, it is also done by [`declare_retry_topology`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is empty or invalid,
+    /// `delays` is empty, any delay is out of range or `confirm_timeout` is zero.
+    ///
+    /// [`declare_retry_topology`]: struct.Channel.html#method.declare_retry_topology
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)?;
+        check_not_empty("queue", &self.queue, "work queue must be named")?;
+        check_queue_name("parking_queue", &self.parking_queue)?;
+        check_not_empty(
+            "parking_queue",
+            &self.parking_queue,
+            "parking queue must be named",
+        )?;
+        check_exchange_name("retry_exchange", &self.retry_exchange)?;
+        check_not_empty(
+            "retry_exchange",
+            &self.retry_exchange,
+            "messages can not be routed by default exchange to retry queues",
+        )?;
+        check_flags(
+            !self.delays.is_empty(),
+            "delays: requires at least one delay",
+        )?;
+        check_flags(
+            self.delays
+                .iter()
+                .all(|delay| delay.as_millis() > 0 && delay.as_millis() <= u32::MAX.into()),
+            "delays: must be within 1 millisecond and u32::MAX milliseconds",
+        )?;
+        check_flags(
+            !self.confirm_timeout.is_zero(),
+            "confirm_timeout: must not be zero",
+        )?;
+        for n in 0..self.delays.len() {
+            check_queue_name("delays", &self.retry_queue(n))?;
+        }
+        Ok(())
+    }
+
+    /// Name of the retry queue of the delay at the index.
+    pub fn retry_queue(&self, index: usize) -> String {
+        format!("{}.retry.{}", self.queue, index)
+    }
+
+    /// Number of retries the message had, counted from the expirations of retry queues.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `x-death` header is malformed.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn attempts(&self, basic_properties: &BasicProperties) -> Result<u32> {
+        let retry_queues: Vec<String> = (0..self.delays.len())
+            .map(|n| self.retry_queue(n))
+            .collect();
+        let attempts: u64 = basic_properties
+            .x_death()?
+            .iter()
+            .filter(|death| death.reason == DeathReason::Expired)
+            .filter(|death| retry_queues.contains(&death.queue))
+            .map(|death| death.count)
+            .sum();
+        Ok(attempts.try_into().unwrap_or(u32::MAX))
+    }
+
+    /// Decide where the failed message goes.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `x-death` header is malformed.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn decide(&self, basic_properties: &BasicProperties) -> Result<RetryDecision> {
+        let attempts = self.attempts(basic_properties)?;
+        if attempts >= self.max_attempts || self.delays.is_empty() {
+            return Ok(RetryDecision::Park {
+                queue: self.parking_queue.clone(),
+            });
+        }
+        let index = (attempts as usize).min(self.delays.len() - 1);
+        Ok(RetryDecision::Retry {
+            queue: self.retry_queue(index),
+            delay: self.delays[index],
+            attempt: attempts + 1,
+        })
+    }
+
+    /// Publish the failed message to the retry queue or parking queue as decided,
+    /// and return the outcome of the delivery.
+    ///
+    /// The message is published as `mandatory` on `channel`, which must be in confirm mode
+    /// and must not be used by other publishers. `publish_seq` is the sequence number of
+    /// the message in confirm mode, i.e. the number of messages published on the channel since
+    /// [`confirm_select`] including this one, which is matched against the confirms of server.
+    /// A `basic.return` is taken for the message confirmed by the next ack, so a message
+    /// confirmed together with a returned one by an ack with `multiple = true` is also
+    /// taken as returned.
+    ///
+    /// Returns [`Outcome::Ack`] only once server confirms the message without returning it,
+    /// otherwise `Nack { requeue: true }` to have server redeliver the message, including when
+    /// no confirm arrives within `confirm_timeout`. So a message is never lost, but may be
+    /// duplicated. Messages are published with their original properties, so that
+    /// the `x-death` header is kept. If the `x-death` header is malformed, the message is parked.
+    ///
+    /// [`Outcome::Ack`]: ../consumer/enum.Outcome.html#variant.Ack
+    /// [`confirm_select`]: struct.Channel.html#method.confirm_select
+    pub async fn route(
+        &self,
+        channel: &Channel,
+        publish_seq: u64,
+        basic_properties: BasicProperties,
+        content: Vec<u8>,
+    ) -> Outcome {
+        let routing_key = match self.decide(&basic_properties) {
+            Ok(RetryDecision::Retry { queue, .. }) => queue,
+            Ok(RetryDecision::Park { queue }) => queue,
+            Err(_err) => {
+                #[cfg(feature = "traces")]
+                warn!("park message of malformed x-death header, cause: {}", _err);
+                self.parking_queue.clone()
+            }
+        };
+        #[cfg(feature = "traces")]
+        debug!("route failed message of {} to {}", self.queue, routing_key);
+        // subscribe before publishing to not miss the confirm
+        let mut events = channel.events();
+        if let Err(_err) = channel
+            .basic_publish(
+                basic_properties,
+                content,
+                BasicPublishArguments::new(&self.retry_exchange, &routing_key)
+                    .mandatory(true)
+                    .finish(),
+            )
+            .await
+        {
+            #[cfg(feature = "traces")]
+            warn!(
+                "failed to route message of {} to {}, requeue it, cause: {}",
+                self.queue, routing_key, _err
+            );
+            return Outcome::Nack { requeue: true };
+        }
+        match time::timeout(
+            self.confirm_timeout,
+            wait_confirm(&mut events, ConfirmMatcher::new(publish_seq)),
+        )
+        .await
+        {
+            Ok(true) => Outcome::Ack,
+            _ => {
+                #[cfg(feature = "traces")]
+                warn!(
+                    "message of {} routed to {} is not confirmed, requeue it",
+                    self.queue, routing_key
+                );
+                Outcome::Nack { requeue: true }
+            }
+        }
+    }
+}
+
+/// Match confirms of server to a published message by its sequence number.
+///
+/// Server sends `basic.return` of an unroutable mandatory message right before its `basic.ack`,
+/// so a return is taken for the message confirmed by the next ack.
+struct ConfirmMatcher {
+    publish_seq: u64,
+    returned: bool,
+}
+
+impl ConfirmMatcher {
+    fn new(publish_seq: u64) -> Self {
+        Self {
+            publish_seq,
+            returned: false,
+        }
+    }
+
+    fn is_confirmed(&self, delivery_tag: u64, multiple: bool) -> bool {
+        delivery_tag == self.publish_seq || (multiple && delivery_tag > self.publish_seq)
+    }
+
+    fn on_return(&mut self) {
+        self.returned = true;
+    }
+
+    /// Returns `Some(true)` if the message is acked and not returned,
+    /// `None` if the ack is of other messages.
+    fn on_ack(&mut self, delivery_tag: u64, multiple: bool) -> Option<bool> {
+        if self.is_confirmed(delivery_tag, multiple) {
+            return Some(!self.returned);
+        }
+        // the return is of the acked message, e.g. an earlier message confirmed late
+        self.returned = false;
+        // the message can not be confirmed after a later one
+        (delivery_tag > self.publish_seq).then(|| false)
+    }
+
+    /// Returns `Some(false)` if the message is nacked, `None` if the nack is of other messages.
+    fn on_nack(&mut self, delivery_tag: u64, multiple: bool) -> Option<bool> {
+        if self.is_confirmed(delivery_tag, multiple) || delivery_tag > self.publish_seq {
+            return Some(false);
+        }
+        self.returned = false;
+        None
+    }
+}
+
+/// Wait for the confirm of the message, returns `true` if it is acked and not returned.
+async fn wait_confirm(
+    events: &mut broadcast::Receiver<ChannelEvent>,
+    mut matcher: ConfirmMatcher,
+) -> bool {
+    loop {
+        let confirmed = match events.recv().await {
+            Ok(ChannelEvent::PublishReturned(_)) => {
+                matcher.on_return();
+                None
+            }
+            Ok(ChannelEvent::PublishAcked(ack)) => {
+                matcher.on_ack(ack.delivery_tag(), ack.mutiple())
+            }
+            Ok(ChannelEvent::PublishNacked(nack)) => {
+                matcher.on_nack(nack.delivery_tag(), nack.multiple())
+            }
+            Ok(ChannelEvent::Closed(_)) => Some(false),
+            Ok(_) => None,
+            // missed events may include the confirm
+            Err(RecvError::Lagged(_)) | Err(RecvError::Closed) => Some(false),
+        };
+        if let Some(confirmed) = confirmed {
+            return confirmed;
+        }
+    }
+}
+
+/// Handler of [`RetryConsumer`], same as [`OutcomeConsumer`] except that it borrows
+/// the message, so that the message is routed without copy if the handler fails.
+///
+/// [`OutcomeConsumer`]: ../consumer/trait.OutcomeConsumer.html
+#[async_trait]
+pub trait RetryHandler {
+    /// Handle a delivery from server, returns the outcome to acknowledge the delivery,
+    /// or an error to route the message by [`RetryPolicy`].
+    ///
+    /// See [`OutcomeConsumer::handle`].
+    ///
+    /// [`OutcomeConsumer::handle`]: ../consumer/trait.OutcomeConsumer.html#tymethod.handle
+    async fn handle(
+        &mut self,
+        channel: &Channel,
+        deliver: Deliver,
+        basic_properties: &BasicProperties,
+        content: &[u8],
+    ) -> std::result::Result<Outcome, HandlerError>;
+}
+
+/// Consumer which routes the message by [`RetryPolicy`] if the inner consumer returns error.
+///
+/// Outcomes returned by the inner handler are kept, so that it can still reject a message
+/// which should never be retried.
+///
+/// Failed messages are routed on a separate channel in confirm mode, opened on
+/// the connection of the consuming channel when first needed and reopened if closed,
+/// see [`RetryPolicy::route`]. The channel is also reopened once a message is not confirmed,
+/// because late confirms or a failed publish leave its sequence number of confirms unknown.
+///
+/// # Usage
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use amqprs::channel::{
+/// #     BasicConsumeArguments, Channel, ConsumerOptions, RetryConsumer, RetryHandler, RetryPolicy,
+/// # };
+/// # async fn run<H: RetryHandler + Send + 'static>(channel: Channel, handler: H) {
+/// let policy = RetryPolicy::new("orders", vec![Duration::from_secs(10)]);
+/// channel.declare_retry_topology(&policy).await.unwrap();
+/// channel
+///     .basic_consume_with_outcome(
+///         RetryConsumer::new(handler, policy),
+///         BasicConsumeArguments::new("orders", ""),
+///         ConsumerOptions::new(),
+///     )
+///     .await
+///     .unwrap();
+/// # }
+/// ```
+pub struct RetryConsumer<H> {
+    handler: H,
+    policy: RetryPolicy,
+    /// channel in confirm mode to route failed messages
+    publisher: Option<RetryPublisher>,
+}
+
+/// Channel in confirm mode to route failed messages.
+struct RetryPublisher {
+    channel: Channel,
+    /// sequence number of the next message in confirm mode
+    next_publish_seq: u64,
+}
+
+impl<H> RetryConsumer<H> {
+    /// Create the consumer routing failed messages of the inner handler by the policy.
+    pub fn new(handler: H, policy: RetryPolicy) -> Self {
+        Self {
+            handler,
+            policy,
+            publisher: None,
+        }
+    }
+
+    /// Get the publisher channel, open a new one if there is none or it is closed.
+    async fn publisher(&mut self, channel: &Channel) -> Result<&mut RetryPublisher> {
+        if let Some(ref publisher) = self.publisher {
+            if publisher.channel.is_open() {
+                return Ok(self.publisher.as_mut().unwrap());
+            }
+        }
+        let publisher = channel.connection.open_channel(None).await?;
+        publisher
+            .confirm_select(ConfirmSelectArguments::default())
+            .await?;
+        Ok(self.publisher.insert(RetryPublisher {
+            channel: publisher,
+            next_publish_seq: 1,
+        }))
+    }
+}
+
+#[async_trait]
+impl<H> OutcomeConsumer for RetryConsumer<H>
+where
+    H: RetryHandler + Send,
+{
+    async fn handle(
+        &mut self,
+        channel: &Channel,
+        deliver: Deliver,
+        basic_properties: BasicProperties,
+        content: Vec<u8>,
+    ) -> std::result::Result<Outcome, HandlerError> {
+        match self
+            .handler
+            .handle(channel, deliver, &basic_properties, &content)
+            .await
+        {
+            Ok(outcome) => Ok(outcome),
+            Err(_err) => {
+                #[cfg(feature = "traces")]
+                debug!("handler of {} failed, cause: {}", self.policy.queue, _err);
+                let policy = self.policy.clone();
+                match self.publisher(channel).await {
+                    Ok(publisher) => {
+                        let publish_seq = publisher.next_publish_seq;
+                        publisher.next_publish_seq += 1;
+                        let outcome = policy
+                            .route(&publisher.channel, publish_seq, basic_properties, content)
+                            .await;
+                        if outcome != Outcome::Ack {
+                            // the channel is closed at drop
+                            self.publisher.take();
+                        }
+                        Ok(outcome)
+                    }
+                    Err(_err) => {
+                        #[cfg(feature = "traces")]
+                        warn!(
+                            "failed to open channel to route message of {}, requeue it, cause: {}",
+                            policy.queue, _err
+                        );
+                        Ok(Outcome::Nack { requeue: true })
+                    }
+                }
+            }
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+/// APIs for retry with delayed backoff.
+impl Channel {
+    /// Declare the retry topology of the policy, see [`RetryPolicy`].
+    ///
+    /// The work queue is not declared, and all entities are durable.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if the policy is invalid.
+    /// Returns an error if a failure occurs while comunicating with the server,
+    /// e.g. a retry queue exists with a different delay.
+    ///
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub async fn declare_retry_topology(&self, policy: &RetryPolicy) -> Result<()> {
+        policy.validate()?;
+        self.exchange_declare(
+            ExchangeDeclareArguments::of_type(&policy.retry_exchange, ExchangeType::Direct)
+                .durable(true)
+                .finish(),
+        )
+        .await?;
+        for (n, delay) in policy.delays.iter().enumerate() {
+            let retry_queue = policy.retry_queue(n);
+            self.queue_declare(
+                QueueDeclareArguments::durable_client_named(&retry_queue)
+                    .queue_arguments(
+                        QueueArguments::new()
+                            .message_ttl(*delay)
+                            .dead_letter_exchange("".to_owned())
+                            .dead_letter_routing_key(policy.queue.clone())
+                            .finish(),
+                    )
+                    .finish(),
+            )
+            .await?;
+            self.queue_bind(QueueBindArguments::new(
+                &retry_queue,
+                &policy.retry_exchange,
+                &retry_queue,
+            ))
+            .await?;
+        }
+        self.queue_declare(QueueDeclareArguments::durable_client_named(
+            &policy.parking_queue,
+        ))
+        .await?;
+        self.queue_bind(QueueBindArguments::new(
+            &policy.parking_queue,
+            &policy.retry_exchange,
+            &policy.parking_queue,
+        ))
+        .await
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use amqp_serde::types::{FieldArray, FieldTable, FieldValue};
+    use async_trait::async_trait;
+    use tokio::sync::mpsc;
+
+    use super::{ConfirmMatcher, RetryConsumer, RetryDecision, RetryHandler, RetryPolicy};
+    use crate::{
+        api::{
+            channel::{
+                BasicConsumeArguments, BasicGetArguments, BasicPublishArguments, Channel,
+                ConsumerOptions, ExchangeDeleteArguments, QueueDeclareArguments,
+                QueueDeleteArguments,
+            },
+            connection::{Connection, OpenConnectionArguments},
+            consumer::{HandlerError, Outcome},
+        },
+        test_utils::setup_logging,
+        BasicProperties, Deliver,
+    };
+
+    fn expired(queue: &str, count: i64) -> FieldValue {
+        let mut table = FieldTable::new();
+        table.insert("queue".try_into().unwrap(), queue.into());
+        table.insert("reason".try_into().unwrap(), "expired".into());
+        table.insert("count".try_into().unwrap(), FieldValue::l(count));
+        table.insert("exchange".try_into().unwrap(), "".into());
+        table.insert(
+            "routing-keys".try_into().unwrap(),
+            FieldValue::A(FieldArray::new()),
+        );
+        table.insert("time".try_into().unwrap(), FieldValue::T(0));
+        FieldValue::F(table)
+    }
+
+    fn died(deaths: Vec<FieldValue>) -> BasicProperties {
+        let mut headers = FieldTable::new();
+        headers.insert(
+            "x-death".try_into().unwrap(),
+            FieldValue::A(FieldArray::try_from(deaths).unwrap()),
+        );
+        BasicProperties::default().with_headers(headers).finish()
+    }
+
+    #[test]
+    fn test_validate_policy() {
+        let delays = vec![Duration::from_secs(1)];
+        assert!(RetryPolicy::new("q", delays.clone()).validate().is_ok());
+        assert!(RetryPolicy::new("", delays.clone()).validate().is_err());
+        assert!(RetryPolicy::new("q", vec![]).validate().is_err());
+        assert!(RetryPolicy::new("q", vec![Duration::from_micros(10)])
+            .validate()
+            .is_err());
+        assert!(RetryPolicy::new("q", delays.clone())
+            .retry_exchange("".to_owned())
+            .finish()
+            .validate()
+            .is_err());
+        assert!(RetryPolicy::new("q", delays.clone())
+            .confirm_timeout(Duration::ZERO)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(RetryPolicy::new(&"q".repeat(250), delays)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_decide() {
+        let policy = RetryPolicy::new("q", vec![Duration::from_secs(1), Duration::from_secs(5)])
+            .max_attempts(3)
+            .finish();
+        assert_eq!(
+            RetryDecision::Retry {
+                queue: "q.retry.0".to_owned(),
+                delay: Duration::from_secs(1),
+                attempt: 1,
+            },
+            policy.decide(&BasicProperties::default()).unwrap()
+        );
+        // expirations of other queues are not counted
+        let props = died(vec![expired("q.retry.0", 1), expired("other", 5)]);
+        assert_eq!(1, policy.attempts(&props).unwrap());
+        assert_eq!(
+            RetryDecision::Retry {
+                queue: "q.retry.1".to_owned(),
+                delay: Duration::from_secs(5),
+                attempt: 2,
+            },
+            policy.decide(&props).unwrap()
+        );
+        // the last delay is repeated
+        let props = died(vec![expired("q.retry.1", 1), expired("q.retry.0", 1)]);
+        assert_eq!(
+            RetryDecision::Retry {
+                queue: "q.retry.1".to_owned(),
+                delay: Duration::from_secs(5),
+                attempt: 3,
+            },
+            policy.decide(&props).unwrap()
+        );
+        let props = died(vec![expired("q.retry.1", 2), expired("q.retry.0", 1)]);
+        assert_eq!(
+            RetryDecision::Park {
+                queue: "q.parking".to_owned()
+            },
+            policy.decide(&props).unwrap()
+        );
+    }
+
+    struct FailingConsumer(mpsc::UnboundedSender<u32>, RetryPolicy);
+
+    #[test]
+    fn test_confirm_matcher() {
+        // acks of earlier messages are skipped
+        let mut matcher = ConfirmMatcher::new(3);
+        assert_eq!(None, matcher.on_ack(1, false));
+        assert_eq!(None, matcher.on_ack(2, true));
+        assert_eq!(Some(true), matcher.on_ack(3, false));
+
+        // confirmed by a multiple ack
+        let mut matcher = ConfirmMatcher::new(3);
+        assert_eq!(Some(true), matcher.on_ack(5, true));
+        // acked after a later message
+        let mut matcher = ConfirmMatcher::new(3);
+        assert_eq!(Some(false), matcher.on_ack(4, false));
+
+        // return of an earlier message
+        let mut matcher = ConfirmMatcher::new(3);
+        matcher.on_return();
+        assert_eq!(None, matcher.on_ack(2, false));
+        assert_eq!(Some(true), matcher.on_ack(3, false));
+
+        // return of the message
+        let mut matcher = ConfirmMatcher::new(3);
+        assert_eq!(None, matcher.on_ack(2, false));
+        matcher.on_return();
+        assert_eq!(Some(false), matcher.on_ack(3, false));
+
+        // nacks
+        let mut matcher = ConfirmMatcher::new(3);
+        assert_eq!(None, matcher.on_nack(2, true));
+        assert_eq!(Some(false), matcher.on_nack(4, true));
+        let mut matcher = ConfirmMatcher::new(3);
+        assert_eq!(Some(false), matcher.on_nack(3, false));
+    }
+
+    #[async_trait]
+    impl RetryHandler for FailingConsumer {
+        async fn handle(
+            &mut self,
+            _channel: &Channel,
+            _deliver: Deliver,
+            basic_properties: &BasicProperties,
+            _content: &[u8],
+        ) -> Result<Outcome, HandlerError> {
+            self.0.send(self.1.attempts(basic_properties).unwrap()).ok();
+            Err("always fail".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_topology() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let queue = "amqprs.test_retry_topology";
+        channel
+            .queue_declare(QueueDeclareArguments::durable_client_named(queue))
+            .await
+            .unwrap();
+        let policy = RetryPolicy::new(
+            queue,
+            vec![Duration::from_millis(100), Duration::from_millis(200)],
+        );
+        channel.declare_retry_topology(&policy).await.unwrap();
+        // idempotent
+        channel.declare_retry_topology(&policy).await.unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        channel
+            .basic_consume_with_outcome(
+                RetryConsumer::new(FailingConsumer(tx, policy.clone()), policy.clone()),
+                BasicConsumeArguments::new(queue, "")
+                    .manual_ack(true)
+                    .finish(),
+                ConsumerOptions::new(),
+            )
+            .await
+            .unwrap();
+        channel
+            .basic_publish(
+                BasicProperties::default(),
+                b"job".to_vec(),
+                BasicPublishArguments::new("", queue),
+            )
+            .await
+            .unwrap();
+        for attempt in 0..3 {
+            assert_eq!(attempt, rx.recv().await.unwrap());
+        }
+
+        // parked after max attempts
+        let mut parked = None;
+        for _ in 0..50 {
+            parked = channel
+                .basic_get(
+                    BasicGetArguments::new(&policy.parking_queue)
+                        .no_ack(true)
+                        .finish(),
+                )
+                .await
+                .unwrap();
+            if parked.is_some() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let (_, props, content) = parked.unwrap();
+        assert_eq!(b"job".to_vec(), content);
+        assert_eq!(2, policy.attempts(&props).unwrap());
+
+        for n in 0..policy.delays.len() {
+            channel
+                .queue_delete(QueueDeleteArguments::new(&policy.retry_queue(n)))
+                .await
+                .unwrap();
+        }
+        for name in [queue, policy.parking_queue.as_str()] {
+            channel
+                .queue_delete(QueueDeleteArguments::new(name))
+                .await
+                .unwrap();
+        }
+        channel
+            .exchange_delete(ExchangeDeleteArguments::new(&policy.retry_exchange))
+            .await
+            .unwrap();
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+    }
+}
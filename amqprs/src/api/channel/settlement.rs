@@ -0,0 +1,607 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use tokio::time;
+
+use crate::{
+    api::{error::Error, validation::check_flags, Result},
+    frame::{Ack, Frame, Nack, Reject},
+};
+
+use super::{AckDeadline, AckDeadlines};
+
+////////////////////////////////////////////////////////////////////////////////
+/// Arguments for [`set_ack_batching`]
+///
+/// Once enabled, acks of single delivery are accumulated and sent as one ack with `multiple = true`,
+/// when all lower delivery tags of the channel are settled. Accumulated acks are flushed after
+/// `max_batch` acks are accumulated, or `interval` elapsed since the first accumulated ack,
+/// and always before [`basic_cancel`] and [`close`].
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::AckBatchingArguments;
+/// # use std::time::Duration;
+/// let x = AckBatchingArguments::new(100, Duration::from_millis(50));
+/// ```
+///
+/// [`set_ack_batching`]: struct.Channel.html#method.set_ack_batching
+/// [`basic_cancel`]: struct.Channel.html#method.basic_cancel
+/// [`close`]: struct.Channel.html#method.close
+#[derive(Debug, Clone)]
+pub struct AckBatchingArguments {
+    /// Max number of accumulated acks before flush. Default: 100.
+    pub max_batch: usize,
+    /// Max delay of an accumulated ack before flush. Default: 100 milliseconds.
+    pub interval: time::Duration,
+}
+
+impl Default for AckBatchingArguments {
+    fn default() -> Self {
+        Self {
+            max_batch: 100,
+            interval: time::Duration::from_millis(100),
+        }
+    }
+}
+
+impl AckBatchingArguments {
+    /// Create new arguments.
+    pub fn new(max_batch: usize, interval: time::Duration) -> Self {
+        Self {
+            max_batch,
+            interval,
+        }
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        max_batch, usize
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        interval, time::Duration
+    }
+
+    /// Finish chained configuration and return new arguments.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the arguments, it is also done by [`set_ack_batching`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if `max_batch` or `interval` is 0.
+    ///
+    /// [`set_ack_batching`]: struct.Channel.html#method.set_ack_batching
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn validate(&self) -> Result<()> {
+        check_flags(self.max_batch > 0, "max_batch: must be at least 1")?;
+        check_flags(!self.interval.is_zero(), "interval: must not be zero")
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Settlement of deliveries requested by user.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Settlement {
+    Ack {
+        delivery_tag: u64,
+        multiple: bool,
+    },
+    Nack {
+        delivery_tag: u64,
+        multiple: bool,
+        requeue: bool,
+    },
+    Reject {
+        delivery_tag: u64,
+        requeue: bool,
+    },
+}
+
+impl Settlement {
+    fn with_delivery_tag(self, tag: u64) -> Self {
+        match self {
+            Settlement::Ack { multiple, .. } => Settlement::Ack {
+                delivery_tag: tag,
+                multiple,
+            },
+            Settlement::Nack {
+                multiple, requeue, ..
+            } => Settlement::Nack {
+                delivery_tag: tag,
+                multiple,
+                requeue,
+            },
+            Settlement::Reject { requeue, .. } => Settlement::Reject {
+                delivery_tag: tag,
+                requeue,
+            },
+        }
+    }
+
+    pub(super) fn into_frame(self) -> Frame {
+        match self {
+            Settlement::Ack {
+                delivery_tag,
+                multiple,
+            } => Ack::new(delivery_tag, multiple).into_frame(),
+            Settlement::Nack {
+                delivery_tag,
+                multiple,
+                requeue,
+            } => {
+                let mut nack = Nack::new(delivery_tag);
+                nack.set_multiple(multiple);
+                nack.set_requeue(requeue);
+                nack.into_frame()
+            }
+            Settlement::Reject {
+                delivery_tag,
+                requeue,
+            } => Reject::new(delivery_tag, requeue).into_frame(),
+        }
+    }
+}
+
+/// Delivery not yet settled by client, see [`unsettled_deliveries`].
+///
+/// [`unsettled_deliveries`]: struct.Channel.html#method.unsettled_deliveries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsettledDelivery {
+    /// Delivery tag, scoped to the channel.
+    pub delivery_tag: u64,
+    /// Tag of the consumer which receives the delivery, `None` if received by [`basic_get`].
+    ///
+    /// [`basic_get`]: struct.Channel.html#method.basic_get
+    pub consumer_tag: Option<String>,
+    /// Time when client received the delivery.
+    pub received_at: std::time::Instant,
+}
+
+/// Track unsettled deliveries of a channel and their ack deadlines, and accumulate acks if batching is enabled.
+///
+/// It does not send frames, but returns the frames to be sent in order while it is locked.
+#[derive(Default)]
+pub(crate) struct Settlements {
+    /// `None` if ack batching is disabled.
+    batching: Option<AckBatchingArguments>,
+    /// deliveries to be settled by client
+    unsettled: BTreeMap<u64, UnsettledDelivery>,
+    /// accumulated acks not yet sent
+    pending_acks: BTreeSet<u64>,
+    /// time to flush accumulated acks
+    flush_deadline: Option<time::Instant>,
+    /// the highest delivery tag received, to tell unknown delivery tags from settled ones
+    last_delivery_tag: u64,
+    /// ack deadlines of unsettled deliveries
+    deadlines: AckDeadlines,
+}
+
+impl Settlements {
+    /// Enable ack batching.
+    pub fn enable_batching(&mut self, args: AckBatchingArguments) {
+        self.batching = Some(args);
+    }
+
+    pub fn flush_deadline(&self) -> Option<time::Instant> {
+        self.flush_deadline
+    }
+
+    /// Returns the unsettled deliveries in the order of delivery tags.
+    pub fn unsettled(&self) -> Vec<UnsettledDelivery> {
+        self.unsettled.values().cloned().collect()
+    }
+
+    /// Returns the delivery if it is not yet settled.
+    pub fn get(&self, delivery_tag: u64) -> Option<&UnsettledDelivery> {
+        self.unsettled.get(&delivery_tag)
+    }
+
+    pub fn unsettled_count(&self) -> usize {
+        self.unsettled.len()
+    }
+
+    /// Record a delivery to be settled by client.
+    pub fn deliver(&mut self, delivery_tag: u64, consumer_tag: Option<String>) {
+        self.last_delivery_tag = self.last_delivery_tag.max(delivery_tag);
+        self.unsettled.insert(
+            delivery_tag,
+            UnsettledDelivery {
+                delivery_tag,
+                consumer_tag,
+                received_at: std::time::Instant::now(),
+            },
+        );
+    }
+
+    /// Stop tracking a delivery which is never settled by client, e.g. consumed in auto ack mode.
+    pub fn forget(&mut self, delivery_tag: u64) {
+        self.remove(delivery_tag);
+    }
+
+    /// Stop tracking all deliveries, e.g. they are redelivered by server with new delivery tags.
+    pub fn forget_all(&mut self) {
+        self.unsettled.clear();
+        self.deadlines.clear();
+    }
+
+    /// Schedule the ack deadline of a delivery received at `received_at`.
+    pub fn schedule_deadline(
+        &mut self,
+        delivery_tag: u64,
+        received_at: time::Instant,
+        deadline: AckDeadline,
+    ) {
+        self.deadlines.schedule(delivery_tag, received_at, deadline);
+    }
+
+    /// Returns the time of the earliest warning or expiry of ack deadlines.
+    pub fn next_deadline_due(&self) -> Option<time::Instant> {
+        self.deadlines.next_due()
+    }
+
+    /// Take the earliest warning or expiry of ack deadlines due by `now`,
+    /// returns the delivery tag, `true` if expired, and the deadline.
+    pub fn pop_due_deadline(&mut self, now: time::Instant) -> Option<(u64, bool, AckDeadline)> {
+        self.deadlines.pop_due(now)
+    }
+
+    fn remove(&mut self, delivery_tag: u64) {
+        self.unsettled.remove(&delivery_tag);
+        self.deadlines.remove(delivery_tag);
+    }
+
+    /// Settle deliveries, returns the frames to be sent.
+    ///
+    /// A delivery tag `0` with `multiple = true` settles all unsettled deliveries.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::DeliveryTagError`] if the delivery tag is unknown or already settled,
+    /// which server would close the channel for.
+    ///
+    /// [`Error::DeliveryTagError`]: ../error/enum.Error.html#variant.DeliveryTagError
+    pub fn settle(&mut self, settlement: Settlement) -> Result<Vec<Frame>> {
+        let settlement = match self.resolve(settlement)? {
+            Some(settlement) => settlement,
+            None => return Ok(vec![]),
+        };
+        let frames = match settlement {
+            Settlement::Ack {
+                delivery_tag,
+                multiple: false,
+            } => {
+                self.remove(delivery_tag);
+                let batching = match self.batching {
+                    Some(ref batching) => batching,
+                    None => return Ok(vec![settlement.into_frame()]),
+                };
+                if self.flush_deadline.is_none() {
+                    self.flush_deadline = Some(time::Instant::now() + batching.interval);
+                }
+                let max_batch = batching.max_batch;
+                self.pending_acks.insert(delivery_tag);
+                if self.pending_acks.len() >= max_batch {
+                    self.flush(false)
+                } else {
+                    vec![]
+                }
+            }
+            Settlement::Ack {
+                delivery_tag,
+                multiple: true,
+            } => {
+                // covers the accumulated acks up to the tag
+                self.settle_up_to(delivery_tag);
+                self.pending_acks = self.pending_acks.split_off(&(delivery_tag + 1));
+                vec![settlement.into_frame()]
+            }
+            Settlement::Nack {
+                delivery_tag,
+                multiple: true,
+                ..
+            } => {
+                // accumulated acks must not be nacked
+                let mut frames = self.take_acks(|tag| tag <= delivery_tag);
+                self.settle_up_to(delivery_tag);
+                frames.push(settlement.into_frame());
+                frames
+            }
+            Settlement::Nack { delivery_tag, .. } | Settlement::Reject { delivery_tag, .. } => {
+                self.remove(delivery_tag);
+                vec![settlement.into_frame()]
+            }
+        };
+        Ok(frames)
+    }
+
+    /// Check the delivery tag of the settlement, returns `None` if nothing to settle.
+    ///
+    /// The delivery tag `0` with `multiple = true` is replaced by the highest unsettled one,
+    /// so deliveries on the way from server are not settled unexpectedly.
+    fn resolve(&self, settlement: Settlement) -> Result<Option<Settlement>> {
+        let (delivery_tag, multiple) = match settlement {
+            Settlement::Ack {
+                delivery_tag,
+                multiple,
+            }
+            | Settlement::Nack {
+                delivery_tag,
+                multiple,
+                ..
+            } => (delivery_tag, multiple),
+            Settlement::Reject { delivery_tag, .. } => (delivery_tag, false),
+        };
+        if multiple && delivery_tag == 0 {
+            return Ok(self
+                .unsettled
+                .keys()
+                .next_back()
+                .map(|&highest| settlement.with_delivery_tag(highest)));
+        }
+        if self.unsettled.contains_key(&delivery_tag) {
+            Ok(Some(settlement))
+        } else if delivery_tag == 0 || delivery_tag > self.last_delivery_tag {
+            Err(Error::DeliveryTagError(format!(
+                "unknown delivery tag {}",
+                delivery_tag
+            )))
+        } else {
+            Err(Error::DeliveryTagError(format!(
+                "delivery tag {} is already settled",
+                delivery_tag
+            )))
+        }
+    }
+
+    /// Flush accumulated acks, returns the frames to be sent.
+    ///
+    /// Acks below the lowest unsettled delivery tag are coalesced into one ack with `multiple = true`.
+    /// If `force` is `true`, the others are sent one by one, otherwise they are kept accumulated.
+    pub fn flush(&mut self, force: bool) -> Vec<Frame> {
+        let frames = match self.unsettled.keys().next().copied() {
+            Some(lowest) if !force => self.take_acks(|tag| tag < lowest),
+            _ => self.take_acks(|_| true),
+        };
+        if self.pending_acks.is_empty() {
+            self.flush_deadline = None;
+        }
+        frames
+    }
+
+    /// Take the accumulated acks of the lowest tags matching `predicate`, returns the frames.
+    ///
+    /// Acks below the lowest unsettled delivery tag are coalesced, the others are sent one by one,
+    /// so an ack with `multiple = true` never covers any unsettled delivery.
+    fn take_acks(&mut self, predicate: impl Fn(u64) -> bool) -> Vec<Frame> {
+        let taken: Vec<u64> = self
+            .pending_acks
+            .iter()
+            .copied()
+            .take_while(|&tag| predicate(tag))
+            .collect();
+        for tag in &taken {
+            self.pending_acks.remove(tag);
+        }
+        let lowest = self.unsettled.keys().next().copied().unwrap_or(u64::MAX);
+        let coalesced = taken.iter().take_while(|&&tag| tag < lowest).count();
+
+        let mut frames = vec![];
+        if coalesced > 0 {
+            frames.push(Ack::new(taken[coalesced - 1], coalesced > 1).into_frame());
+        }
+        frames.extend(
+            taken[coalesced..]
+                .iter()
+                .map(|&tag| Ack::new(tag, false).into_frame()),
+        );
+        frames
+    }
+
+    fn settle_up_to(&mut self, delivery_tag: u64) {
+        let unsettled = self.unsettled.split_off(&(delivery_tag + 1));
+        let settled = std::mem::replace(&mut self.unsettled, unsettled);
+        for delivery_tag in settled.keys() {
+            self.deadlines.remove(*delivery_tag);
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use super::{AckBatchingArguments, Settlement, Settlements};
+    use crate::{api::channel::AckDeadline, api::error::Error, frame::Frame};
+    use tokio::time::{self, Duration};
+
+    /// Returns (delivery tag, multiple) of ack frames, or `None` for other frames.
+    fn acks(frames: Vec<Frame>) -> Vec<Option<(u64, bool)>> {
+        frames
+            .into_iter()
+            .map(|frame| match frame {
+                Frame::Ack(_, ack) => Some((ack.delivery_tag(), ack.mutiple())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn ack(delivery_tag: u64) -> Settlement {
+        Settlement::Ack {
+            delivery_tag,
+            multiple: false,
+        }
+    }
+
+    #[test]
+    fn test_ack_batching() {
+        let mut settlements = Settlements::default();
+        settlements.enable_batching(AckBatchingArguments::new(3, Duration::from_secs(1)));
+        for tag in 1..=5 {
+            settlements.deliver(tag, Some("ctag".to_owned()));
+        }
+        // out of order completion, 1 is still unsettled
+        assert!(settlements.settle(ack(2)).unwrap().is_empty());
+        assert!(settlements.flush_deadline().is_some());
+        assert!(settlements.settle(ack(3)).unwrap().is_empty());
+        // batch is full, but nothing is below the lowest unsettled
+        assert!(acks(settlements.settle(ack(4)).unwrap()).is_empty());
+
+        // rejected delivery is settled
+        let frames = settlements
+            .settle(Settlement::Reject {
+                delivery_tag: 1,
+                requeue: false,
+            })
+            .unwrap();
+        assert_eq!(vec![None], acks(frames));
+        assert_eq!(vec![Some((4, true))], acks(settlements.flush(false)));
+        assert!(settlements.flush_deadline().is_none());
+
+        // forced flush sends accumulated acks above unsettled one by one
+        settlements.deliver(6, None);
+        assert!(settlements.settle(ack(6)).unwrap().is_empty());
+        assert_eq!(vec![Some((6, false))], acks(settlements.flush(true)));
+        assert!(settlements.flush(true).is_empty());
+    }
+
+    #[test]
+    fn test_nack_multiple_flushes_acks() {
+        let mut settlements = Settlements::default();
+        settlements.enable_batching(AckBatchingArguments::default());
+        for tag in 1..=4 {
+            settlements.deliver(tag, None);
+        }
+        assert!(settlements.settle(ack(1)).unwrap().is_empty());
+        assert!(settlements.settle(ack(2)).unwrap().is_empty());
+        let frames = settlements
+            .settle(Settlement::Nack {
+                delivery_tag: 3,
+                multiple: true,
+                requeue: true,
+            })
+            .unwrap();
+        assert_eq!(vec![Some((2, true)), None], acks(frames));
+        // only 4 is unsettled
+        assert!(settlements.settle(ack(4)).unwrap().is_empty());
+        assert_eq!(vec![Some((4, false))], acks(settlements.flush(false)));
+
+        // coalesced ack never covers the unsettled delivery to be nacked
+        for tag in 5..=7 {
+            settlements.deliver(tag, None);
+        }
+        assert!(settlements.settle(ack(6)).unwrap().is_empty());
+        let frames = settlements
+            .settle(Settlement::Nack {
+                delivery_tag: 7,
+                multiple: true,
+                requeue: true,
+            })
+            .unwrap();
+        assert_eq!(vec![Some((6, false)), None], acks(frames));
+    }
+
+    #[test]
+    fn test_unsettled_deliveries() {
+        let mut settlements = Settlements::default();
+        settlements.deliver(1, Some("ctag".to_owned()));
+        settlements.deliver(2, None);
+        settlements.deliver(3, Some("ctag".to_owned()));
+        let unsettled = settlements.unsettled();
+        assert_eq!(
+            vec![1, 2, 3],
+            unsettled.iter().map(|d| d.delivery_tag).collect::<Vec<_>>()
+        );
+        assert_eq!(None, unsettled[1].consumer_tag);
+
+        // sent immediately without batching
+        assert_eq!(
+            vec![Some((2, false))],
+            acks(settlements.settle(ack(2)).unwrap())
+        );
+        // already settled or unknown
+        assert!(matches!(
+            settlements.settle(ack(2)),
+            Err(Error::DeliveryTagError(_))
+        ));
+        assert!(matches!(
+            settlements.settle(Settlement::Reject {
+                delivery_tag: 10,
+                requeue: true
+            }),
+            Err(Error::DeliveryTagError(_))
+        ));
+        assert!(settlements.settle(ack(0)).is_err());
+
+        // tag 0 with multiple settles all unsettled, up to the highest one
+        let frames = settlements
+            .settle(Settlement::Nack {
+                delivery_tag: 0,
+                multiple: true,
+                requeue: true,
+            })
+            .unwrap();
+        match frames.as_slice() {
+            [Frame::Nack(_, nack)] => assert_eq!(3, nack.delivery_tag()),
+            _ => panic!("expect a nack"),
+        }
+        assert!(settlements.unsettled().is_empty());
+        // nothing to settle
+        let frames = settlements
+            .settle(Settlement::Ack {
+                delivery_tag: 0,
+                multiple: true,
+            })
+            .unwrap();
+        assert!(frames.is_empty());
+    }
+
+    #[test]
+    fn test_settle_removes_deadlines() {
+        let mut settlements = Settlements::default();
+        let now = time::Instant::now();
+        let deadline = AckDeadline::new(Duration::from_secs(10));
+        for tag in 1..=5 {
+            settlements.deliver(tag, Some("ctag".to_owned()));
+            settlements.schedule_deadline(tag, now, deadline);
+        }
+        settlements.settle(ack(1)).unwrap();
+        settlements
+            .settle(Settlement::Reject {
+                delivery_tag: 2,
+                requeue: false,
+            })
+            .unwrap();
+        settlements
+            .settle(Settlement::Ack {
+                delivery_tag: 4,
+                multiple: true,
+            })
+            .unwrap();
+        settlements.forget(5);
+        settlements.deliver(6, None);
+        settlements.schedule_deadline(6, now, deadline);
+
+        // only the events of the unsettled delivery are left
+        let mut events = vec![];
+        while let Some((tag, expired, _)) = settlements.pop_due_deadline(now + deadline.timeout) {
+            events.push((tag, expired));
+        }
+        assert_eq!(vec![(6, false), (6, true)], events);
+        assert!(settlements.next_deadline_due().is_none());
+    }
+
+    #[test]
+    fn test_validate_arguments() {
+        assert!(AckBatchingArguments::default().validate().is_ok());
+        assert!(AckBatchingArguments::new(0, Duration::from_secs(1))
+            .validate()
+            .is_err());
+        assert!(AckBatchingArguments::new(1, Duration::ZERO)
+            .validate()
+            .is_err());
+    }
+}
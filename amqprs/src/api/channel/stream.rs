@@ -0,0 +1,721 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    fs::{self, File},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use amqp_serde::types::{FieldArray, FieldValue, LongLongInt};
+use async_trait::async_trait;
+use tokio::runtime::Handle;
+#[cfg(feature = "traces")]
+use tracing::error;
+
+use super::{arguments::insert, BasicConsumeArguments, BasicQosArguments, Channel};
+use crate::{
+    api::{
+        consumer::AsyncConsumer,
+        validation::{check_flags, check_not_empty, check_queue_name, check_short_str},
+        FieldTable, Result,
+    },
+    BasicProperties, Deliver,
+};
+
+const X_STREAM_OFFSET: &str = "x-stream-offset";
+const X_STREAM_FILTER: &str = "x-stream-filter";
+const X_STREAM_MATCH_UNFILTERED: &str = "x-stream-match-unfiltered";
+
+/// Default prefetch count of stream consumer.
+const DEFAULT_STREAM_PREFETCH_COUNT: u16 = 100;
+
+////////////////////////////////////////////////////////////////////////////////
+/// Where a stream consumer starts to read the stream, see [Consuming](https://www.rabbitmq.com/streams.html#consuming).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StreamOffset {
+    /// The first message available in the stream.
+    First,
+    /// The last chunk of messages written to the stream.
+    Last,
+    /// The next message written to the stream after the consumer starts, it is the default of server.
+    Next,
+    /// The message of the offset.
+    Offset(u64),
+    /// The messages written since the time, in seconds precision.
+    Timestamp(SystemTime),
+    /// The messages written within the interval before now, in seconds precision.
+    Interval(Duration),
+}
+
+impl From<StreamOffset> for FieldValue {
+    fn from(offset: StreamOffset) -> Self {
+        match offset {
+            StreamOffset::First => "first".into(),
+            StreamOffset::Last => "last".into(),
+            StreamOffset::Next => "next".into(),
+            StreamOffset::Offset(v) => {
+                FieldValue::l(LongLongInt::try_from(v).unwrap_or(LongLongInt::MAX))
+            }
+            StreamOffset::Timestamp(v) => FieldValue::T(
+                v.duration_since(UNIX_EPOCH)
+                    .map(|since_epoch| since_epoch.as_secs())
+                    .unwrap_or(0),
+            ),
+            StreamOffset::Interval(v) => format!("{}s", v.as_secs()).into(),
+        }
+    }
+}
+
+/// Store of the last processed offset of stream consumers, used by [`basic_consume_stream`]
+/// to resume a restarted consumer.
+///
+/// Consumers are identified by the stream and the consumer tag. Failures are expected to be
+/// handled by the implementation, e.g. logged, since a consumer can always start from
+/// the offset of its arguments.
+///
+/// [`basic_consume_stream`]: struct.Channel.html#method.basic_consume_stream
+pub trait OffsetStore: Send + Sync {
+    /// Returns the last processed offset of the consumer, `None` if not found.
+    fn load(&self, stream: &str, consumer_tag: &str) -> Option<u64>;
+
+    /// Store the last processed offset of the consumer.
+    ///
+    /// It is invoked once a delivery is processed, in the consumer task,
+    /// so it should not block, e.g. hand over the offset to be written in background.
+    fn store(&self, stream: &str, consumer_tag: &str, offset: u64);
+}
+
+/// [`OffsetStore`] which keeps the offset of each consumer in a file of the directory.
+///
+/// Offsets are written by a blocking task in background, and the offsets stored while
+/// a write is in progress are coalesced, so only the latest offset of a consumer is written next.
+/// Each file is written to a temporary file, which is synced to disk before being renamed
+/// over the file, so an offset is never partially written. The last offsets may still be lost
+/// if the process exits before they are written, then the consumer resumes from an earlier
+/// offset and receives some deliveries again.
+#[derive(Debug, Clone)]
+pub struct FileOffsetStore {
+    dir: PathBuf,
+    /// offsets not yet written, shared with the writer
+    pending: Arc<Mutex<PendingOffsets>>,
+}
+
+#[derive(Debug, Default)]
+struct PendingOffsets {
+    offsets: HashMap<PathBuf, u64>,
+    /// `true` if a writer is running
+    writing: bool,
+}
+
+impl FileOffsetStore {
+    /// Create the store in the directory, it is created if not exists.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the directory can not be created.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            pending: Arc::new(Mutex::new(PendingOffsets::default())),
+        })
+    }
+
+    /// File name of a consumer, names are escaped so that any pair maps to a distinct file.
+    fn path(&self, stream: &str, consumer_tag: &str) -> PathBuf {
+        fn escape(name: &str) -> String {
+            name.bytes()
+                .map(|b| match b {
+                    b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                        (b as char).to_string()
+                    }
+                    _ => format!("%{:02X}", b),
+                })
+                .collect()
+        }
+        self.dir.join(format!(
+            "{}@{}.offset",
+            escape(stream),
+            escape(consumer_tag)
+        ))
+    }
+
+    /// Write pending offsets until there is none.
+    fn write_pending(dir: &Path, pending: &Mutex<PendingOffsets>) {
+        loop {
+            let offsets: Vec<(PathBuf, u64)> = {
+                let mut pending = pending.lock().unwrap();
+                if pending.offsets.is_empty() {
+                    pending.writing = false;
+                    return;
+                }
+                pending
+                    .offsets
+                    .iter()
+                    .map(|(path, offset)| (path.clone(), *offset))
+                    .collect()
+            };
+            for (path, offset) in offsets {
+                if let Err(_err) = write_offset(dir, &path, offset) {
+                    #[cfg(feature = "traces")]
+                    error!(
+                        "failed to store offset {} to {}, cause: {}",
+                        offset,
+                        path.display(),
+                        _err
+                    );
+                }
+                // keep the offset stored meanwhile
+                let mut pending = pending.lock().unwrap();
+                if pending.offsets.get(&path) == Some(&offset) {
+                    pending.offsets.remove(&path);
+                }
+            }
+        }
+    }
+}
+
+/// Write the offset to a synced temporary file, and rename it over the file.
+fn write_offset(dir: &Path, path: &Path, offset: u64) -> io::Result<()> {
+    let tmp = path.with_extension("offset.tmp");
+    let mut file = File::create(&tmp)?;
+    file.write_all(offset.to_string().as_bytes())?;
+    file.sync_all()?;
+    fs::rename(&tmp, path)?;
+    // the rename is durable once the directory is synced, which is not supported on windows
+    #[cfg(unix)]
+    File::open(dir)?.sync_all()?;
+    #[cfg(not(unix))]
+    let _ = dir;
+    Ok(())
+}
+
+impl OffsetStore for FileOffsetStore {
+    fn load(&self, stream: &str, consumer_tag: &str) -> Option<u64> {
+        let path = self.path(stream, consumer_tag);
+        if let Some(offset) = self.pending.lock().unwrap().offsets.get(&path) {
+            return Some(*offset);
+        }
+        let content = fs::read_to_string(path).ok()?;
+        content.trim().parse().ok()
+    }
+
+    fn store(&self, stream: &str, consumer_tag: &str, offset: u64) {
+        {
+            let mut pending = self.pending.lock().unwrap();
+            pending
+                .offsets
+                .insert(self.path(stream, consumer_tag), offset);
+            if pending.writing {
+                return;
+            }
+            pending.writing = true;
+        }
+        let dir = self.dir.clone();
+        let pending = self.pending.clone();
+        match Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn_blocking(move || Self::write_pending(&dir, &pending));
+            }
+            // not in runtime, write in place
+            Err(_) => Self::write_pending(&dir, &pending),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Arguments for [`basic_consume_stream`].
+///
+/// Stream consumers must acknowledge deliveries, and server requires a prefetch count for them,
+/// which is set by [`basic_consume_stream`] before consuming.
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::{StreamConsumeArguments, StreamOffset};
+/// let args = StreamConsumeArguments::new("events", "reporting")
+///     .offset(StreamOffset::First)
+///     .filters(vec!["eu".to_owned()])
+///     .prefetch_count(500)
+///     .finish();
+/// assert!(args.validate().is_ok());
+/// ```
+///
+/// [`basic_consume_stream`]: struct.Channel.html#method.basic_consume_stream
+#[derive(Clone)]
+pub struct StreamConsumeArguments {
+    /// Stream name. Default: "".
+    pub queue: String,
+    /// Consumer tag, required by `offset_store`. Default: "" (server-generated).
+    pub consumer_tag: String,
+    /// `x-stream-offset`. Default: [`StreamOffset::Next`].
+    pub offset: StreamOffset,
+    /// `x-stream-filter`, only messages published with any of the filter values are delivered,
+    /// see [`BasicProperties::with_stream_filter_value`]. Default: empty, no filtering.
+    ///
+    /// Filtering is done per chunk by server, so the consumer may still receive messages
+    /// of other filter values.
+    ///
+    /// [`BasicProperties::with_stream_filter_value`]: ../struct.BasicProperties.html#method.with_stream_filter_value
+    pub filters: Vec<String>,
+    /// `x-stream-match-unfiltered`, also deliver messages published without filter value,
+    /// requires `filters`. Default: `false`.
+    pub match_unfiltered: bool,
+    /// Prefetch count of the consumer, must be positive. Default: 100.
+    pub prefetch_count: u16,
+    /// Default: `false`.
+    pub exclusive: bool,
+    /// Other arguments. Default: empty table.
+    pub arguments: FieldTable,
+    /// Store of the last processed offset, which overrides `offset` if the consumer has stored one.
+    /// Default: `None`.
+    pub offset_store: Option<Arc<dyn OffsetStore>>,
+}
+
+impl fmt::Debug for StreamConsumeArguments {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamConsumeArguments")
+            .field("queue", &self.queue)
+            .field("consumer_tag", &self.consumer_tag)
+            .field("offset", &self.offset)
+            .field("filters", &self.filters)
+            .field("match_unfiltered", &self.match_unfiltered)
+            .field("prefetch_count", &self.prefetch_count)
+            .field("exclusive", &self.exclusive)
+            .field("arguments", &self.arguments)
+            .field("offset_store", &self.offset_store.is_some())
+            .finish()
+    }
+}
+
+impl StreamConsumeArguments {
+    /// Create new arguments with defaults.
+    pub fn new(queue: &str, consumer_tag: &str) -> Self {
+        Self {
+            queue: queue.to_owned(),
+            consumer_tag: consumer_tag.to_owned(),
+            offset: StreamOffset::Next,
+            filters: vec![],
+            match_unfiltered: false,
+            prefetch_count: DEFAULT_STREAM_PREFETCH_COUNT,
+            exclusive: false,
+            arguments: FieldTable::new(),
+            offset_store: None,
+        }
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        queue, String
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        consumer_tag, String
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        offset, StreamOffset
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        filters, Vec<String>
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        match_unfiltered, bool
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        prefetch_count, u16
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        exclusive, bool
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        arguments, FieldTable
+    }
+    impl_chainable_option_setter! {
+        /// Chainable setter method.
+        offset_store, Arc<dyn OffsetStore>
+    }
+
+    /// Finish chained configuration and return new arguments.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the arguments, it is also done by [`basic_consume_stream`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is longer than 255 bytes,
+    /// `prefetch_count` is zero, `match_unfiltered` is set without `filters`,
+    /// or consumer tag is server-generated with `offset_store`, because the consumer
+    /// could not be identified once restarted.
+    ///
+    /// [`basic_consume_stream`]: struct.Channel.html#method.basic_consume_stream
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn validate(&self) -> Result<()> {
+        check_queue_name("queue", &self.queue)?;
+        check_not_empty("queue", &self.queue, "stream must be named")?;
+        check_short_str("consumer_tag", &self.consumer_tag)?;
+        check_flags(
+            self.prefetch_count > 0,
+            "prefetch_count: stream consumer requires a positive prefetch count",
+        )?;
+        check_flags(
+            self.filters.is_empty() || !self.filters.iter().any(|filter| filter.is_empty()),
+            "filters: filter value must not be empty",
+        )?;
+        check_flags(
+            !self.match_unfiltered || !self.filters.is_empty(),
+            "match_unfiltered: requires filters",
+        )?;
+        check_flags(
+            self.offset_store.is_none() || !self.consumer_tag.is_empty(),
+            "offset_store: requires a client-named consumer tag",
+        )
+    }
+
+    /// Arguments of `basic.consume` starting from the offset.
+    fn consume_arguments(&self, offset: StreamOffset) -> BasicConsumeArguments {
+        let mut arguments = self.arguments.clone();
+        insert(&mut arguments, X_STREAM_OFFSET, offset.into());
+        match self.filters.len() {
+            0 => {}
+            1 => insert(
+                &mut arguments,
+                X_STREAM_FILTER,
+                self.filters[0].as_str().into(),
+            ),
+            _ => {
+                let filters: Vec<FieldValue> = self
+                    .filters
+                    .iter()
+                    .map(|filter| filter.as_str().into())
+                    .collect();
+                // the number of filters never exceeds the limit of array length
+                if let Ok(filters) = FieldArray::try_from(filters) {
+                    insert(&mut arguments, X_STREAM_FILTER, FieldValue::A(filters));
+                }
+            }
+        }
+        if self.match_unfiltered {
+            insert(&mut arguments, X_STREAM_MATCH_UNFILTERED, true.into());
+        }
+        BasicConsumeArguments::new(&self.queue, &self.consumer_tag)
+            .manual_ack(true)
+            .exclusive(self.exclusive)
+            .arguments(arguments)
+            .finish()
+    }
+}
+
+/// Consumer which stores the offset of each delivery once processed by the inner consumer.
+struct OffsetStoringConsumer<F> {
+    consumer: F,
+    stream: String,
+    offset_store: Arc<dyn OffsetStore>,
+}
+
+#[async_trait]
+impl<F> AsyncConsumer for OffsetStoringConsumer<F>
+where
+    F: AsyncConsumer + Send,
+{
+    async fn consume(
+        &mut self,
+        channel: &Channel,
+        deliver: Deliver,
+        basic_properties: BasicProperties,
+        content: Vec<u8>,
+    ) {
+        let offset = basic_properties.stream_offset();
+        let consumer_tag = deliver.consumer_tag().clone();
+        self.consumer
+            .consume(channel, deliver, basic_properties, content)
+            .await;
+        if let Some(offset) = offset {
+            self.offset_store.store(&self.stream, &consumer_tag, offset);
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+/// APIs for RabbitMQ streams.
+impl Channel {
+    /// Consume a stream by the consumer, see [Streams](https://www.rabbitmq.com/streams.html).
+    ///
+    /// It sets the prefetch count of `args` for consumers of the channel by [`basic_qos`], and
+    /// starts consuming in manual ack mode from the offset of `args`, or the one after the last
+    /// processed offset if `offset_store` has it. The consumer must acknowledge each delivery,
+    /// and the offset of a delivery is available by [`BasicProperties::stream_offset`].
+    ///
+    /// Returns the consumer tag on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if arguments are invalid, see `validate` of the arguments.
+    /// Returns an error if a failure occurs while comunicating with the server.
+    ///
+    /// [`basic_qos`]: struct.Channel.html#method.basic_qos
+    /// [`BasicProperties::stream_offset`]: ../struct.BasicProperties.html#method.stream_offset
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub async fn basic_consume_stream<F>(
+        &self,
+        consumer: F,
+        args: StreamConsumeArguments,
+    ) -> Result<String>
+    where
+        F: AsyncConsumer + Send + 'static,
+    {
+        args.validate()?;
+        let offset = args
+            .offset_store
+            .as_ref()
+            .and_then(|store| store.load(&args.queue, &args.consumer_tag))
+            .map(|offset| StreamOffset::Offset(offset.saturating_add(1)))
+            .unwrap_or(args.offset);
+        self.basic_qos(BasicQosArguments::new(0, args.prefetch_count, false))
+            .await?;
+        let consume_args = args.consume_arguments(offset);
+        match args.offset_store {
+            Some(offset_store) => {
+                let consumer = OffsetStoringConsumer {
+                    consumer,
+                    stream: args.queue,
+                    offset_store,
+                };
+                self.basic_consume(consumer, consume_args).await
+            }
+            None => self.basic_consume(consumer, consume_args).await,
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use amqp_serde::types::FieldValue;
+    use async_trait::async_trait;
+    use tokio::sync::mpsc;
+
+    use super::{FileOffsetStore, OffsetStore, StreamConsumeArguments, StreamOffset};
+    use crate::{
+        api::{
+            channel::{
+                BasicAckArguments, BasicPublishArguments, Channel, QueueArguments,
+                QueueDeclareArguments, QueueDeleteArguments, QueueType,
+            },
+            connection::{Connection, OpenConnectionArguments},
+            consumer::AsyncConsumer,
+        },
+        test_utils::setup_logging,
+        BasicProperties, Deliver,
+    };
+
+    /// Unique directory of a test, so that concurrent runs do not share it.
+    fn test_dir(name: &str) -> PathBuf {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        std::env::temp_dir().join(format!("amqprs_{}_{}_{}", name, std::process::id(), nanos))
+    }
+
+    #[test]
+    fn test_stream_offset() {
+        assert_eq!(FieldValue::from("first"), StreamOffset::First.into());
+        assert_eq!(FieldValue::l(42), StreamOffset::Offset(42).into());
+        assert_eq!(
+            FieldValue::T(1_000),
+            StreamOffset::Timestamp(UNIX_EPOCH + Duration::from_millis(1_000_500)).into()
+        );
+        assert_eq!(
+            FieldValue::from("3600s"),
+            StreamOffset::Interval(Duration::from_secs(3600)).into()
+        );
+    }
+
+    #[test]
+    fn test_validate_arguments() {
+        assert!(StreamConsumeArguments::new("s", "").validate().is_ok());
+        assert!(StreamConsumeArguments::new("", "").validate().is_err());
+        assert!(StreamConsumeArguments::new("s", "")
+            .prefetch_count(0)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(StreamConsumeArguments::new("s", "")
+            .match_unfiltered(true)
+            .finish()
+            .validate()
+            .is_err());
+        assert!(StreamConsumeArguments::new("s", "")
+            .filters(vec!["".to_owned()])
+            .finish()
+            .validate()
+            .is_err());
+
+        let dir = test_dir("test_validate_stream_arguments");
+        let store: Arc<dyn OffsetStore> = Arc::new(FileOffsetStore::new(&dir).unwrap());
+        assert!(StreamConsumeArguments::new("s", "")
+            .offset_store(store.clone())
+            .finish()
+            .validate()
+            .is_err());
+        assert!(StreamConsumeArguments::new("s", "c")
+            .offset_store(store)
+            .finish()
+            .validate()
+            .is_ok());
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_file_offset_store() {
+        let dir = test_dir("test_file_offset_store");
+        let store = FileOffsetStore::new(&dir).unwrap();
+        assert_eq!(None, store.load("s", "c"));
+        store.store("s", "c", 10);
+        store.store("s", "c", 11);
+        assert_eq!(Some(11), store.load("s", "c"));
+        // escaped names never collide
+        store.store("s/c", "", 1);
+        store.store("s@c", "", 2);
+        assert_eq!(Some(1), store.load("s/c", ""));
+        assert_eq!(Some(2), store.load("s@c", ""));
+        assert_eq!(Some(11), store.load("s", "c"));
+        // written to files
+        let reopened = FileOffsetStore::new(&dir).unwrap();
+        assert_eq!(Some(11), reopened.load("s", "c"));
+        assert_eq!(Some(2), reopened.load("s@c", ""));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[tokio::test]
+    async fn test_file_offset_store_in_background() {
+        let dir = test_dir("test_file_offset_store_in_background");
+        let store = FileOffsetStore::new(&dir).unwrap();
+        for offset in 0..100 {
+            store.store("s", "c", offset);
+        }
+        assert_eq!(Some(99), store.load("s", "c"));
+        let reopened = FileOffsetStore::new(&dir).unwrap();
+        let mut loaded = None;
+        for _ in 0..50 {
+            loaded = reopened.load("s", "c");
+            if loaded == Some(99) {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(Some(99), loaded);
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    struct OffsetConsumer(mpsc::UnboundedSender<u64>);
+
+    #[async_trait]
+    impl AsyncConsumer for OffsetConsumer {
+        async fn consume(
+            &mut self,
+            channel: &Channel,
+            deliver: Deliver,
+            basic_properties: BasicProperties,
+            _content: Vec<u8>,
+        ) {
+            channel
+                .basic_ack(BasicAckArguments::new(deliver.delivery_tag(), false))
+                .await
+                .unwrap();
+            self.0.send(basic_properties.stream_offset().unwrap()).ok();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_basic_consume_stream() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let stream = "amqprs.test_basic_consume_stream";
+        channel
+            .queue_declare(
+                QueueDeclareArguments::durable_client_named(stream)
+                    .queue_arguments(QueueArguments::new().queue_type(QueueType::Stream).finish())
+                    .finish(),
+            )
+            .await
+            .unwrap();
+        for _ in 0..3 {
+            channel
+                .basic_publish(
+                    BasicProperties::default(),
+                    b"event".to_vec(),
+                    BasicPublishArguments::new("", stream),
+                )
+                .await
+                .unwrap();
+        }
+
+        let dir = test_dir("test_basic_consume_stream");
+        let store: Arc<dyn OffsetStore> = Arc::new(FileOffsetStore::new(&dir).unwrap());
+        let args = StreamConsumeArguments::new(stream, "amqprs.stream_consumer")
+            .offset(StreamOffset::First)
+            .offset_store(store.clone())
+            .finish();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        channel
+            .basic_consume_stream(OffsetConsumer(tx), args.clone())
+            .await
+            .unwrap();
+        let mut offsets = vec![];
+        for _ in 0..3 {
+            offsets.push(rx.recv().await.unwrap());
+        }
+        assert!(offsets.windows(2).all(|w| w[0] < w[1]));
+        channel.close().await.unwrap();
+
+        // restarted consumer resumes after the last processed offset
+        let channel = connection.open_channel(None).await.unwrap();
+        channel
+            .basic_publish(
+                BasicProperties::default(),
+                b"event".to_vec(),
+                BasicPublishArguments::new("", stream),
+            )
+            .await
+            .unwrap();
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        channel
+            .basic_consume_stream(OffsetConsumer(tx), args)
+            .await
+            .unwrap();
+        let offset = rx.recv().await.unwrap();
+        assert!(offset > offsets[2]);
+        assert_eq!(Some(offset), store.load(stream, "amqprs.stream_consumer"));
+
+        channel
+            .queue_delete(QueueDeleteArguments::new(stream))
+            .await
+            .unwrap();
+        channel.close().await.unwrap();
+        connection.close().await.unwrap();
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
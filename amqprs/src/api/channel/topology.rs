@@ -0,0 +1,885 @@
+use std::collections::{BTreeMap, HashSet};
+
+use amqp_serde::types::{FieldArray, FieldValue};
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "traces")]
+use tracing::debug;
+
+use super::{
+    arguments::insert, Channel, ExchangeBindArguments, ExchangeDeclareArguments,
+    ExchangeDeleteArguments, ExchangeType, ExchangeUnbindArguments, QueueBindArguments,
+    QueueDeclareArguments, QueueDeleteArguments, QueueUnbindArguments,
+};
+use crate::api::{
+    validation::{
+        check_exchange_name, check_flags, check_not_empty, check_not_reserved, check_queue_name,
+        check_short_str,
+    },
+    Error, FieldTable, Result,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+/// Value of an argument in [`Topology`].
+///
+/// A number is deserialized as `Int` if it is an integer, otherwise as `Float`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+#[non_exhaustive]
+pub enum ArgumentValue {
+    /// Sent as boolean.
+    Bool(bool),
+    /// Sent as signed 64-bit integer.
+    Int(i64),
+    /// Sent as double.
+    Float(f64),
+    /// Sent as long string.
+    String(String),
+    /// Sent as field array.
+    Array(Vec<ArgumentValue>),
+    /// Sent as field table.
+    Table(ArgumentTable),
+}
+
+impl From<bool> for ArgumentValue {
+    fn from(v: bool) -> Self {
+        ArgumentValue::Bool(v)
+    }
+}
+
+impl From<i64> for ArgumentValue {
+    fn from(v: i64) -> Self {
+        ArgumentValue::Int(v)
+    }
+}
+
+impl From<f64> for ArgumentValue {
+    fn from(v: f64) -> Self {
+        ArgumentValue::Float(v)
+    }
+}
+
+impl From<&str> for ArgumentValue {
+    fn from(v: &str) -> Self {
+        ArgumentValue::String(v.to_owned())
+    }
+}
+
+impl From<String> for ArgumentValue {
+    fn from(v: String) -> Self {
+        ArgumentValue::String(v)
+    }
+}
+
+impl From<Vec<ArgumentValue>> for ArgumentValue {
+    fn from(v: Vec<ArgumentValue>) -> Self {
+        ArgumentValue::Array(v)
+    }
+}
+
+impl From<ArgumentTable> for ArgumentValue {
+    fn from(v: ArgumentTable) -> Self {
+        ArgumentValue::Table(v)
+    }
+}
+
+/// Arguments of a definition, ordered by name.
+pub type ArgumentTable = BTreeMap<String, ArgumentValue>;
+
+fn field_value(value: &ArgumentValue) -> Result<FieldValue> {
+    let value = match value {
+        ArgumentValue::Bool(v) => (*v).into(),
+        ArgumentValue::Int(v) => FieldValue::l(*v),
+        ArgumentValue::Float(v) => FieldValue::d(*v),
+        ArgumentValue::String(v) => v.as_str().into(),
+        ArgumentValue::Array(values) => {
+            let values = values.iter().map(field_value).collect::<Result<Vec<_>>>()?;
+            let array: FieldArray = values.try_into().map_err(|err| {
+                Error::InvalidArgument(format!("arguments: array too large, {}", err))
+            })?;
+            FieldValue::A(array)
+        }
+        ArgumentValue::Table(arguments) => FieldValue::F(field_table(arguments)?),
+    };
+    Ok(value)
+}
+
+fn field_table(arguments: &ArgumentTable) -> Result<FieldTable> {
+    let mut table = FieldTable::new();
+    for (key, value) in arguments {
+        check_short_str("arguments", key)?;
+        insert(&mut table, key, field_value(value)?);
+    }
+    Ok(table)
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Definition of an exchange in [`Topology`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExchangeDefinition {
+    /// Exchange name.
+    pub name: String,
+    /// Exchange type, e.g. "direct", "topic", or the type of a plugin.
+    #[serde(rename = "type")]
+    pub exchange_type: String,
+    /// Default: `true`.
+    #[serde(default = "default_true")]
+    pub durable: bool,
+    /// Default: `false`.
+    #[serde(default)]
+    pub auto_delete: bool,
+    /// Default: `false`.
+    #[serde(default)]
+    pub internal: bool,
+    /// Default: empty.
+    #[serde(default)]
+    pub arguments: ArgumentTable,
+}
+
+impl ExchangeDefinition {
+    /// Create a durable exchange definition.
+    pub fn new(name: &str, exchange_type: ExchangeType) -> Self {
+        Self {
+            name: name.to_owned(),
+            exchange_type: exchange_type.into(),
+            durable: true,
+            auto_delete: false,
+            internal: false,
+            arguments: ArgumentTable::new(),
+        }
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        durable, bool
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        auto_delete, bool
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        internal, bool
+    }
+
+    /// Chainable setter of an argument.
+    pub fn argument<V: Into<ArgumentValue>>(&mut self, key: &str, value: V) -> &mut Self {
+        self.arguments.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Finish chained configuration and return new definition.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    fn declare_arguments(&self) -> Result<ExchangeDeclareArguments> {
+        Ok(
+            ExchangeDeclareArguments::new(&self.name, &self.exchange_type)
+                .durable(self.durable)
+                .auto_delete(self.auto_delete)
+                .internal(self.internal)
+                .arguments(field_table(&self.arguments)?)
+                .finish(),
+        )
+    }
+}
+
+/// Definition of a queue in [`Topology`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct QueueDefinition {
+    /// Queue name.
+    pub name: String,
+    /// Default: `true`.
+    #[serde(default = "default_true")]
+    pub durable: bool,
+    /// Default: `false`.
+    #[serde(default)]
+    pub auto_delete: bool,
+    /// Default: empty.
+    #[serde(default)]
+    pub arguments: ArgumentTable,
+}
+
+impl QueueDefinition {
+    /// Create a durable queue definition.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            durable: true,
+            auto_delete: false,
+            arguments: ArgumentTable::new(),
+        }
+    }
+
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        durable, bool
+    }
+    impl_chainable_setter! {
+        /// Chainable setter method.
+        auto_delete, bool
+    }
+
+    /// Chainable setter of an argument.
+    pub fn argument<V: Into<ArgumentValue>>(&mut self, key: &str, value: V) -> &mut Self {
+        self.arguments.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Finish chained configuration and return new definition.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    fn declare_arguments(&self) -> Result<QueueDeclareArguments> {
+        Ok(QueueDeclareArguments::new(&self.name)
+            .durable(self.durable)
+            .auto_delete(self.auto_delete)
+            .arguments(field_table(&self.arguments)?)
+            .finish())
+    }
+}
+
+/// Type of binding destination.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum DestinationType {
+    /// Bind a queue to the source exchange.
+    Queue,
+    /// Bind an exchange to the source exchange.
+    Exchange,
+}
+
+/// Definition of a binding in [`Topology`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BindingDefinition {
+    /// Source exchange.
+    pub source: String,
+    /// Destination queue or exchange.
+    pub destination: String,
+    /// Type of destination.
+    pub destination_type: DestinationType,
+    /// Default: "".
+    #[serde(default)]
+    pub routing_key: String,
+    /// Default: empty.
+    #[serde(default)]
+    pub arguments: ArgumentTable,
+}
+
+impl BindingDefinition {
+    /// Create a binding from the source exchange to the queue.
+    pub fn queue(source: &str, queue: &str, routing_key: &str) -> Self {
+        Self {
+            source: source.to_owned(),
+            destination: queue.to_owned(),
+            destination_type: DestinationType::Queue,
+            routing_key: routing_key.to_owned(),
+            arguments: ArgumentTable::new(),
+        }
+    }
+
+    /// Create a binding from the source exchange to the destination exchange.
+    pub fn exchange(source: &str, destination: &str, routing_key: &str) -> Self {
+        Self {
+            destination_type: DestinationType::Exchange,
+            ..Self::queue(source, destination, routing_key)
+        }
+    }
+
+    /// Chainable setter of an argument.
+    pub fn argument<V: Into<ArgumentValue>>(&mut self, key: &str, value: V) -> &mut Self {
+        self.arguments.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Finish chained configuration and return new definition.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+/// Declarative definitions of exchanges, queues and bindings.
+///
+/// It can be built in code, or deserialized from a config file in any format supported by `serde`.
+/// The schema follows the definitions exported by RabbitMQ management plugin, so an export
+/// can be loaded as it is, and the fields not listed below, such as `vhost` and `users`, are ignored.
+///
+/// | Field                               | Type                                     |
+/// |-------------------------------------|------------------------------------------|
+/// | `exchanges[].name`                  | string                                   |
+/// | `exchanges[].type`                  | string                                   |
+/// | `exchanges[].durable`               | boolean, default `true`                  |
+/// | `exchanges[].auto_delete`           | boolean, default `false`                 |
+/// | `exchanges[].internal`              | boolean, default `false`                 |
+/// | `exchanges[].arguments`             | map of bool, number, string, array, map  |
+/// | `queues[].name`                     | string                                   |
+/// | `queues[].durable`                  | boolean, default `true`                  |
+/// | `queues[].auto_delete`              | boolean, default `false`                 |
+/// | `queues[].arguments`                | map of bool, number, string, array, map  |
+/// | `bindings[].source`                 | string                                   |
+/// | `bindings[].destination`            | string                                   |
+/// | `bindings[].destination_type`       | "queue" or "exchange"                    |
+/// | `bindings[].routing_key`            | string, default ""                       |
+/// | `bindings[].arguments`              | map of bool, number, string, array, map  |
+///
+/// Bindings may refer to exchanges and queues not defined in the topology, e.g. `amq.topic`.
+///
+/// # Usage
+///
+/// ```
+/// # use amqprs::channel::{BindingDefinition, ExchangeDefinition, ExchangeType, QueueDefinition, Topology};
+/// let topology = Topology::new()
+///     .exchange(ExchangeDefinition::new("orders", ExchangeType::Topic))
+///     .queue(
+///         QueueDefinition::new("orders.created")
+///             .argument("x-queue-type", "quorum")
+///             .finish(),
+///     )
+///     .binding(BindingDefinition::queue("orders", "orders.created", "order.created"))
+///     .finish();
+/// assert!(topology.validate().is_ok());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Topology {
+    /// Default: empty.
+    #[serde(default)]
+    pub exchanges: Vec<ExchangeDefinition>,
+    /// Default: empty.
+    #[serde(default)]
+    pub queues: Vec<QueueDefinition>,
+    /// Default: empty.
+    #[serde(default)]
+    pub bindings: Vec<BindingDefinition>,
+}
+
+/// Entities defined by a previous [`Topology`] but not by the current one, see [`Topology::diff`].
+///
+/// [`Topology::diff`]: struct.Topology.html#method.diff
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TopologyDiff {
+    /// Exchanges to delete.
+    pub exchanges: Vec<String>,
+    /// Queues to delete.
+    pub queues: Vec<String>,
+    /// Bindings to remove. Bindings of deleted exchanges or queues are not listed,
+    /// because server removes them along with the entities.
+    pub bindings: Vec<BindingDefinition>,
+}
+
+impl TopologyDiff {
+    /// Returns `true` if nothing needs to be deleted.
+    pub fn is_empty(&self) -> bool {
+        self.exchanges.is_empty() && self.queues.is_empty() && self.bindings.is_empty()
+    }
+}
+
+impl Topology {
+    /// Create an empty topology.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Chainable method to add an exchange.
+    pub fn exchange(&mut self, exchange: ExchangeDefinition) -> &mut Self {
+        self.exchanges.push(exchange);
+        self
+    }
+
+    /// Chainable method to add a queue.
+    pub fn queue(&mut self, queue: QueueDefinition) -> &mut Self {
+        self.queues.push(queue);
+        self
+    }
+
+    /// Chainable method to add a binding.
+    pub fn binding(&mut self, binding: BindingDefinition) -> &mut Self {
+        self.bindings.push(binding);
+        self
+    }
+
+    /// Finish chained configuration and return new topology.
+    pub fn finish(&mut self) -> Self {
+        self.clone()
+    }
+
+    /// Validate the topology, it is also done by [`declare_topology`].
+    ///
+    /// Arguments are further validated by [`exchange_declare`] and [`queue_declare`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if any name is invalid, empty or reserved,
+    /// an exchange or a queue is defined more than once, or a binding has default exchange as source.
+    ///
+    /// [`declare_topology`]: struct.Channel.html#method.declare_topology
+    /// [`exchange_declare`]: struct.Channel.html#method.exchange_declare
+    /// [`queue_declare`]: struct.Channel.html#method.queue_declare
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub fn validate(&self) -> Result<()> {
+        let mut names = HashSet::new();
+        for exchange in &self.exchanges {
+            check_exchange_name("exchanges", &exchange.name)?;
+            check_not_empty(
+                "exchanges",
+                &exchange.name,
+                "default exchange can not be declared",
+            )?;
+            check_not_reserved("exchanges", &exchange.name)?;
+            check_flags(
+                names.insert(&exchange.name),
+                &format!("exchanges: '{}' is defined more than once", exchange.name),
+            )?;
+            exchange.declare_arguments()?.validate()?;
+        }
+        let mut names = HashSet::new();
+        for queue in &self.queues {
+            check_queue_name("queues", &queue.name)?;
+            check_not_empty(
+                "queues",
+                &queue.name,
+                "server-named queue can not be defined",
+            )?;
+            check_not_reserved("queues", &queue.name)?;
+            check_flags(
+                names.insert(&queue.name),
+                &format!("queues: '{}' is defined more than once", queue.name),
+            )?;
+            queue.declare_arguments()?.validate()?;
+        }
+        for binding in &self.bindings {
+            check_exchange_name("bindings", &binding.source)?;
+            check_not_empty(
+                "bindings",
+                &binding.source,
+                "default exchange can not be bound",
+            )?;
+            match binding.destination_type {
+                DestinationType::Queue => check_queue_name("bindings", &binding.destination)?,
+                DestinationType::Exchange => check_exchange_name("bindings", &binding.destination)?,
+            }
+            check_short_str("bindings", &binding.routing_key)?;
+            field_table(&binding.arguments)?;
+        }
+        Ok(())
+    }
+
+    /// Returns what is defined by `previous` but not by this topology.
+    ///
+    /// Exchanges and queues are compared by name, and bindings by all fields, so a binding
+    /// with changed arguments is removed and added again. An exchange or a queue with changed
+    /// definition is not listed, it has to be deleted explicitly before it can be declared again.
+    pub fn diff(&self, previous: &Topology) -> TopologyDiff {
+        let exchanges: Vec<String> = previous
+            .exchanges
+            .iter()
+            .filter(|old| !self.exchanges.iter().any(|new| new.name == old.name))
+            .map(|old| old.name.clone())
+            .collect();
+        let queues: Vec<String> = previous
+            .queues
+            .iter()
+            .filter(|old| !self.queues.iter().any(|new| new.name == old.name))
+            .map(|old| old.name.clone())
+            .collect();
+        let bindings = previous
+            .bindings
+            .iter()
+            .filter(|old| !self.bindings.contains(old))
+            .filter(|old| {
+                let destination_deleted = match old.destination_type {
+                    DestinationType::Queue => queues.contains(&old.destination),
+                    DestinationType::Exchange => exchanges.contains(&old.destination),
+                };
+                !exchanges.contains(&old.source) && !destination_deleted
+            })
+            .cloned()
+            .collect();
+        TopologyDiff {
+            exchanges,
+            queues,
+            bindings,
+        }
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+/// APIs for declarative topology.
+impl Channel {
+    /// Declare the exchanges, queues and bindings of the topology, in that order.
+    ///
+    /// Declaration is idempotent, so the topology can be declared at every start.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if the topology is invalid, see [`Topology::validate`].
+    /// Returns an error if a failure occurs while comunicating with the server,
+    /// e.g. an entity exists with a different definition.
+    ///
+    /// [`Topology::validate`]: struct.Topology.html#method.validate
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub async fn declare_topology(&self, topology: &Topology) -> Result<()> {
+        topology.validate()?;
+        for exchange in &topology.exchanges {
+            self.exchange_declare(exchange.declare_arguments()?).await?;
+        }
+        for queue in &topology.queues {
+            self.queue_declare(queue.declare_arguments()?).await?;
+        }
+        for binding in &topology.bindings {
+            let arguments = field_table(&binding.arguments)?;
+            match binding.destination_type {
+                DestinationType::Queue => {
+                    self.queue_bind(
+                        QueueBindArguments::new(
+                            &binding.destination,
+                            &binding.source,
+                            &binding.routing_key,
+                        )
+                        .arguments(arguments)
+                        .finish(),
+                    )
+                    .await?
+                }
+                DestinationType::Exchange => {
+                    self.exchange_bind(
+                        ExchangeBindArguments::new(
+                            &binding.destination,
+                            &binding.source,
+                            &binding.routing_key,
+                        )
+                        .arguments(arguments)
+                        .finish(),
+                    )
+                    .await?
+                }
+            }
+        }
+        #[cfg(feature = "traces")]
+        debug!(
+            "declared topology of {} exchanges, {} queues and {} bindings on {}",
+            topology.exchanges.len(),
+            topology.queues.len(),
+            topology.bindings.len(),
+            self
+        );
+        Ok(())
+    }
+
+    /// Verify the exchanges and queues of the topology exist, without creating anything.
+    ///
+    /// It passively declares each of them, so server closes the channel with `NOT_FOUND` at
+    /// the first missing one, and it should be done on a dedicated channel.
+    /// Bindings can not be verified by AMQP 0-9-1.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidArgument`] if the topology is invalid, see [`Topology::validate`].
+    /// Returns an error if any exchange or queue does not exist, or a failure occurs while
+    /// comunicating with the server.
+    ///
+    /// [`Topology::validate`]: struct.Topology.html#method.validate
+    /// [`Error::InvalidArgument`]: ../error/enum.Error.html#variant.InvalidArgument
+    pub async fn verify_topology(&self, topology: &Topology) -> Result<()> {
+        topology.validate()?;
+        for exchange in &topology.exchanges {
+            self.exchange_declare(
+                ExchangeDeclareArguments::new(&exchange.name, &exchange.exchange_type)
+                    .passive(true)
+                    .finish(),
+            )
+            .await?;
+        }
+        for queue in &topology.queues {
+            self.queue_declare(
+                QueueDeclareArguments::new(&queue.name)
+                    .passive(true)
+                    .finish(),
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Remove the bindings, and delete the queues and exchanges of the diff, in that order.
+    ///
+    /// Queues are deleted regardless of their messages and consumers.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a failure occurs while comunicating with the server.
+    pub async fn delete_topology(&self, diff: &TopologyDiff) -> Result<()> {
+        for binding in &diff.bindings {
+            let arguments = field_table(&binding.arguments)?;
+            match binding.destination_type {
+                DestinationType::Queue => {
+                    self.queue_unbind(
+                        QueueUnbindArguments::new(
+                            &binding.destination,
+                            &binding.source,
+                            &binding.routing_key,
+                        )
+                        .arguments(arguments)
+                        .finish(),
+                    )
+                    .await?
+                }
+                DestinationType::Exchange => {
+                    self.exchange_unbind(
+                        ExchangeUnbindArguments::new(
+                            &binding.destination,
+                            &binding.source,
+                            &binding.routing_key,
+                        )
+                        .arguments(arguments)
+                        .finish(),
+                    )
+                    .await?
+                }
+            }
+        }
+        for queue in &diff.queues {
+            self.queue_delete(QueueDeleteArguments::new(queue)).await?;
+        }
+        for exchange in &diff.exchanges {
+            self.exchange_delete(ExchangeDeleteArguments::new(exchange))
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use amqp_serde::types::FieldValue;
+
+    use super::{
+        field_table, ArgumentTable, ArgumentValue, BindingDefinition, ExchangeDefinition,
+        QueueDefinition, Topology, TopologyDiff,
+    };
+    use crate::{
+        api::{
+            channel::ExchangeType,
+            connection::{Connection, OpenConnectionArguments},
+        },
+        test_utils::setup_logging,
+    };
+
+    fn topology() -> Topology {
+        Topology::new()
+            .exchange(ExchangeDefinition::new("orders", ExchangeType::Topic))
+            .exchange(ExchangeDefinition::new(
+                "orders.audit",
+                ExchangeType::Fanout,
+            ))
+            .queue(
+                QueueDefinition::new("orders.created")
+                    .argument("x-message-ttl", 60000)
+                    .finish(),
+            )
+            .binding(BindingDefinition::queue(
+                "orders",
+                "orders.created",
+                "order.created",
+            ))
+            .binding(BindingDefinition::exchange("orders", "orders.audit", "#"))
+            .finish()
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let json = r##"{
+            "rabbit_version": "3.12.0",
+            "exchanges": [
+                {"name": "orders", "vhost": "/", "type": "topic", "durable": true,
+                 "auto_delete": false, "internal": false, "arguments": {}},
+                {"name": "orders.audit", "type": "fanout"}
+            ],
+            "queues": [
+                {"name": "orders.created", "vhost": "/", "arguments": {"x-message-ttl": 60000}}
+            ],
+            "bindings": [
+                {"source": "orders", "vhost": "/", "destination": "orders.created",
+                 "destination_type": "queue", "routing_key": "order.created", "arguments": {}},
+                {"source": "orders", "destination": "orders.audit",
+                 "destination_type": "exchange", "routing_key": "#"}
+            ]
+        }"##;
+        let parsed: Topology = serde_json::from_str(json).unwrap();
+        assert_eq!(topology(), parsed);
+        assert_eq!(
+            Some(&ArgumentValue::Int(60000)),
+            parsed.queues[0].arguments.get("x-message-ttl")
+        );
+
+        let serialized = serde_json::to_string(&parsed).unwrap();
+        assert_eq!(parsed, serde_json::from_str(&serialized).unwrap());
+
+        assert!(serde_json::from_str::<Topology>(
+            r#"{"bindings": [{"source": "a", "destination": "b", "destination_type": "stream"}]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_deserialize_arguments() {
+        let json = r#"{
+            "x-int": 1, "x-float": 0.5, "x-str": "a", "x-bool": true,
+            "x-array": [1, "b"], "x-table": {"x-nested": [false]}
+        }"#;
+        let parsed: ArgumentTable = serde_json::from_str(json).unwrap();
+        let nested: ArgumentTable = [(
+            "x-nested".to_owned(),
+            vec![ArgumentValue::Bool(false)].into(),
+        )]
+        .into_iter()
+        .collect();
+        assert_eq!(Some(&ArgumentValue::Int(1)), parsed.get("x-int"));
+        assert_eq!(Some(&ArgumentValue::Float(0.5)), parsed.get("x-float"));
+        assert_eq!(
+            Some(&ArgumentValue::Array(vec![1.into(), "b".into()])),
+            parsed.get("x-array")
+        );
+        assert_eq!(Some(&ArgumentValue::Table(nested)), parsed.get("x-table"));
+
+        let table = field_table(&parsed).unwrap();
+        let get = |key: &str| table.get(&key.try_into().unwrap()).cloned();
+        assert_eq!(Some(FieldValue::l(1)), get("x-int"));
+        assert_eq!(Some(FieldValue::d(0.5)), get("x-float"));
+        assert!(matches!(get("x-array"), Some(FieldValue::A(_))));
+        assert!(matches!(get("x-table"), Some(FieldValue::F(_))));
+
+        let mut invalid = ArgumentTable::new();
+        invalid.insert(
+            "x-table".to_owned(),
+            [("k".repeat(256), ArgumentValue::Int(1))]
+                .into_iter()
+                .collect::<ArgumentTable>()
+                .into(),
+        );
+        assert!(field_table(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(topology().validate().is_ok());
+        assert!(Topology::new()
+            .exchange(ExchangeDefinition::new("", ExchangeType::Direct))
+            .finish()
+            .validate()
+            .is_err());
+        assert!(Topology::new()
+            .exchange(ExchangeDefinition::new("amq.custom", ExchangeType::Direct))
+            .finish()
+            .validate()
+            .is_err());
+        assert!(Topology::new()
+            .queue(QueueDefinition::new("q"))
+            .queue(QueueDefinition::new("q"))
+            .finish()
+            .validate()
+            .is_err());
+        assert!(Topology::new()
+            .queue(
+                QueueDefinition::new("q")
+                    .argument("x-message-ttl", -1)
+                    .finish()
+            )
+            .finish()
+            .validate()
+            .is_err());
+        assert!(Topology::new()
+            .binding(BindingDefinition::queue("", "q", "q"))
+            .finish()
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = topology();
+        assert!(previous.diff(&previous).is_empty());
+
+        let current = Topology::new()
+            .exchange(ExchangeDefinition::new("orders", ExchangeType::Topic))
+            .queue(QueueDefinition::new("orders.created"))
+            .binding(
+                BindingDefinition::queue("orders", "orders.created", "order.created")
+                    .argument("x-match", "all")
+                    .finish(),
+            )
+            .finish();
+        assert_eq!(
+            TopologyDiff {
+                exchanges: vec!["orders.audit".to_owned()],
+                queues: vec![],
+                // binding to the deleted exchange is removed by server
+                bindings: vec![BindingDefinition::queue(
+                    "orders",
+                    "orders.created",
+                    "order.created"
+                )],
+            },
+            current.diff(&previous)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_declare_topology() {
+        setup_logging();
+
+        let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        let previous = Topology::new()
+            .exchange(ExchangeDefinition::new(
+                "amqprs.topology",
+                ExchangeType::Topic,
+            ))
+            .exchange(ExchangeDefinition::new(
+                "amqprs.topology.audit",
+                ExchangeType::Fanout,
+            ))
+            .queue(QueueDefinition::new("amqprs.topology.created"))
+            .binding(BindingDefinition::queue(
+                "amqprs.topology",
+                "amqprs.topology.created",
+                "created",
+            ))
+            .binding(BindingDefinition::exchange(
+                "amqprs.topology",
+                "amqprs.topology.audit",
+                "#",
+            ))
+            .finish();
+        channel.declare_topology(&previous).await.unwrap();
+        // idempotent
+        channel.declare_topology(&previous).await.unwrap();
+        channel.verify_topology(&previous).await.unwrap();
+
+        let mut current = previous.clone();
+        current.exchanges.truncate(1);
+        current.bindings.truncate(1);
+        let diff = current.diff(&previous);
+        assert_eq!(vec!["amqprs.topology.audit".to_owned()], diff.exchanges);
+        channel.delete_topology(&diff).await.unwrap();
+        channel.verify_topology(&current).await.unwrap();
+
+        // deleted exchange no longer exists
+        assert!(channel.verify_topology(&previous).await.is_err());
+
+        let channel = connection.open_channel(None).await.unwrap();
+        channel
+            .delete_topology(&Topology::new().diff(&current))
+            .await
+            .unwrap();
+        assert!(channel.verify_topology(&current).await.is_err());
+        connection.close().await.unwrap();
+    }
+}
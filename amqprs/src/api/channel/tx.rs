@@ -31,15 +31,9 @@ impl Channel {
     pub async fn tx_select(&self) -> Result<()> {
         let select = TxSelect;
 
-        let responder_rx = self.register_responder(TxSelectOk::header()).await?;
-
-        let _method = synchronous_request!(
-            self.shared.outgoing_tx,
-            (self.shared.channel_id, select.into_frame()),
-            responder_rx,
-            Frame::TxSelectOk,
-            Error::ChannelUseError
-        )?;
+        let responder_rx = self.send_request(TxSelectOk::header(), select.into_frame())?;
+        let _method =
+            synchronous_request!(responder_rx, Frame::TxSelectOk, Error::ChannelUseError)?;
         Ok(())
     }
     /// This method commits all message publications and acknowledgments performed in
@@ -52,15 +46,9 @@ impl Channel {
     pub async fn tx_commit(&self) -> Result<()> {
         let select = TxCommit;
 
-        let responder_rx = self.register_responder(TxCommitOk::header()).await?;
-
-        let _method = synchronous_request!(
-            self.shared.outgoing_tx,
-            (self.shared.channel_id, select.into_frame()),
-            responder_rx,
-            Frame::TxCommitOk,
-            Error::ChannelUseError
-        )?;
+        let responder_rx = self.send_request(TxCommitOk::header(), select.into_frame())?;
+        let _method =
+            synchronous_request!(responder_rx, Frame::TxCommitOk, Error::ChannelUseError)?;
         Ok(())
     }
     /// This method abandons all message publications and acknowledgments performed in
@@ -76,15 +64,9 @@ impl Channel {
     pub async fn tx_rollback(&self) -> Result<()> {
         let select = TxRollback;
 
-        let responder_rx = self.register_responder(TxRollbackOk::header()).await?;
-
-        let _method = synchronous_request!(
-            self.shared.outgoing_tx,
-            (self.shared.channel_id, select.into_frame()),
-            responder_rx,
-            Frame::TxRollbackOk,
-            Error::ChannelUseError
-        )?;
+        let responder_rx = self.send_request(TxRollbackOk::header(), select.into_frame())?;
+        let _method =
+            synchronous_request!(responder_rx, Frame::TxRollbackOk, Error::ChannelUseError)?;
         Ok(())
     }
 }
@@ -1,41 +1,63 @@
 //! Utility functions for compliance assert
 //! See [Specs](https://www.rabbitmq.com/resources/specs/amqp0-9-1.extended.xml)
+//!
+//! The `check_*` functions return [`Error::InvalidArgument`] on violation.
+
+use super::{error::Error, Result};
 
 #[inline]
-pub(crate) fn assert_regexp(value: &str) {
+pub(crate) fn check_regexp(field: &str, value: &str) -> Result<()> {
     // regexp: [a-zA-Z0-9-_.:]
-    assert!(!value.chars().any(|c| !c.is_alphanumeric()
-        && c != '-'
-        && c != '_'
-        && c != '.'
-        && c != ':'));
+    if value
+        .chars()
+        .any(|c| !c.is_alphanumeric() && c != '-' && c != '_' && c != '.' && c != ':')
+    {
+        return Err(Error::InvalidArgument(format!(
+            "{}: '{}' contains characters other than [a-zA-Z0-9-_.:]",
+            field, value
+        )));
+    }
+    Ok(())
 }
 
 #[inline]
-pub(crate) fn assert_length(value: &str) {
+pub(crate) fn check_length(field: &str, value: &str) -> Result<()> {
     // max length: 127
-    assert!(value.len() < 128);
+    if value.len() >= 128 {
+        return Err(Error::InvalidArgument(format!(
+            "{}: length {} exceeds 127 bytes",
+            field,
+            value.len()
+        )));
+    }
+    Ok(())
 }
 
 #[inline]
-pub(crate) fn assert_notnull(value: &str) {
-    assert_ne!(value, "");
+pub(crate) fn check_notnull(field: &str, value: &str) -> Result<()> {
+    if value.is_empty() {
+        return Err(Error::InvalidArgument(format!(
+            "{}: must not be empty",
+            field
+        )));
+    }
+    Ok(())
 }
 
 #[inline]
-pub(crate) fn assert_exchange_name(value: &str) {
-    assert_length(value);
-    assert_regexp(value);
+pub(crate) fn check_exchange_name(field: &str, value: &str) -> Result<()> {
+    check_length(field, value)?;
+    check_regexp(field, value)
 }
 
 #[inline]
-pub(crate) fn assert_queue_name(value: &str) {
-    assert_length(value);
-    assert_regexp(value);
+pub(crate) fn check_queue_name(field: &str, value: &str) -> Result<()> {
+    check_length(field, value)?;
+    check_regexp(field, value)
 }
 
 #[inline]
-pub(crate) fn assert_path(value: &str) {
-    assert_notnull(value);
-    assert_length(value);
+pub(crate) fn check_path(field: &str, value: &str) -> Result<()> {
+    check_notnull(field, value)?;
+    check_length(field, value)
 }
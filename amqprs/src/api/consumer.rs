@@ -3,12 +3,16 @@
 //! The consumer is required by [`Channel::basic_consume`] or [`Channel::basic_consume_blocking`].
 //! User should create its own consumer by implementing the trait [`AsyncConsumer`] or [`BlockingConsumer`].
 //!
+//! Alternatively, implement the trait [`OutcomeConsumer`] and use [`Channel::basic_consume_with_outcome`],
+//! which acknowledges the deliveries by the returned [`Outcome`] instead of the consumer itself.
+//!
 //! # Examples
 //!
 //! See implementation of [`DefaultConsumer`] and [`DefaultBlockingConsumer`].
 //!
 //! [`Channel::basic_consume`]: ../channel/struct.Channel.html#method.basic_consume
 //! [`Channel::basic_consume_blocking`]: ../channel/struct.Channel.html#method.basic_consume_blocking
+//! [`Channel::basic_consume_with_outcome`]: ../channel/struct.Channel.html#method.basic_consume_with_outcome
 //!
 use super::channel::{BasicAckArguments, Channel};
 use crate::frame::{BasicProperties, Deliver};
@@ -176,3 +180,49 @@ impl BlockingConsumer for DefaultBlockingConsumer {
         }
     }
 }
+
+//////////////////////////////////////////////////////////////////////////////
+/// Outcome of handling a delivery, which is sent to server as acknowledgement.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Outcome {
+    /// Acknowledge the delivery, see [basic.ack](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.ack).
+    Ack,
+    /// Negatively acknowledge the delivery, see [basic.nack](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.nack).
+    Nack {
+        /// Requeue the delivery, otherwise it is discarded or dead-lettered.
+        requeue: bool,
+    },
+    /// Reject the delivery, see [basic.reject](https://www.rabbitmq.com/amqp-0-9-1-reference.html#basic.reject).
+    Reject {
+        /// Requeue the delivery, otherwise it is discarded or dead-lettered.
+        requeue: bool,
+    },
+}
+
+/// Error returned by [`OutcomeConsumer::handle`].
+pub type HandlerError = Box<dyn std::error::Error + Send + Sync + 'static>;
+
+/// Trait defines the handler of asynchronous content data from server, which returns
+/// the [`Outcome`] instead of acknowledging the delivery by itself.
+///
+/// It is used by [`Channel::basic_consume_with_outcome`], which sends acknowledgement of each
+/// delivery according to the outcome. If the handler returns error or panics, the default outcome
+/// of [`ConsumerOptions`] is used, so that no delivery is left unacknowledged.
+///
+/// [`Channel::basic_consume_with_outcome`]: ../channel/struct.Channel.html#method.basic_consume_with_outcome
+/// [`ConsumerOptions`]: ../channel/struct.ConsumerOptions.html
+#[async_trait]
+pub trait OutcomeConsumer {
+    /// Handle a delivery from server, returns the outcome to acknowledge the delivery.
+    ///
+    /// Unlike [`AsyncConsumer::consume`], the implementation should NOT acknowledge the delivery.
+    /// Otherwise see explanation of inputs and non-blocking requirement in [`AsyncConsumer::consume`].
+    async fn handle(
+        &mut self,
+        channel: &Channel,
+        deliver: Deliver,
+        basic_properties: BasicProperties,
+        content: Vec<u8>,
+    ) -> Result<Outcome, HandlerError>;
+}
@@ -13,6 +13,11 @@ use uriparse::uri_reference::URIReferenceError;
 pub enum Error {
     /// Error when using an amqp(s) uri. Usually due to incorrect usage by user.
     UriError(String),
+    /// Error when an argument given by user is invalid, checked before sending to server.
+    InvalidArgument(String),
+    /// Error when loading connection settings from config or environment variables.
+    /// The message lists every invalid setting.
+    ConfigError(String),
     /// Error during openning a connection.
     ConnectionOpenError(String),
     /// Error during closing a connection.
@@ -25,11 +30,95 @@ pub enum Error {
     ChannelCloseError(String),
     /// Error when using the channel. Usually due to incorrect usage by user.
     ChannelUseError(String),
+    /// Error when settling a delivery tag which is unknown or already settled,
+    /// checked before sending to server, which would close the channel otherwise.
+    DeliveryTagError(String),
     /// Error occurs in network layer.
     NetworkError(String),
+    /// Error when server violates the protocol, e.g. sends an unexpected or malformed frame.
+    /// The affected channel or connection is closed by client with a reply code such as
+    /// 505 (UNEXPECTED_FRAME) or 501 (FRAME_ERROR).
+    ProtocolError(String),
+    /// Error when publishing is rejected because the connection is blocked or the
+    /// channel flow is paused by server. See [`PublishGating`].
+    ///
+    /// [`PublishGating`]: ../connection/enum.PublishGating.html
+    Blocked(String),
     /// Error in sending or receiving messages via internal communication channel.
     /// Usually due to incorrect usage by user.
     InternalChannelError(String),
+    /// Error when a queue or exchange is redeclared with flags or arguments different from
+    /// the existing one, server closes the channel with 406 (PRECONDITION_FAILED).
+    InequivalentArguments(Box<InequivalentArguments>),
+}
+
+/// Details of [`Error::InequivalentArguments`], parsed from the reply text of server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InequivalentArguments {
+    /// Type of the entity, e.g. "queue" or "exchange".
+    pub entity_type: String,
+    /// Name of the entity.
+    pub entity: String,
+    /// Name of the flag or argument, e.g. "durable" or "x-message-ttl".
+    pub argument: String,
+    /// Value of the existing entity, [`None`] if the argument is not set.
+    pub existing: Option<String>,
+    /// Value of the declaration, [`None`] if the argument is not set.
+    pub requested: Option<String>,
+    /// Reply text of server.
+    pub reply_text: String,
+}
+
+impl InequivalentArguments {
+    /// Parse the reply text of server, e.g. "PRECONDITION_FAILED - inequivalent arg 'durable'
+    /// for queue 'orders' in vhost '/': received 'false' but current is 'true'".
+    ///
+    /// Returns [`None`] if it is not about inequivalent arguments.
+    pub(crate) fn parse(reply_text: &str) -> Option<Self> {
+        let (_, rest) = reply_text.split_once("inequivalent arg '")?;
+        let (argument, rest) = rest.split_once("' for ")?;
+        let (entity_type, rest) = rest.split_once(" '")?;
+        let (entity, rest) = rest.split_once("' in vhost '")?;
+        let (_, rest) = rest.split_once("': received ")?;
+        let (requested, existing) = rest.split_once(" but current is ")?;
+        Some(Self {
+            entity_type: entity_type.to_owned(),
+            entity: entity.to_owned(),
+            argument: argument.to_owned(),
+            existing: parse_value(existing),
+            requested: parse_value(requested),
+            reply_text: reply_text.to_owned(),
+        })
+    }
+}
+
+/// Value is either `none`, `'<value>'` or `the value '<value>' of type '<type>'`.
+fn parse_value(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value == "none" {
+        return None;
+    }
+    let quoted = match value.strip_prefix("the value '") {
+        Some(rest) => rest.rfind("' of type '").map(|index| &rest[..index]),
+        None => value
+            .strip_prefix('\'')
+            .and_then(|rest| rest.strip_suffix('\'')),
+    };
+    Some(quoted.unwrap_or(value).to_owned())
+}
+
+impl fmt::Display for InequivalentArguments {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} '{}' exists with {} = {}, but declared with {}",
+            self.entity_type,
+            self.entity,
+            self.argument,
+            self.existing.as_deref().unwrap_or("none"),
+            self.requested.as_deref().unwrap_or("none"),
+        )
+    }
 }
 
 #[cfg(feature = "urispec")]
@@ -61,18 +150,69 @@ impl fmt::Display for Error {
             Error::UriError(msg) => {
                 write!(f, "AMQP(S) URI error: {}", msg)
             }
+            Error::InvalidArgument(msg) => write!(f, "AMQP invalid argument: {}", msg),
+            Error::ConfigError(msg) => write!(f, "AMQP configuration error: {}", msg),
             Error::NetworkError(msg) => write!(f, "AMQP network error: {}", msg),
+            Error::ProtocolError(msg) => write!(f, "AMQP protocol error: {}", msg),
+            Error::Blocked(msg) => write!(f, "AMQP publish blocked: {}", msg),
             Error::ConnectionOpenError(msg) => write!(f, "AMQP connection open error: {}", msg),
             Error::ConnectionCloseError(msg) => write!(f, "AMQP connection close error: {}", msg),
             Error::ConnectionUseError(msg) => write!(f, "AMQP connection usage error: {}", msg),
             Error::ChannelOpenError(msg) => write!(f, "AMQP channel open error: {}", msg),
             Error::ChannelUseError(msg) => write!(f, "AMQP channel usage error: {}", msg),
             Error::ChannelCloseError(msg) => write!(f, "AMQP channel close error: {}", msg),
+            Error::DeliveryTagError(msg) => write!(f, "AMQP delivery tag error: {}", msg),
             Error::InternalChannelError(msg) => {
                 write!(f, "AMQP internal communication error: {}", msg)
             }
+            Error::InequivalentArguments(details) => {
+                write!(f, "AMQP inequivalent arguments: {}", details)
+            }
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::InequivalentArguments;
+
+    #[test]
+    fn test_parse_inequivalent_arguments() {
+        let text = "PRECONDITION_FAILED - inequivalent arg 'durable' for queue 'orders' \
+                    in vhost '/': received 'false' but current is 'true'";
+        assert_eq!(
+            Some(InequivalentArguments {
+                entity_type: "queue".to_owned(),
+                entity: "orders".to_owned(),
+                argument: "durable".to_owned(),
+                existing: Some("true".to_owned()),
+                requested: Some("false".to_owned()),
+                reply_text: text.to_owned(),
+            }),
+            InequivalentArguments::parse(text)
+        );
+
+        let text = "PRECONDITION_FAILED - inequivalent arg 'x-message-ttl' for queue 'a b' \
+                    in vhost 'v': received the value '1000' of type 'signedint' but current is none";
+        let details = InequivalentArguments::parse(text).unwrap();
+        assert_eq!("a b", details.entity);
+        assert_eq!(Some("1000".to_owned()), details.requested);
+        assert_eq!(None, details.existing);
+
+        let text = "PRECONDITION_FAILED - inequivalent arg 'type' for exchange 'x' \
+                    in vhost '/': received 'topic' but current is 'direct'";
+        let details = InequivalentArguments::parse(text).unwrap();
+        assert_eq!("exchange", details.entity_type);
+        assert_eq!(
+            "exchange 'x' exists with type = direct, but declared with topic",
+            details.to_string()
+        );
+
+        assert_eq!(
+            None,
+            InequivalentArguments::parse("PRECONDITION_FAILED - unknown delivery tag 1")
+        );
+    }
+}
@@ -0,0 +1,139 @@
+//! Lifecycle events of [`Connection`] and [`Channel`].
+//!
+//! Unlike [`callbacks`], which allow only one registered handler per connection or channel,
+//! any number of tasks can subscribe to the events by [`Connection::events`] and [`Channel::events`],
+//! e.g. a metrics layer and the application logic. Events are published regardless of whether
+//! callbacks are registered, and callbacks still handle the protocol replies to server.
+//!
+//! Events are delivered through a [`broadcast`] channel of fixed capacity, a subscriber which does
+//! not keep up will get [`RecvError::Lagged`] and miss the oldest events.
+//! Only events published after subscription are received.
+//!
+//! # Example
+//! ```rust
+//! # use amqprs::connection::{OpenConnectionArguments, Connection};
+//! # use amqprs::events::ConnectionEvent;
+//! # #[tokio::main]
+//! # async fn main() {
+//! # let args = OpenConnectionArguments::new("localhost", 5672, "user", "bitnami");
+//! let connection = Connection::open(&args).await.unwrap();
+//!
+//! let mut events = connection.events();
+//! tokio::spawn(async move {
+//!     while let Ok(event) = events.recv().await {
+//!         if let ConnectionEvent::Closed(reason) = event {
+//!             println!("connection closed, {}", reason);
+//!             break;
+//!         }
+//!     }
+//! });
+//!
+//! connection.close().await.unwrap();
+//! # }
+//! ```
+//!
+//! [`Connection`]: ../connection/struct.Connection.html
+//! [`Connection::events`]: ../connection/struct.Connection.html#method.events
+//! [`Channel`]: ../channel/struct.Channel.html
+//! [`Channel::events`]: ../channel/struct.Channel.html#method.events
+//! [`callbacks`]: ../callbacks/index.html
+//! [`broadcast`]: https://docs.rs/tokio/latest/tokio/sync/broadcast/index.html
+//! [`RecvError::Lagged`]: https://docs.rs/tokio/latest/tokio/sync/broadcast/error/enum.RecvError.html
+
+use amqp_serde::types::AmqpChannelId;
+use tokio::sync::broadcast;
+
+use crate::{channel::UnsettledDelivery, connection::CloseReason, Ack, Nack, Return};
+
+/// Capacity of event channel per connection or channel.
+const EVENT_BUFFER_SIZE: usize = 64;
+
+/////////////////////////////////////////////////////////////////////////////
+/// Events of a [`Connection`].
+///
+/// [`Connection`]: ../connection/struct.Connection.html
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ConnectionEvent {
+    /// A channel is opened on the connection.
+    ChannelOpened(AmqpChannelId),
+    /// Server blocks the connection from publishing, with the reason.
+    Blocked(String),
+    /// Server unblocks the connection.
+    Unblocked,
+    /// The connection is closed, with the reason.
+    Closed(CloseReason),
+}
+
+/// Events of a [`Channel`].
+///
+/// [`Channel`]: ../channel/struct.Channel.html
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ChannelEvent {
+    /// Server requests to start (`true`) or pause (`false`) the flow of content data.
+    FlowChanged(bool),
+    /// Server cancels the consumer, with the consumer tag.
+    ConsumerCancelled(String),
+    /// Server acknowledges published messages, only in `publish confirm` mode.
+    PublishAcked(Ack),
+    /// Server rejects published messages, only in `publish confirm` mode.
+    PublishNacked(Nack),
+    /// Server returns an undeliverable message, the message itself is passed to
+    /// [`ChannelCallback::publish_return`].
+    ///
+    /// [`ChannelCallback::publish_return`]: ../callbacks/trait.ChannelCallback.html#tymethod.publish_return
+    PublishReturned(Return),
+    /// A delivery is about to pass its ack deadline, see [`AckDeadline`].
+    ///
+    /// [`AckDeadline`]: ../channel/struct.AckDeadline.html
+    AckDeadlineApproaching(UnsettledDelivery),
+    /// A delivery has passed its ack deadline and is settled by client, see [`AckDeadline`].
+    ///
+    /// [`AckDeadline`]: ../channel/struct.AckDeadline.html
+    AckDeadlineExpired(UnsettledDelivery),
+    /// The channel is closed, with the reason.
+    Closed(CloseReason),
+}
+
+/// Sender half of events, which never fails even if there is no subscriber.
+#[derive(Debug)]
+pub(crate) struct EventSender<T>(broadcast::Sender<T>);
+
+impl<T: Clone> EventSender<T> {
+    pub fn new() -> Self {
+        Self(broadcast::channel(EVENT_BUFFER_SIZE).0)
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<T> {
+        self.0.subscribe()
+    }
+
+    pub fn send(&self, event: T) {
+        // error only means no subscriber
+        self.0.send(event).ok();
+    }
+}
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use super::{ChannelEvent, EventSender};
+
+    #[tokio::test]
+    async fn test_event_sender() {
+        let sender = EventSender::new();
+        // no subscriber
+        sender.send(ChannelEvent::FlowChanged(false));
+
+        let mut rx1 = sender.subscribe();
+        let mut rx2 = sender.subscribe();
+        sender.send(ChannelEvent::ConsumerCancelled("ctag".to_owned()));
+        for rx in [&mut rx1, &mut rx2] {
+            match rx.recv().await.unwrap() {
+                ChannelEvent::ConsumerCancelled(tag) => assert_eq!("ctag", tag),
+                other => panic!("unexpected event {:?}", other),
+            }
+        }
+    }
+}
@@ -8,9 +8,8 @@ pub(in crate::api) type Result<T> = std::result::Result<T, Error>;
 pub(crate) mod helpers {
 
     macro_rules! synchronous_request {
-        ($tx:expr, $msg:expr, $rx:expr, $response:path, $err:path) => {{
-            $tx.send($msg).await?;
-            match $rx.await? {
+        ($rx:expr, $response:path, $err:path) => {{
+            match $rx.await?? {
                 $response(_, method) => Ok(method),
                 unexpected => Err($err(unexpected.to_string())),
             }
@@ -48,6 +47,17 @@ pub(crate) mod helpers {
         };
     }
 
+    macro_rules! impl_chainable_option_setter {
+        ($(#[$($attrss:tt)*])* $field_name:ident, $input_type:ty) => {
+            $(#[$($attrss)*])*
+            pub fn $field_name(&mut self, $field_name: $input_type) -> &mut Self {
+                self.$field_name = Some($field_name);
+                self
+            }
+
+        };
+    }
+
     // pub(crate) use impl_chainable_setter;
 }
 
@@ -62,4 +72,6 @@ pub mod channel;
 pub mod connection;
 pub mod consumer;
 pub mod error;
+pub mod events;
 pub mod security;
+mod validation;
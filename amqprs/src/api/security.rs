@@ -2,19 +2,92 @@
 //!
 //! The configuration is used as part of [`OpenConnectionArguments`] value.
 //!
+//! Secrets are kept in [`SecretString`], which is redacted when formatted and zeroized on drop.
+//! To avoid holding a password for the lifetime of the process, implement [`CredentialsProvider`]
+//! and set it by [`OpenConnectionArguments::credentials_provider`], then the credentials are
+//! fetched at each connection attempt and dropped once the authentication is done.
+//!
 //! [`OpenConnectionArguments`]: ../connection/struct.OpenConnectionArguments.html
+//! [`OpenConnectionArguments::credentials_provider`]: ../connection/struct.OpenConnectionArguments.html#method.credentials_provider
 //! [`Connection::open`]: ../connection/struct.Connection.html#method.open
-use amqp_serde::{
-    to_buffer,
-    types::{LongStr, ShortStr},
-};
-use bytes::BytesMut;
+use std::fmt;
+
+use async_trait::async_trait;
+use zeroize::{Zeroize, Zeroizing};
+
+use crate::api::{error::Error, Result};
+
+/////////////////////////////////////////////////////////////////////////////
+/// A string holding a secret, e.g. a password.
+///
+/// It is redacted by both [`fmt::Debug`] and [`fmt::Display`], and its memory is
+/// overwritten with zeros when dropped. It does not implement [`PartialEq`], because
+/// a comparison which is not constant-time may leak the secret by timing,
+/// compare the exposed secrets with a constant-time comparison if needed.
+///
+/// # Example
+///
+/// ```
+/// # use amqprs::security::SecretString;
+/// let secret = SecretString::from("bitnami");
+/// assert_eq!("***", format!("{}", secret));
+/// assert_eq!("bitnami", secret.expose_secret());
+/// ```
+#[derive(Clone, Default)]
+pub struct SecretString(String);
+
+impl SecretString {
+    /// Create a new secret, taking the ownership of given string.
+    pub fn new(secret: String) -> Self {
+        Self(secret)
+    }
+
+    /// Get the secret in plain text.
+    ///
+    /// Avoid copying the returned value, copies are not zeroized on drop.
+    pub fn expose_secret(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(secret: String) -> Self {
+        Self(secret)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(secret: &str) -> Self {
+        Self(secret.to_owned())
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
+
+impl fmt::Display for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("***")
+    }
+}
+
+impl Drop for SecretString {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
 
+/////////////////////////////////////////////////////////////////////////////
 /// Credentials used to open a connection.
-#[derive(Clone)]
+///
+/// The password is kept in [`SecretString`], so it is redacted in debug output.
+#[derive(Debug, Clone)]
 pub struct SecurityCredentials {
     username: String,
-    password: String,
+    password: SecretString,
     mechanism: AuthenticationMechanism,
 }
 
@@ -33,19 +106,34 @@ impl SecurityCredentials {
     ///
     /// See [RabbitMQ access control](https://www.rabbitmq.com/access-control.html#mechanisms).
     pub fn new_plain(username: &str, password: &str) -> Self {
+        Self::new_plain_secret(username, password.into())
+    }
+
+    /// Same as [`new_plain`], but take the ownership of a password which is already a secret.
+    ///
+    /// [`new_plain`]: struct.SecurityCredentials.html#method.new_plain
+    pub fn new_plain_secret(username: &str, password: SecretString) -> Self {
         Self {
             username: username.to_owned(),
-            password: password.to_owned(),
+            password,
             mechanism: AuthenticationMechanism::PLAIN,
         }
     }
+
     /// Create and return a AMQPLAIN credential with given `username` and `password`.
     ///
     /// See [RabbitMQ access control](https://www.rabbitmq.com/access-control.html#mechanisms).
     pub fn new_amqplain(username: &str, password: &str) -> Self {
+        Self::new_amqplain_secret(username, password.into())
+    }
+
+    /// Same as [`new_amqplain`], but take the ownership of a password which is already a secret.
+    ///
+    /// [`new_amqplain`]: struct.SecurityCredentials.html#method.new_amqplain
+    pub fn new_amqplain_secret(username: &str, password: SecretString) -> Self {
         Self {
             username: username.to_owned(),
-            password: password.to_owned(),
+            password,
             mechanism: AuthenticationMechanism::AMQPLAIN,
         }
     }
@@ -56,11 +144,19 @@ impl SecurityCredentials {
     pub fn new_external() -> Self {
         Self {
             username: "".to_owned(),
-            password: "".to_owned(),
+            password: SecretString::default(),
             mechanism: AuthenticationMechanism::EXTERNAL,
         }
     }
 
+    pub(crate) fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub(crate) fn password(&self) -> &str {
+        self.password.expose_secret()
+    }
+
     /// Get the name of authentication mechanism of current credential
     pub(crate) fn get_mechanism_name(&self) -> &str {
         match self.mechanism {
@@ -69,38 +165,124 @@ impl SecurityCredentials {
             AuthenticationMechanism::EXTERNAL => "EXTERNAL",
         }
     }
-    /// Get the security challenge `response` string, to be sent to server.
-    pub(crate) fn get_response(&self) -> String {
+    /// Get the security challenge `response` bytes, to be sent to server.
+    ///
+    /// The buffer is allocated with the exact size, so that no copy of the secret
+    /// is left behind by reallocation, and it is zeroized on drop.
+    pub(crate) fn get_response(&self) -> Result<Zeroizing<Vec<u8>>> {
+        let username = self.username.as_bytes();
+        let password = self.password.expose_secret().as_bytes();
+        let too_long = || Error::ConnectionOpenError("username or password is too long".to_owned());
         match self.mechanism {
-            AuthenticationMechanism::PLAIN => format!("\0{}\0{}", self.username, self.password),
+            AuthenticationMechanism::PLAIN => {
+                let size = 2 + username.len() + password.len();
+                u32::try_from(size).map_err(|_| too_long())?;
+                let mut buf = Zeroizing::new(Vec::with_capacity(size));
+                buf.push(0);
+                buf.extend_from_slice(username);
+                buf.push(0);
+                buf.extend_from_slice(password);
+                Ok(buf)
+            }
             AuthenticationMechanism::AMQPLAIN => {
-                let mut buf = BytesMut::new();
-                to_buffer(
-                    &<&str as TryInto<ShortStr>>::try_into("LOGIN").unwrap(),
-                    &mut buf,
-                )
-                .unwrap();
-                to_buffer(&'S', &mut buf).unwrap();
-                to_buffer(
-                    &<&str as TryInto<LongStr>>::try_into(&self.username).unwrap(),
-                    &mut buf,
-                )
-                .unwrap();
-
-                to_buffer(
-                    &<&str as TryInto<ShortStr>>::try_into("PASSWORD").unwrap(),
-                    &mut buf,
-                )
-                .unwrap();
-                to_buffer(&'S', &mut buf).unwrap();
-                to_buffer(
-                    &<&str as TryInto<LongStr>>::try_into(&self.password).unwrap(),
-                    &mut buf,
-                )
-                .unwrap();
-                String::from_utf8(buf.to_vec()).unwrap()
+                // field table entries without the table length:
+                // "LOGIN" 'S' <username>, "PASSWORD" 'S' <password>
+                const LOGIN: &[u8] = b"LOGIN";
+                const PASSWORD: &[u8] = b"PASSWORD";
+                let size = 1
+                    + LOGIN.len()
+                    + 1
+                    + 4
+                    + username.len()
+                    + 1
+                    + PASSWORD.len()
+                    + 1
+                    + 4
+                    + password.len();
+                u32::try_from(size).map_err(|_| too_long())?;
+                let mut buf = Zeroizing::new(Vec::with_capacity(size));
+                for (key, value) in [(LOGIN, username), (PASSWORD, password)] {
+                    buf.push(key.len() as u8);
+                    buf.extend_from_slice(key);
+                    buf.push(b'S');
+                    // fits as the whole size fits
+                    buf.extend_from_slice(&(value.len() as u32).to_be_bytes());
+                    buf.extend_from_slice(value);
+                }
+                Ok(buf)
             }
-            AuthenticationMechanism::EXTERNAL => "".to_string(),
+            AuthenticationMechanism::EXTERNAL => Ok(Zeroizing::new(Vec::new())),
         }
     }
 }
+
+/////////////////////////////////////////////////////////////////////////////
+/// Interface to fetch credentials lazily at each connection attempt, e.g. from a secret store.
+///
+/// Set by [`OpenConnectionArguments::credentials_provider`]. The returned credentials are
+/// used only for authentication and are dropped, hence zeroized, right after.
+///
+/// # Example
+///
+/// ```
+/// # use amqprs::security::{CredentialsProvider, SecurityCredentials, SecretString};
+/// # use amqprs::error::Error;
+/// struct EnvPassword;
+///
+/// #[async_trait::async_trait]
+/// impl CredentialsProvider for EnvPassword {
+///     async fn credentials(&self) -> Result<SecurityCredentials, Error> {
+///         let password = std::env::var("AMQP_PASSWORD")
+///             .map_err(|e| Error::ConnectionOpenError(e.to_string()))?;
+///         Ok(SecurityCredentials::new_plain_secret("user", SecretString::new(password)))
+///     }
+/// }
+/// ```
+///
+/// [`OpenConnectionArguments::credentials_provider`]: ../connection/struct.OpenConnectionArguments.html#method.credentials_provider
+#[async_trait]
+pub trait CredentialsProvider: Send + Sync {
+    /// Fetch the credentials for a new connection attempt.
+    async fn credentials(&self) -> Result<SecurityCredentials>;
+}
+
+/// Unit tests
+#[cfg(test)]
+mod tests {
+    use super::{SecretString, SecurityCredentials};
+
+    #[test]
+    fn test_secret_redacted() {
+        let credentials = SecurityCredentials::new_plain("user", "bitnami");
+        let debug = format!("{:?}", credentials);
+        assert!(debug.contains("user"));
+        assert!(!debug.contains("bitnami"));
+
+        let secret = SecretString::from("bitnami");
+        assert_eq!("***", format!("{}", secret));
+        assert_eq!("\"***\"", format!("{:?}", secret));
+    }
+
+    #[test]
+    fn test_get_response() {
+        let plain = SecurityCredentials::new_plain("user", "bitnami")
+            .get_response()
+            .unwrap();
+        assert_eq!(b"\0user\0bitnami", &plain[..]);
+
+        let amqplain = SecurityCredentials::new_amqplain("user", "bitnami")
+            .get_response()
+            .unwrap();
+        let expected = b"\x05LOGINS\x00\x00\x00\x04user\x08PASSWORDS\x00\x00\x00\x07bitnami";
+        assert_eq!(&expected[..], &amqplain[..]);
+
+        // length bytes of a password of 128 to 255 bytes are not valid UTF-8
+        let password = "p".repeat(200);
+        let amqplain = SecurityCredentials::new_amqplain("user", &password)
+            .get_response()
+            .unwrap();
+        assert_eq!(amqplain.capacity(), amqplain.len());
+        assert_eq!(&[0, 0, 0, 200], &amqplain[25..29]);
+        assert_eq!(password.as_bytes(), &amqplain[29..]);
+    }
+}
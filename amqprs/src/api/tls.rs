@@ -6,12 +6,15 @@
 //! [`OpenConnectionArguments`]: ../connection/struct.OpenConnectionArguments.html
 //! [`Connection::open`]: ../connection/struct.Connection.html#method.open
 
+use serde::{Deserialize, Deserializer};
 use std::{fs::File, io::BufReader, path::Path, sync::Arc};
 use tokio_rustls::{
     rustls::{Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore},
     webpki, TlsConnector,
 };
 
+use super::connection::TlsConfig;
+
 /// The TLS adaptor used to enable TLS network stream.
 ///
 /// Currently, it depends on [`tokio-rustls`] and provides convenient
@@ -143,6 +146,33 @@ impl TlsAdaptor {
     }
 }
 
+/// Deserialize and build the TLS adaptor from a config file.
+///
+/// | Field                | Type                                                  |
+/// |----------------------|-------------------------------------------------------|
+/// | `root_ca_cert`       | path, optional, default to use `webpki-roots`         |
+/// | `client_cert`        | path, optional, required if `client_private_key` set  |
+/// | `client_private_key` | path, optional, required if `client_cert` set         |
+/// | `domain`             | string, required                                      |
+///
+/// Client authentication is enabled if both client certificate and private key are given.
+impl<'de> Deserialize<'de> for TlsAdaptor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let config = TlsConfig::deserialize(deserializer)?;
+        let mut errors = Vec::new();
+        config
+            .to_adaptor(
+                None,
+                &|field| field.trim_start_matches("tls.").to_owned(),
+                &mut errors,
+            )
+            .ok_or_else(|| serde::de::Error::custom(errors.join("; ")))
+    }
+}
+
 /// Unit tests
 #[cfg(test)]
 mod tests {
@@ -171,6 +201,20 @@ mod tests {
         assert!(key.len() > 0);
     }
 
+    #[test]
+    fn deserialize_tls_adaptor() {
+        let adaptor: TlsAdaptor = serde_json::from_str(r#"{"domain": "localhost"}"#).unwrap();
+        assert_eq!(adaptor.domain, "localhost");
+
+        // missing domain and private key are both reported
+        let err = serde_json::from_str::<TlsAdaptor>(r#"{"client_cert": "client.pem"}"#)
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("domain: missing"), "{}", err);
+        assert!(err.contains("client_private_key: required"), "{}", err);
+    }
+
     #[test]
     fn read_rsa_key() {
         // RSA private key in PEM format
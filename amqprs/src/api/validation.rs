@@ -0,0 +1,106 @@
+//! Validation of user inputs.
+//!
+//! Arguments are validated before being sent to server, so that a violation is returned
+//! as [`Error::InvalidArgument`] instead of panicking or closing the channel by server.
+//!
+//! If feature "compliance_assert" is enabled, names are also checked against the rules of
+//! AMQP specification.
+
+use amqp_serde::types::ShortStr;
+
+use super::{error::Error, Result};
+
+/// Names with this prefix are reserved by server.
+const RESERVED_NAME_PREFIX: &str = "amq.";
+
+/// Maximum length in bytes of AMQP `shortstr` type.
+const SHORT_STR_MAX_LENGTH: usize = u8::MAX as usize;
+
+/// Convert to AMQP `shortstr` type.
+pub(crate) fn to_short_str(field: &str, value: String) -> Result<ShortStr> {
+    check_short_str(field, &value)?;
+    value
+        .try_into()
+        .map_err(|err| Error::InvalidArgument(format!("{}: {}", field, err)))
+}
+
+/// Check the value fits in AMQP `shortstr` type.
+pub(crate) fn check_short_str(field: &str, value: &str) -> Result<()> {
+    if value.len() > SHORT_STR_MAX_LENGTH {
+        return Err(Error::InvalidArgument(format!(
+            "{}: length {} exceeds {} bytes",
+            field,
+            value.len(),
+            SHORT_STR_MAX_LENGTH
+        )));
+    }
+    Ok(())
+}
+
+/// Check the value is not empty.
+pub(crate) fn check_not_empty(field: &str, value: &str, reason: &str) -> Result<()> {
+    if value.is_empty() {
+        return Err(Error::InvalidArgument(format!(
+            "{}: must not be empty, {}",
+            field, reason
+        )));
+    }
+    Ok(())
+}
+
+/// Check the name does not use the prefix reserved by server.
+pub(crate) fn check_not_reserved(field: &str, value: &str) -> Result<()> {
+    if value.starts_with(RESERVED_NAME_PREFIX) {
+        return Err(Error::InvalidArgument(format!(
+            "{}: '{}' uses reserved prefix '{}'",
+            field, value, RESERVED_NAME_PREFIX
+        )));
+    }
+    Ok(())
+}
+
+/// Reject an invalid combination of flags.
+pub(crate) fn check_flags(valid: bool, message: &str) -> Result<()> {
+    if !valid {
+        return Err(Error::InvalidArgument(message.to_owned()));
+    }
+    Ok(())
+}
+
+/// Check exchange name, and the rules of specification if feature "compliance_assert" is enabled.
+pub(crate) fn check_exchange_name(field: &str, value: &str) -> Result<()> {
+    check_short_str(field, value)?;
+    #[cfg(feature = "compliance_assert")]
+    super::compliance_asserts::check_exchange_name(field, value)?;
+    Ok(())
+}
+
+/// Check queue name, and the rules of specification if feature "compliance_assert" is enabled.
+pub(crate) fn check_queue_name(field: &str, value: &str) -> Result<()> {
+    check_short_str(field, value)?;
+    #[cfg(feature = "compliance_assert")]
+    super::compliance_asserts::check_queue_name(field, value)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_str() {
+        assert!(to_short_str("queue", "q".repeat(255)).is_ok());
+        match to_short_str("queue", "q".repeat(256)) {
+            Err(Error::InvalidArgument(msg)) => {
+                assert_eq!("queue: length 256 exceeds 255 bytes", msg)
+            }
+            _ => panic!("expect invalid argument"),
+        }
+    }
+
+    #[test]
+    fn test_reserved_name() {
+        assert!(check_not_reserved("exchange", "amq.topic").is_err());
+        assert!(check_not_reserved("exchange", "amqprs.topic").is_ok());
+    }
+}
@@ -1,7 +1,10 @@
-use std::fmt;
+use std::{fmt, time::Duration};
 
 use crate::{DELIVERY_MODE_PERSISTENT, DELIVERY_MODE_TRANSIENT};
-use amqp_serde::types::{FieldTable, LongLongUint, Octect, ShortStr, ShortUint, TimeStamp};
+use amqp_serde::types::{
+    FieldArray, FieldTable, FieldValue, LongLongInt, LongLongUint, LongStr, Octect, ShortStr,
+    ShortUint, TimeStamp,
+};
 use serde::{de::Visitor, Deserialize, Serialize};
 
 use super::Frame;
@@ -295,6 +298,82 @@ impl BasicProperties {
         self
     }
 
+    /// Chainable setter of `x-delay` header, in milliseconds precision.
+    ///
+    /// The message is routed after the delay by `x-delayed-message` exchange of
+    /// the 'rabbitmq-delayed-message-exchange' plugin. Other headers are kept.
+    ///
+    /// # Default: [`None`]
+    pub fn with_delay(&mut self, delay: Duration) -> &mut Self {
+        let mut headers = self.headers.take().unwrap_or_default();
+        headers.insert(
+            "x-delay".try_into().unwrap(),
+            FieldValue::l(LongLongInt::try_from(delay.as_millis()).unwrap_or(LongLongInt::MAX)),
+        );
+        self.with_headers(headers)
+    }
+
+    /// Chainable setter of `CC` header, the message is also routed with the routing keys.
+    /// Other headers are kept.
+    ///
+    /// # Default: [`None`]
+    pub fn with_cc<S: AsRef<str>>(&mut self, routing_keys: &[S]) -> &mut Self {
+        self.with_routing_keys_header("CC", routing_keys)
+    }
+
+    /// Chainable setter of `BCC` header, the message is also routed with the routing keys,
+    /// and server removes the header before delivery. Other headers are kept.
+    ///
+    /// # Default: [`None`]
+    pub fn with_bcc<S: AsRef<str>>(&mut self, routing_keys: &[S]) -> &mut Self {
+        self.with_routing_keys_header("BCC", routing_keys)
+    }
+
+    fn with_routing_keys_header<S: AsRef<str>>(
+        &mut self,
+        key: &str,
+        routing_keys: &[S],
+    ) -> &mut Self {
+        // server requires an array of long strings
+        let routing_keys: Vec<FieldValue> = routing_keys
+            .iter()
+            .map(|routing_key| {
+                let routing_key: LongStr = routing_key.as_ref().try_into().unwrap();
+                FieldValue::S(routing_key)
+            })
+            .collect();
+        let mut headers = self.headers.take().unwrap_or_default();
+        headers.insert(
+            key.try_into().unwrap(),
+            FieldValue::A(FieldArray::try_from(routing_keys).unwrap()),
+        );
+        self.with_headers(headers)
+    }
+
+    /// Offset of a message delivered from a stream, i.e. its `x-stream-offset` header.
+    ///
+    /// Returns `None` if the message is not delivered from a stream.
+    pub fn stream_offset(&self) -> Option<u64> {
+        match self
+            .headers
+            .as_ref()?
+            .get(&"x-stream-offset".try_into().unwrap())?
+        {
+            FieldValue::l(v) => u64::try_from(*v).ok(),
+            _ => None,
+        }
+    }
+
+    /// Chainable setter of `x-stream-filter-value` header, used by stream consumers
+    /// to filter messages. Other headers are kept.
+    ///
+    /// # Default: [`None`]
+    pub fn with_stream_filter_value(&mut self, value: &str) -> &mut Self {
+        let mut headers = self.headers.take().unwrap_or_default();
+        headers.insert("x-stream-filter-value".try_into().unwrap(), value.into());
+        self.with_headers(headers)
+    }
+
     pub fn delivery_mode(&self) -> Option<u8> {
         self.delivery_mode
     }
@@ -676,7 +755,9 @@ impl fmt::Display for BasicProperties {
 
 #[cfg(test)]
 mod tests {
-    use amqp_serde::types::FieldTable;
+    use std::time::Duration;
+
+    use amqp_serde::types::{FieldArray, FieldTable, FieldValue};
 
     use crate::{BasicProperties, DELIVERY_MODE_TRANSIENT};
 
@@ -725,4 +806,60 @@ mod tests {
         props.with_timestamp(1674404425);
         assert_eq!([0xC8, 0xC8], props.property_flags);
     }
+
+    #[test]
+    fn test_with_delay() {
+        let mut headers = FieldTable::new();
+        headers.insert("k".try_into().unwrap(), "v".into());
+        let props = BasicProperties::default()
+            .with_headers(headers)
+            .with_delay(Duration::from_secs(5))
+            .finish();
+        let headers = props.headers().unwrap();
+        assert_eq!(
+            Some(&FieldValue::l(5000)),
+            headers.get(&"x-delay".try_into().unwrap())
+        );
+        assert!(headers.get(&"k".try_into().unwrap()).is_some());
+    }
+
+    #[test]
+    fn test_with_cc_bcc() {
+        let mut headers = FieldTable::new();
+        headers.insert("k".try_into().unwrap(), "v".into());
+        let props = BasicProperties::default()
+            .with_headers(headers)
+            .with_cc(&["a", "b"])
+            .with_bcc(&["c".to_owned()])
+            .finish();
+        assert_eq!(vec!["a".to_owned(), "b".to_owned()], props.cc().unwrap());
+        let headers = props.headers().unwrap();
+        assert_eq!(
+            Some(&FieldValue::A(
+                FieldArray::try_from(vec![FieldValue::S("c".try_into().unwrap())]).unwrap()
+            )),
+            headers.get(&"BCC".try_into().unwrap())
+        );
+        assert!(headers.get(&"k".try_into().unwrap()).is_some());
+    }
+
+    #[test]
+    fn test_stream_headers() {
+        assert_eq!(None, BasicProperties::default().stream_offset());
+
+        let mut headers = FieldTable::new();
+        headers.insert("x-stream-offset".try_into().unwrap(), FieldValue::l(7));
+        let props = BasicProperties::default()
+            .with_headers(headers)
+            .with_stream_filter_value("eu")
+            .finish();
+        assert_eq!(Some(7), props.stream_offset());
+        assert_eq!(
+            Some(&FieldValue::from("eu")),
+            props
+                .headers()
+                .unwrap()
+                .get(&"x-stream-filter-value".try_into().unwrap())
+        );
+    }
 }
@@ -1,8 +1,12 @@
+use amqp_serde::types::ShortUint;
 use std::fmt;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     Corrupted,
+    /// class id and method id of an unknown method frame
+    UnknownMethod(ShortUint, ShortUint),
     SerdeError(String),
 }
 
@@ -16,6 +20,11 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Error::Corrupted => f.write_str("corrupted frame"),
+            Error::UnknownMethod(class_id, method_id) => write!(
+                f,
+                "unknown method, class id = {}, method id = {}",
+                class_id, method_id
+            ),
             Error::SerdeError(msg) => write!(f, "serde error: {}", msg),
         }
     }
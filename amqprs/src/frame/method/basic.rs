@@ -88,6 +88,9 @@ impl Consume {
             self.bits &= !bit_flag::consume::NO_ACK;
         }
     }
+    pub fn no_ack(&self) -> bool {
+        self.bits & bit_flag::consume::NO_ACK != 0
+    }
     pub fn set_exclusive(&mut self, value: bool) {
         if value {
             self.bits |= bit_flag::consume::EXCLUSIVE;
@@ -116,9 +119,9 @@ pub struct ConsumeOk {
 ///
 /// [`cancel`]: callbacks/trait.ChannelCallback.html#tymethod.cancel
 // TX + RX
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Cancel {
-    consumer_tag: ShortStr,
+    pub(crate) consumer_tag: ShortStr,
     no_wait: Boolean,
 }
 
@@ -191,7 +194,7 @@ impl Publish {
 ///
 /// [`publish_return`]: callbacks/trait.ChannelCallback.html#tymethod.publish_return
 // RX
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Return {
     reply_code: ShortUint,
     reply_text: ShortStr,
@@ -270,7 +273,7 @@ impl Deliver {
 }
 
 // TX
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Get {
     ticket: ShortUint,
     queue: AmqpQueueName,
@@ -340,7 +343,7 @@ pub struct GetEmpty {
 ///
 /// [`publish_ack`]: callbacks/trait.ChannelCallback.html#tymethod.publish_ack
 // TX + RX
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Ack {
     delivery_tag: LongLongUint,
     mutiple: Boolean,
@@ -414,7 +417,7 @@ pub struct RecoverOk;
 ///
 /// [`publish_nack`]: callbacks/trait.ChannelCallback.html#tymethod.publish_nack
 // TX + RX
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Nack {
     delivery_tag: LongLongUint,
     bits: Octect,
@@ -37,6 +37,20 @@ pub struct CloseChannel {
 }
 
 impl CloseChannel {
+    /// Create a close request with reply code and text, reply text longer than 255 bytes is truncated.
+    pub(crate) fn new(reply_code: ShortUint, reply_text: &str) -> Self {
+        let mut end = reply_text.len().min(u8::MAX as usize);
+        while !reply_text.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            reply_code,
+            reply_text: reply_text[..end].try_into().unwrap_or_default(),
+            class_id: 0,
+            method_id: 0,
+        }
+    }
+
     pub fn reply_code(&self) -> u16 {
         self.reply_code
     }
@@ -1,8 +1,11 @@
 use std::fmt;
 
 use crate::frame::REPLY_SUCCESS;
-use amqp_serde::types::{AmqpPeerProperties, LongStr, LongUint, Octect, ShortStr, ShortUint};
+use amqp_serde::types::{
+    AmqpPeerProperties, ByteArray, LongStr, LongUint, Octect, ShortStr, ShortUint,
+};
 use serde::{Deserialize, Serialize};
+use zeroize::Zeroize;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Start {
@@ -13,11 +16,12 @@ pub struct Start {
     pub(crate) locales: LongStr,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct StartOk {
     client_properties: AmqpPeerProperties,
     machanisms: ShortStr,
-    response: LongStr,
+    // bytes, because a response such as AMQPLAIN's may not be valid UTF-8
+    response: ByteArray,
     locale: ShortStr,
 }
 
@@ -25,7 +29,7 @@ impl StartOk {
     pub fn new(
         client_properties: AmqpPeerProperties,
         machanisms: ShortStr,
-        response: LongStr,
+        response: ByteArray,
         locale: ShortStr,
     ) -> Self {
         Self {
@@ -37,12 +41,31 @@ impl StartOk {
     }
 }
 
+impl fmt::Debug for StartOk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StartOk")
+            .field("client_properties", &self.client_properties)
+            .field("machanisms", &self.machanisms)
+            .field("response", &"***")
+            .field("locale", &self.locale)
+            .finish()
+    }
+}
+
+/// `response` contains the credentials, zeroize it.
+impl Drop for StartOk {
+    fn drop(&mut self) {
+        let response = std::mem::replace(&mut self.response, Vec::new().try_into().unwrap());
+        Vec::from(response).zeroize();
+    }
+}
+
 impl Default for StartOk {
     fn default() -> Self {
         Self {
             client_properties: AmqpPeerProperties::new(),
             machanisms: "PLAIN".try_into().unwrap(),
-            response: "\0guest\0guest".try_into().unwrap(),
+            response: b"\0guest\0guest".to_vec().try_into().unwrap(),
             locale: "en_US".try_into().unwrap(),
         }
     }
@@ -147,6 +170,20 @@ impl fmt::Display for Close {
     }
 }
 impl Close {
+    /// Create a close request with reply code and text, reply text longer than 255 bytes is truncated.
+    pub(crate) fn new(reply_code: ShortUint, reply_text: &str) -> Self {
+        let mut end = reply_text.len().min(u8::MAX as usize);
+        while !reply_text.is_char_boundary(end) {
+            end -= 1;
+        }
+        Self {
+            reply_code,
+            reply_text: reply_text[..end].try_into().unwrap_or_default(),
+            class_id: 0,
+            method_id: 0,
+        }
+    }
+
     pub fn reply_code(&self) -> u16 {
         self.reply_code
     }
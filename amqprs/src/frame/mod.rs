@@ -33,10 +33,10 @@ mod helpers {
                     $($class_id => {
                         match header.method_id() {
                             $($method_id => Ok(from_bytes::<$method>(content)?.into_frame()),)+
-                            _ => unimplemented!("unknown method id"),
+                            _ => Err(Error::UnknownMethod(header.class_id(), header.method_id())),
                         }
                     })+
-                    _ => unimplemented!("unknown class id"),
+                    _ => Err(Error::UnknownMethod(header.class_id(), header.method_id())),
                 }
             }
 
@@ -197,10 +197,7 @@ impl Frame {
             frame_type,
             channel,
             payload_size,
-        } = from_bytes(match buf.get(0..FRAME_HEADER_SIZE) {
-            Some(s) => s,
-            None => unreachable!("out of bound"),
-        })?;
+        } = from_bytes(buf.get(0..FRAME_HEADER_SIZE).ok_or(Error::Corrupted)?)?;
 
         // check full frame is received payload_size + 8 octects
         let total_size = payload_size as usize + FRAME_HEADER_SIZE + 1;
@@ -208,28 +205,19 @@ impl Frame {
             return Ok(None);
         }
         // check frame end
-        match buf.get(total_size - 1) {
-            Some(v) => {
-                // expect frame_end
-                if &FRAME_END != v {
-                    return Err(Error::Corrupted);
-                }
-            }
-            None => unreachable!("out of bound"),
-        };
+        if buf.get(total_size - 1) != Some(&FRAME_END) {
+            return Err(Error::Corrupted);
+        }
+        // payload of the frame, excluding frame end
+        let payload = buf
+            .get(FRAME_HEADER_SIZE..total_size - 1)
+            .ok_or(Error::Corrupted)?;
 
         // parse frame payload
         match frame_type {
             FRAME_METHOD => {
-                let header: MethodHeader =
-                    from_bytes(match buf.get(FRAME_HEADER_SIZE..FRAME_HEADER_SIZE + 4) {
-                        Some(s) => s,
-                        None => unreachable!("out of bound"),
-                    })?;
-                let method_raw = match buf.get(FRAME_HEADER_SIZE + 4..total_size - 1) {
-                    Some(s) => s,
-                    None => unreachable!("out of bound"),
-                };
+                let header: MethodHeader = from_bytes(payload.get(0..4).ok_or(Error::Corrupted)?)?;
+                let method_raw = payload.get(4..).ok_or(Error::Corrupted)?;
 
                 let frame = decode_method_frame(header, method_raw)?;
 
@@ -237,19 +225,10 @@ impl Frame {
             }
             FRAME_HEARTBEAT => Ok(Some((total_size, channel, Frame::HeartBeat(HeartBeat)))),
             FRAME_CONTENT_HEADER => {
-                let mut start = FRAME_HEADER_SIZE;
-                let mut end = start + 12;
-                let header_common: ContentHeaderCommon = from_bytes(match buf.get(start..end) {
-                    Some(s) => s,
-                    None => unreachable!("out of bound"),
-                })?;
-
-                start = end;
-                end = total_size - 1;
-                let basic_properties: BasicProperties = from_bytes(match buf.get(start..end) {
-                    Some(s) => s,
-                    None => unreachable!("out of bound"),
-                })?;
+                let header_common: ContentHeaderCommon =
+                    from_bytes(payload.get(0..12).ok_or(Error::Corrupted)?)?;
+                let basic_properties: BasicProperties =
+                    from_bytes(payload.get(12..).ok_or(Error::Corrupted)?)?;
 
                 Ok(Some((
                     total_size,
@@ -260,19 +239,47 @@ impl Frame {
                     ))),
                 )))
             }
-            FRAME_CONTENT_BODY => {
-                let start = FRAME_HEADER_SIZE;
-                let end = total_size - 1;
-                let body = buf.get(start..end).expect("should never fail");
-                Ok(Some((
-                    total_size,
-                    channel,
-                    Frame::ContentBody(ContentBody::new(body.to_vec())),
-                )))
-            }
+            FRAME_CONTENT_BODY => Ok(Some((
+                total_size,
+                channel,
+                Frame::ContentBody(ContentBody::new(payload.to_vec())),
+            ))),
             _ => Err(Error::Corrupted),
         }
     }
 }
 
 /////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use super::{Error, Frame, FRAME_END, FRAME_METHOD};
+
+    #[test]
+    fn test_decode_unknown_method() {
+        // method frame on channel 1 with class id 60 and method id 99
+        let buf = [FRAME_METHOD, 0, 1, 0, 0, 0, 4, 0, 60, 0, 99, FRAME_END];
+        match Frame::decode(&buf) {
+            Err(Error::UnknownMethod(60, 99)) => {}
+            other => panic!("expect unknown method error, but got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_corrupted_frame() {
+        // incomplete frame
+        let buf = [FRAME_METHOD, 0, 1, 0, 0, 0, 4, 0, 60];
+        assert!(matches!(Frame::decode(&buf), Ok(None)));
+
+        // invalid frame end
+        let buf = [FRAME_METHOD, 0, 1, 0, 0, 0, 4, 0, 60, 0, 99, 0];
+        assert!(matches!(Frame::decode(&buf), Err(Error::Corrupted)));
+
+        // payload too short for method header
+        let buf = [FRAME_METHOD, 0, 1, 0, 0, 0, 2, 0, 60, FRAME_END];
+        assert!(matches!(Frame::decode(&buf), Err(Error::Corrupted)));
+
+        // unknown frame type
+        let buf = [9, 0, 1, 0, 0, 0, 0, FRAME_END];
+        assert!(matches!(Frame::decode(&buf), Err(Error::Corrupted)));
+    }
+}
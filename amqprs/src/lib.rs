@@ -47,9 +47,9 @@
 //! # Optional Features
 //!
 //! - "traces": enable `tracing` in the library.
-//! - "compliance_assert": enable compliance assertion according to AMQP spec.
-//!     If enabled, library always check user inputs and `panic` if any non-compliance.
-//!     If disabled, then it relies on server to reject.
+//! - "compliance_assert": enable compliance checks according to AMQP spec.
+//!     If enabled, names and virtual host are also checked against the rules of the spec,
+//!     and any non-compliance is returned as `Error::InvalidArgument` instead of being rejected by server.
 //! - "tls": enable SSL/TLS.
 //! - "urispec": enable support of [RabbitMQ URI Specification](https://www.rabbitmq.com/uri-spec.html)
 //!
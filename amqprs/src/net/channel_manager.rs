@@ -5,12 +5,12 @@ use tokio::sync::{mpsc::UnboundedSender, oneshot};
 
 use crate::frame::MethodHeader;
 
-use super::{channel_id_repo::ChannelIdRepository, IncomingMessage};
+use super::{channel_id_repo::ChannelIdRepository, IncomingMessage, IncomingResponse};
 
 pub(crate) struct ChannelResource {
     /// responder to acknowledge synchronous request
     /// responders are oneshot channel, which are not dedicated resource for channel
-    pub responders: HashMap<&'static MethodHeader, oneshot::Sender<IncomingMessage>>,
+    pub responders: HashMap<&'static MethodHeader, oneshot::Sender<IncomingResponse>>,
 
     /// connection's default channel does not have dispatcher
     /// each channel has one and only one dispatcher
@@ -81,14 +81,35 @@ impl ChannelManager {
 
     /// remove channel resource, when channel to be closed
     pub fn remove_resource(&mut self, channel_id: &AmqpChannelId) -> Option<ChannelResource> {
-        assert!(
-            self.channel_id_repo.release(*channel_id),
-            "release a free id, implementation error"
-        );
+        // the id is already free if channel has been removed, e.g. closed by both peers
+        // at the same time, then nothing to remove.
+        if !self.channel_id_repo.release(*channel_id) {
+            return None;
+        }
         // remove responder means channel is to be  closed
         self.resource.remove(channel_id)
     }
 
+    /// Remove all responders of all channels, when connection is to be closed.
+    pub fn remove_all_responders(&mut self) -> Vec<oneshot::Sender<IncomingResponse>> {
+        self.resource
+            .values_mut()
+            .flat_map(|resource| resource.responders.drain().map(|(_, responder)| responder))
+            .collect()
+    }
+
+    /// Drop the dispatcher of a channel whose open request has been cancelled.
+    /// The resource is kept to reserve the channel id until server confirms close of the channel.
+    pub fn abandon_resource(&mut self, channel_id: &AmqpChannelId) {
+        if let Some(resource) = self.resource.get_mut(channel_id) {
+            resource.dispatcher.take();
+        }
+    }
+
+    pub fn contains_resource(&self, channel_id: &AmqpChannelId) -> bool {
+        self.resource.contains_key(channel_id)
+    }
+
     pub fn get_dispatcher(
         &self,
         channel_id: &AmqpChannelId,
@@ -100,8 +121,8 @@ impl ChannelManager {
         &mut self,
         channel_id: &AmqpChannelId,
         method_header: &'static MethodHeader,
-        responder: oneshot::Sender<IncomingMessage>,
-    ) -> Option<oneshot::Sender<IncomingMessage>> {
+        responder: oneshot::Sender<IncomingResponse>,
+    ) -> Option<oneshot::Sender<IncomingResponse>> {
         self.resource
             .get_mut(channel_id)?
             .responders
@@ -112,7 +133,7 @@ impl ChannelManager {
         &mut self,
         channel_id: &AmqpChannelId,
         method_header: &'static MethodHeader,
-    ) -> Option<oneshot::Sender<IncomingMessage>> {
+    ) -> Option<oneshot::Sender<IncomingResponse>> {
         self.resource
             .get_mut(channel_id)?
             .responders
@@ -22,19 +22,26 @@ pub type OutgoingMessage = (AmqpChannelId, Frame);
 
 pub(crate) type IncomingMessage = Frame;
 
+/// Response to a synchronous request, or the error which aborts the request.
+pub(crate) type IncomingResponse = std::result::Result<IncomingMessage, crate::api::error::Error>;
+
 pub(crate) struct RegisterChannelResource {
     /// If None, `net` handler will allocate a channel id for client
     pub channel_id: Option<AmqpChannelId>,
     /// send `None` to client if `net` handler fail to allocate a channel id
     pub acker: oneshot::Sender<Option<AmqpChannelId>>,
     pub resource: ChannelResource,
+    /// If Some, `net` handler will send request to open the channel once the resource is registered,
+    /// and forward the `open-ok` response to it
+    pub open_responder: Option<oneshot::Sender<IncomingResponse>>,
 }
 
 pub(crate) struct RegisterResponder {
     pub channel_id: AmqpChannelId,
     pub method_header: &'static MethodHeader,
-    pub responder: oneshot::Sender<IncomingMessage>,
-    pub acker: oneshot::Sender<()>,
+    pub responder: oneshot::Sender<IncomingResponse>,
+    /// request sent to server by `net` handler once the responder is registered
+    pub request: Frame,
 }
 
 pub(crate) struct RegisterConnectionCallback {
@@ -11,8 +11,16 @@ use tokio::{
 use tracing::{debug, error, info, trace, warn};
 
 use crate::{
-    api::{callbacks::ConnectionCallback, connection::Connection},
-    frame::{CloseOk, Frame, DEFAULT_CONN_CHANNEL},
+    api::{
+        callbacks::ConnectionCallback,
+        connection::{CloseReason, Connection},
+        error::Error as ApiError,
+        events::ConnectionEvent,
+    },
+    frame::{
+        Close, CloseChannel, CloseChannelOk, CloseOk, Frame, OpenChannel, OpenChannelOk,
+        CHANNEL_ERROR, DEFAULT_CONN_CHANNEL, FRAME_ERROR, UNEXPECTED_FRAME,
+    },
 };
 
 use super::{
@@ -48,6 +56,10 @@ pub(crate) struct ReaderHandler {
     /// send `true` if due to network I/O failure
     /// send `false` if other reasons
     shutdown_notifier: broadcast::Sender<bool>,
+
+    /// `true` if client is closing the connection due to protocol error,
+    /// then frames are discarded until server responds `close-ok`.
+    closing: bool,
 }
 
 impl ReaderHandler {
@@ -67,13 +79,88 @@ impl ReaderHandler {
             callback: None,
             channel_manager: ChannelManager::new(channel_max),
             shutdown_notifier,
+            closing: false,
+        }
+    }
+
+    /// Close the connection because server violates the protocol.
+    ///
+    /// Pending requests are aborted with [`ApiError::ProtocolError`] and the `close` callback is
+    /// invoked with the reply code and text sent to server.
+    async fn close_on_protocol_error(&mut self, reply_code: ShortUint, reply_text: String) {
+        #[cfg(feature = "traces")]
+        error!(
+            "close connection {} due to protocol error, '{}: {}'",
+            self.amqp_connection, reply_code, reply_text
+        );
+        self.closing = true;
+        self.amqp_connection
+            .notify_closed(CloseReason::ProtocolError {
+                reply_code,
+                reply_text: reply_text.clone(),
+            });
+
+        let reason = format!(
+            "connection {} is closed by client, '{}: {}'",
+            self.amqp_connection, reply_code, reply_text
+        );
+        // requests may have been cancelled, no one to notify then
+        for responder in self.channel_manager.remove_all_responders() {
+            responder
+                .send(Err(ApiError::ProtocolError(reason.clone())))
+                .ok();
+        }
+        if let Some(ref mut callback) = self.callback {
+            let close = Close::new(reply_code, &reply_text);
+            if let Err(_err) = callback.close(&self.amqp_connection, close).await {
+                #[cfg(feature = "traces")]
+                error!(
+                    "close callback error on connection {}, cause: {}",
+                    self.amqp_connection, _err
+                );
+            }
+        }
+        let close = Close::new(reply_code, &reply_text).into_frame();
+        if let Err(_err) = self.outgoing_tx.send((DEFAULT_CONN_CHANNEL, close)).await {
+            #[cfg(feature = "traces")]
+            error!(
+                "failed to send close request of connection {}, cause: {}",
+                self.amqp_connection, _err
+            );
         }
     }
 
+    /// Handle frame received when client is closing the connection due to protocol error.
+    async fn handle_frame_on_closing(&mut self, frame: Frame) -> Result<(), Error> {
+        match frame {
+            Frame::CloseOk(..) => self.amqp_connection.set_is_open(false),
+            // server may close the connection at the same time, respond to it
+            Frame::Close(..) => {
+                self.amqp_connection.set_is_open(false);
+                self.outgoing_tx
+                    .send((DEFAULT_CONN_CHANNEL, CloseOk.into_frame()))
+                    .await?;
+                // Try to yield for last sent message to be scheduled.
+                yield_now().await;
+            }
+            _frame => {
+                #[cfg(feature = "traces")]
+                debug!(
+                    "discard {} on closing connection {}",
+                    _frame, self.amqp_connection
+                );
+            }
+        }
+        Ok(())
+    }
+
     /// If OK, user can continue to handle frame
     /// If NOK, user should stop consuming frame
     /// TODO: implement as Iterator, then user do not need to care about the error
     async fn handle_frame(&mut self, channel_id: AmqpChannelId, frame: Frame) -> Result<(), Error> {
+        if self.closing {
+            return self.handle_frame_on_closing(frame).await;
+        }
         // handle only connection level frame,
         // channel level frames are forwarded to corresponding channel dispatcher
         match frame {
@@ -87,19 +174,35 @@ impl ReaderHandler {
 
             // Method frames for synchronous response
             Frame::OpenChannelOk(method_header, open_channel_ok) => {
-                let responder = self
+                match self
                     .channel_manager
                     .remove_responder(&channel_id, method_header)
-                    .expect("responder must be registered");
-
-                responder
-                    .send(open_channel_ok.into_frame())
-                    .map_err(|err_frame| {
-                        Error::SyncChannel(format!(
-                            "failed to forward {} to connection {}",
-                            err_frame, self.amqp_connection
-                        ))
-                    })
+                {
+                    Some(responder) => {
+                        if responder.send(Ok(open_channel_ok.into_frame())).is_err() {
+                            // the open request is cancelled, close the channel which is
+                            // already opened on server side
+                            #[cfg(feature = "traces")]
+                            warn!(
+                                "open of channel {} is cancelled, close it on connection {}",
+                                channel_id, self.amqp_connection
+                            );
+                            self.channel_manager.abandon_resource(&channel_id);
+                            self.outgoing_tx
+                                .send((channel_id, CloseChannel::default().into_frame()))
+                                .await?;
+                        }
+                        Ok(())
+                    }
+                    None => {
+                        self.close_on_protocol_error(
+                            UNEXPECTED_FRAME,
+                            format!("unexpected open-ok on channel {}", channel_id),
+                        )
+                        .await;
+                        Ok(())
+                    }
+                }
             }
             Frame::CloseOk(method_header, close_ok) => {
                 self.amqp_connection.set_is_open(false);
@@ -108,9 +211,16 @@ impl ReaderHandler {
                     .channel_manager
                     .remove_responder(&channel_id, method_header)
                 {
-                    Some(responder) => responder
-                        .send(close_ok.into_frame())
-                        .map_err(|response| Error::SyncChannel(response.to_string()))?,
+                    Some(responder) => {
+                        if responder.send(Ok(close_ok.into_frame())).is_err() {
+                            // the close request is cancelled, connection is closed anyway
+                            #[cfg(feature = "traces")]
+                            debug!(
+                                "close-ok of connection {} is discarded, close request is cancelled",
+                                self.amqp_connection
+                            );
+                        }
+                    }
                     None => {
                         #[cfg(feature = "traces")]
                         warn!(
@@ -131,6 +241,11 @@ impl ReaderHandler {
             // Method frames of asynchronous request
             // Server request to close connection
             Frame::Close(_, close) => {
+                self.amqp_connection
+                    .notify_closed(CloseReason::ServerInitiated {
+                        reply_code: close.reply_code(),
+                        reply_text: close.reply_text().clone(),
+                    });
                 if let Some(ref mut callback) = self.callback {
                     if let Err(err) = callback.close(&self.amqp_connection, close).await {
                         #[cfg(feature = "traces")]
@@ -164,10 +279,12 @@ impl ReaderHandler {
             }
 
             Frame::Blocked(_, blocked) => {
+                let reason: String = blocked.reason.into();
+                self.amqp_connection.set_blocked(Some(reason.clone()));
+                self.amqp_connection
+                    .publish_event(ConnectionEvent::Blocked(reason.clone()));
                 if let Some(ref mut callback) = self.callback {
-                    callback
-                        .blocked(&self.amqp_connection, blocked.reason.into())
-                        .await;
+                    callback.blocked(&self.amqp_connection, reason).await;
                 } else {
                     #[cfg(feature = "traces")]
                     error!(
@@ -178,6 +295,9 @@ impl ReaderHandler {
                 Ok(())
             }
             Frame::Unblocked(_, _unblocked) => {
+                self.amqp_connection.set_blocked(None);
+                self.amqp_connection
+                    .publish_event(ConnectionEvent::Unblocked);
                 if let Some(ref mut callback) = self.callback {
                     callback.unblocked(&self.amqp_connection).await;
                 } else {
@@ -194,14 +314,48 @@ impl ReaderHandler {
                 let dispatcher = self.channel_manager.get_dispatcher(&channel_id);
                 match dispatcher {
                     Some(dispatcher) => {
-                        dispatcher.send(frame)?;
+                        // dispatcher may have exited after channel is closed, discard the frame
+                        if let Err(_err) = dispatcher.send(frame) {
+                            #[cfg(feature = "traces")]
+                            warn!(
+                                "discard {} of closed channel {} on connection {}",
+                                _err.0, channel_id, self.amqp_connection
+                            );
+                        }
+                        Ok(())
+                    }
+                    // channel may have been deregistered after close
+                    None if matches!(frame, Frame::CloseChannelOk(..)) => {
+                        // complete close of channel whose open request was cancelled
+                        if channel_id != DEFAULT_CONN_CHANNEL {
+                            self.channel_manager.remove_resource(&channel_id);
+                        }
+                        Ok(())
+                    }
+                    // channel whose open request was cancelled, wait for its close-ok
+                    None if channel_id != DEFAULT_CONN_CHANNEL
+                        && self.channel_manager.contains_resource(&channel_id) =>
+                    {
+                        if let Frame::CloseChannel(..) = frame {
+                            self.channel_manager.remove_resource(&channel_id);
+                            self.outgoing_tx
+                                .send((channel_id, CloseChannelOk.into_frame()))
+                                .await?;
+                        }
                         Ok(())
                     }
                     None => {
-                        unreachable!(
-                            "dispatcher must be registered for channel {} of {}",
-                            channel_id, self.amqp_connection,
-                        );
+                        let reply_code = if channel_id == DEFAULT_CONN_CHANNEL {
+                            UNEXPECTED_FRAME
+                        } else {
+                            CHANNEL_ERROR
+                        };
+                        self.close_on_protocol_error(
+                            reply_code,
+                            format!("unexpected {} on channel {}", frame, channel_id),
+                        )
+                        .await;
+                        Ok(())
                     }
                 }
             }
@@ -223,14 +377,31 @@ impl ReaderHandler {
                         None => {
                             // should never happen because `ReadHandler` holds
                             // a `Connection` itself
-                            unreachable!("connection command channel is closed, {}", self.amqp_connection)
+                            #[cfg(feature="traces")]
+                            error!("connection command channel is closed, {}", self.amqp_connection);
+                            break;
                         },
                         Some(v) => v,
                     };
                     match command {
                         ConnManagementCommand::RegisterChannelResource(cmd) => {
                             let id = self.channel_manager.insert_resource(cmd.channel_id, cmd.resource);
-                            cmd.acker.send(id).expect("ack to command RegisterChannelResource must succeed");
+                            if cmd.acker.send(id).is_err() {
+                                // the request is cancelled, release the channel id
+                                if let Some(id) = id {
+                                    self.channel_manager.remove_resource(&id);
+                                }
+                            } else if let (Some(id), Some(responder)) = (id, cmd.open_responder) {
+                                // register responder before sending request, so that
+                                // cancelling the caller can never miss the response
+                                self.channel_manager.insert_responder(&id, OpenChannelOk::header(), responder);
+                                if let Err(err) = self.outgoing_tx.send((id, OpenChannel::new().into_frame())).await {
+                                    if let Some(responder) = self.channel_manager.remove_responder(&id, OpenChannelOk::header()) {
+                                        responder.send(Err(err.into())).ok();
+                                    }
+                                    self.channel_manager.remove_resource(&id);
+                                }
+                            }
                             #[cfg(feature="traces")]
                             debug!("register channel resource on connection {}", self.amqp_connection);
 
@@ -241,8 +412,14 @@ impl ReaderHandler {
                             debug!("deregister channel {} from connection {}", channel_id, self.amqp_connection);
                         },
                         ConnManagementCommand::RegisterResponder(cmd) => {
+                            // register responder before sending request, so that
+                            // cancelling the caller can never miss the response
                             self.channel_manager.insert_responder(&cmd.channel_id, cmd.method_header, cmd.responder);
-                            cmd.acker.send(()).expect("ack to command RegisterResponder must succeed");
+                            if let Err(err) = self.outgoing_tx.send((cmd.channel_id, cmd.request)).await {
+                                if let Some(responder) = self.channel_manager.remove_responder(&cmd.channel_id, cmd.method_header) {
+                                    responder.send(Err(err.into())).ok();
+                                }
+                            }
                         },
                         ConnManagementCommand::RegisterConnectionCallback(cmd) => {
                             self.callback.replace(cmd.callback);
@@ -263,6 +440,7 @@ impl ReaderHandler {
                             if let Err(err) = self.handle_frame(channel_id, frame).await {
                                 // notifiy network failure
                                 is_network_failure = true;
+                                self.amqp_connection.notify_closed(CloseReason::NetworkIo(err.to_string()));
                                 #[cfg(feature="traces")]
                                 error!("socket will be closed due to error of handling frame, cause: {}", err);
                                 break;
@@ -274,9 +452,15 @@ impl ReaderHandler {
                                 break;
                             }
                         },
+                        // malformed frame received, best effort to notify server before shutdown
+                        Err(err @ (Error::Framing(_) | Error::Serde(_))) => {
+                            self.close_on_protocol_error(FRAME_ERROR, err.to_string()).await;
+                            break;
+                        },
                         Err(err) => {
                             // notifiy network failure
                             is_network_failure = true;
+                            self.amqp_connection.notify_closed(CloseReason::NetworkIo(err.to_string()));
                             #[cfg(feature="traces")]
                             error!("socket will be closed due to failure of reading frame, cause: {}", err);
                             break;
@@ -296,6 +480,7 @@ impl ReaderHandler {
                         if heartbeat_miss >= MAX_HEARTBEAT_MISS {
                             // Shutdown connection due to heartbeat timeout
                             is_network_failure = true;
+                            self.amqp_connection.notify_closed(CloseReason::HeartbeatTimeout);
                             break;
                         }
                     }
@@ -306,6 +491,14 @@ impl ReaderHandler {
             }
         }
         self.amqp_connection.set_is_open(false);
+        // in case the reason has not been reported
+        self.amqp_connection.notify_closed(CloseReason::NetworkIo(
+            "socket I/O handlers are shut down".to_owned(),
+        ));
+        if let Some(reason) = self.amqp_connection.close_reason() {
+            self.amqp_connection
+                .publish_event(ConnectionEvent::Closed(reason));
+        }
         if self.shutdown_notifier.send(is_network_failure).is_err() {
             #[cfg(feature = "traces")]
             error!("failed to notify shutdown for {}", self.amqp_connection);
@@ -314,3 +507,114 @@ impl ReaderHandler {
         // all tasks which have `subscribed` to `shutdown_notifier` will be notified
     }
 }
+
+/////////////////////////////////////////////////////////////////////////////
+#[cfg(test)]
+mod tests {
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use crate::{
+        api::error::Error,
+        connection::{Connection, OpenConnectionArguments},
+        frame::{FRAME_END, FRAME_ERROR, FRAME_METHOD},
+        test_utils::setup_logging,
+    };
+
+    /// Write a method frame of a minimal server.
+    async fn write_method(
+        stream: &mut TcpStream,
+        channel_id: u16,
+        class_id: u16,
+        method_id: u16,
+        args: &[u8],
+    ) {
+        let mut frame = vec![FRAME_METHOD];
+        frame.extend_from_slice(&channel_id.to_be_bytes());
+        frame.extend_from_slice(&(args.len() as u32 + 4).to_be_bytes());
+        frame.extend_from_slice(&class_id.to_be_bytes());
+        frame.extend_from_slice(&method_id.to_be_bytes());
+        frame.extend_from_slice(args);
+        frame.push(FRAME_END);
+        stream.write_all(&frame).await.unwrap();
+    }
+
+    /// Read a method frame sent by client, returns channel id, class id, method id and arguments.
+    async fn read_method(stream: &mut TcpStream) -> (u16, u16, u16, Vec<u8>) {
+        let mut header = [0u8; 7];
+        stream.read_exact(&mut header).await.unwrap();
+        assert_eq!(FRAME_METHOD, header[0]);
+        let channel_id = u16::from_be_bytes([header[1], header[2]]);
+        let size = u32::from_be_bytes([header[3], header[4], header[5], header[6]]) as usize;
+        let mut payload = vec![0u8; size + 1];
+        stream.read_exact(&mut payload).await.unwrap();
+        let class_id = u16::from_be_bytes([payload[0], payload[1]]);
+        let method_id = u16::from_be_bytes([payload[2], payload[3]]);
+        (channel_id, class_id, method_id, payload[4..size].to_vec())
+    }
+
+    /// Run a server which opens the connection and a channel, then responds a malformed frame
+    /// to the first request on the channel.
+    ///
+    /// Returns the reply code of `close` sent by client.
+    async fn run_malformed_server(listener: TcpListener) -> u16 {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut protocol_header = [0u8; 8];
+        stream.read_exact(&mut protocol_header).await.unwrap();
+        // start: version, empty server properties, mechanisms and locales
+        let mut start = vec![0, 9, 0, 0, 0, 0];
+        for value in ["PLAIN", "en_US"] {
+            start.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            start.extend_from_slice(value.as_bytes());
+        }
+        write_method(&mut stream, 0, 10, 10, &start).await;
+        assert_eq!((10, 11), {
+            let (_, class_id, method_id, _) = read_method(&mut stream).await;
+            (class_id, method_id)
+        });
+        // tune: channel max, frame max and heartbeat
+        let mut tune = vec![];
+        tune.extend_from_slice(&2047u16.to_be_bytes());
+        tune.extend_from_slice(&131072u32.to_be_bytes());
+        tune.extend_from_slice(&60u16.to_be_bytes());
+        write_method(&mut stream, 0, 10, 30, &tune).await;
+        // tune-ok and open
+        read_method(&mut stream).await;
+        read_method(&mut stream).await;
+        write_method(&mut stream, 0, 10, 41, &[0]).await;
+        // channel.open
+        let (channel_id, class_id, method_id, _) = read_method(&mut stream).await;
+        assert_eq!((20, 10), (class_id, method_id));
+        write_method(&mut stream, channel_id, 20, 11, &[0, 0, 0, 0]).await;
+        // channel.flow, respond a frame with invalid frame end
+        let (_, class_id, method_id, _) = read_method(&mut stream).await;
+        assert_eq!((20, 20), (class_id, method_id));
+        stream
+            .write_all(&[FRAME_METHOD, 0, 1, 0, 0, 0, 4, 0, 20, 0, 21, 0])
+            .await
+            .unwrap();
+        // connection.close
+        let (channel_id, class_id, method_id, args) = read_method(&mut stream).await;
+        assert_eq!((0, 10, 50), (channel_id, class_id, method_id));
+        u16::from_be_bytes([args[0], args[1]])
+    }
+
+    #[tokio::test]
+    async fn test_close_on_malformed_frame() {
+        setup_logging();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = tokio::spawn(run_malformed_server(listener));
+
+        let args = OpenConnectionArguments::new("127.0.0.1", port, "user", "bitnami");
+        let connection = Connection::open(&args).await.unwrap();
+        let channel = connection.open_channel(None).await.unwrap();
+        match channel.flow(false).await {
+            Err(Error::ProtocolError(_)) => {}
+            other => panic!("expect protocol error, but got {:?}", other),
+        }
+        assert_eq!(FRAME_ERROR, server.await.unwrap());
+    }
+}
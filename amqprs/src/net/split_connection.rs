@@ -6,10 +6,11 @@ use amqp_serde::{
     to_buffer,
     types::{AmqpChannelId, LongUint},
 };
-use bytes::{Buf, BufMut, BytesMut};
+use bytes::{buf::UninitSlice, Buf, BufMut, BytesMut};
 use serde::Serialize;
 use std::{
     io::{self, Cursor},
+    ops::{Deref, DerefMut},
     pin::Pin,
 };
 use tokio::{
@@ -20,6 +21,7 @@ use tokio::{
 use tokio_rustls::{client::TlsStream, rustls, TlsConnector};
 #[cfg(feature = "traces")]
 use tracing::trace;
+use zeroize::Zeroize;
 
 use super::Error;
 type Result<T> = std::result::Result<T, Error>;
@@ -182,6 +184,14 @@ impl SplitConnection {
         self.writer.write_frame(channel, frame, frame_max).await
     }
 
+    pub async fn write_sensitive_frame(
+        &mut self,
+        channel: AmqpChannelId,
+        frame: Frame,
+    ) -> Result<usize> {
+        self.writer.write_sensitive_frame(channel, frame).await
+    }
+
     pub async fn read_frame(&mut self) -> Result<ChannelFrame> {
         self.reader.read_frame().await
     }
@@ -203,29 +213,7 @@ impl BufIoWriter {
         channel: AmqpChannelId,
         frame: Frame,
     ) -> Result<()> {
-        // there can be data unsent in buffer
-        let start_index = self.buffer.len();
-
-        // reserve bytes for frame header, which to be updated after encoding payload
-        let header = FrameHeader {
-            frame_type: frame.get_frame_type(),
-            channel,
-            payload_size: 0,
-        };
-        to_buffer(&header, &mut self.buffer).unwrap();
-
-        // encode payload
-        let payload_size = to_buffer(&frame, &mut self.buffer)?;
-
-        // update frame's payload size
-        for (i, v) in (payload_size as u32).to_be_bytes().iter().enumerate() {
-            let p = self.buffer.get_mut(i + 3 + start_index).unwrap();
-            *p = *v;
-        }
-
-        // encode frame end byte
-        self.buffer.put_u8(FRAME_END);
-        Ok(())
+        serialize_frame(&mut self.buffer, channel, frame)
     }
 
     /// specific version for serialize content body frames
@@ -308,6 +296,20 @@ impl BufIoWriter {
         Ok(len)
     }
 
+    // write a frame which contains secrets, e.g. `StartOk`,
+    // the frame is serialized into its own buffer, which is zeroized after flush.
+    pub async fn write_sensitive_frame(
+        &mut self,
+        channel: AmqpChannelId,
+        frame: Frame,
+    ) -> Result<usize> {
+        let mut buffer = SensitiveBuffer::with_capacity(DEFAULT_IO_BUFFER_SIZE);
+        // `Frame` is dropped after serialization, its secrets are zeroized by its own `Drop`
+        serialize_frame(&mut buffer, channel, frame)?;
+        self.stream.write_all(&buffer).await?;
+        Ok(buffer.len())
+    }
+
     // // The socket connection will be shutdown if writer half is shutdown
     pub async fn close(mut self) -> Result<()> {
         self.stream.shutdown().await?;
@@ -370,13 +372,121 @@ impl BufIoReader {
 /////////////////////////////////////////////////////////////////////////////
 
 /////////////////////////////////////////////////////////////////////////////
+fn serialize_frame<B>(buffer: &mut B, channel: AmqpChannelId, frame: Frame) -> Result<()>
+where
+    B: BufMut + DerefMut<Target = [u8]>,
+{
+    // there can be data unsent in buffer
+    let start_index = buffer.len();
+
+    // reserve bytes for frame header, which to be updated after encoding payload
+    let header = FrameHeader {
+        frame_type: frame.get_frame_type(),
+        channel,
+        payload_size: 0,
+    };
+    to_buffer(&header, buffer).unwrap();
+
+    // encode payload
+    let payload_size = to_buffer(&frame, buffer)?;
+
+    // update frame's payload size
+    for (i, v) in (payload_size as u32).to_be_bytes().iter().enumerate() {
+        buffer[i + 3 + start_index] = *v;
+    }
+
+    // encode frame end byte
+    buffer.put_u8(FRAME_END);
+    Ok(())
+}
+
+/// Write buffer of secrets, which zeroizes its old allocation when it grows and
+/// its allocation when dropped, so that no copy of the secrets is left behind.
+struct SensitiveBuffer(Vec<u8>);
+
+impl SensitiveBuffer {
+    fn with_capacity(capacity: usize) -> Self {
+        Self(Vec::with_capacity(capacity))
+    }
+
+    fn reserve(&mut self, additional: usize) {
+        if self.0.capacity() - self.0.len() >= additional {
+            return;
+        }
+        let capacity = (self.0.len() + additional).max(self.0.capacity() * 2);
+        let mut grown = Vec::with_capacity(capacity);
+        grown.extend_from_slice(&self.0);
+        self.0.zeroize();
+        self.0 = grown;
+    }
+}
+
+impl Deref for SensitiveBuffer {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl DerefMut for SensitiveBuffer {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        &mut self.0
+    }
+}
+
+// SAFETY: same as `BufMut` of `Vec<u8>`, except that it grows by `reserve`.
+unsafe impl BufMut for SensitiveBuffer {
+    fn remaining_mut(&self) -> usize {
+        isize::MAX as usize - self.0.len()
+    }
+
+    unsafe fn advance_mut(&mut self, cnt: usize) {
+        let len = self.0.len() + cnt;
+        assert!(len <= self.0.capacity(), "advance out of bounds");
+        self.0.set_len(len);
+    }
+
+    fn chunk_mut(&mut self) -> &mut UninitSlice {
+        if self.0.capacity() == self.0.len() {
+            self.reserve(64);
+        }
+        let len = self.0.len();
+        let cap = self.0.capacity();
+        // SAFETY: the slice is the spare capacity of the vector.
+        unsafe { UninitSlice::from_raw_parts_mut(self.0.as_mut_ptr().add(len), cap - len) }
+    }
+
+    fn put_slice(&mut self, src: &[u8]) {
+        self.reserve(src.len());
+        self.0.extend_from_slice(src);
+    }
+}
+
+impl Drop for SensitiveBuffer {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use super::SplitConnection;
+    use super::{serialize_frame, SensitiveBuffer, SplitConnection};
     use crate::{frame::*, test_utils::setup_logging};
     use amqp_serde::types::AmqpPeerProperties;
+    use bytes::BytesMut;
     use tokio::sync::mpsc;
 
+    #[test]
+    fn test_sensitive_buffer() {
+        // grows from a tiny capacity
+        let mut sensitive = SensitiveBuffer::with_capacity(4);
+        serialize_frame(&mut sensitive, 0, StartOk::default().into_frame()).unwrap();
+        let mut buffer = BytesMut::new();
+        serialize_frame(&mut buffer, 0, StartOk::default().into_frame()).unwrap();
+        assert_eq!(&buffer[..], &sensitive[..]);
+    }
+
     #[tokio::test]
     async fn test_open_amqp_connection() {
         setup_logging();
@@ -417,7 +527,7 @@ mod test {
         let start_ok = StartOk::new(
             AmqpPeerProperties::new(),
             "RABBIT-CR-DEMO".try_into().unwrap(),
-            "user".try_into().unwrap(),
+            b"user".to_vec().try_into().unwrap(),
             "en_US".try_into().unwrap(),
         );
         tx_req
@@ -1,7 +1,6 @@
 use amqp_serde::types::ShortUint;
 use tokio::{
     sync::{broadcast, mpsc},
-    task::yield_now,
     time,
 };
 #[cfg(feature = "traces")]
@@ -76,11 +75,20 @@ impl WriterHandler {
                         debug!("sent heartbeat over connection {}", self.amqp_connection,);
                     }
                 }
-                _ = self.shutdown.recv() => {
+                is_network_failure = self.shutdown.recv() => {
                     #[cfg(feature="tracing")]
                     info!("received shutdown notification for connection {}", self.amqp_connection);
-                    // try to give last chance for last message.
-                    yield_now().await;
+                    // write the frames queued before shutdown, e.g. `close` due to protocol error,
+                    // unless the network has failed
+                    if matches!(is_network_failure, Ok(false)) {
+                        while let Ok((channel_id, frame)) = self.outgoing_rx.try_recv() {
+                            if let Err(err) = self.stream.write_frame(channel_id, frame, self.amqp_connection.frame_max()).await {
+                                #[cfg(feature="tracing")]
+                                error!("failed to send frame over connection {}, cause: {}", self.amqp_connection, err);
+                                break;
+                            }
+                        }
+                    }
                     break;
                 }
                 else => {